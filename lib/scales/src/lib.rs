@@ -7,12 +7,16 @@
 #[macro_use]
 extern crate alloc;
 
+#[cfg(feature = "json")]
+mod schema;
 #[cfg(feature = "experimental-serializer")]
 mod serializer;
 mod value;
 
 pub use bytes::Bytes;
 #[cfg(feature = "json")]
+pub use schema::{json_schema, JsonSchema};
+#[cfg(feature = "json")]
 pub use serde_json::Value as JsonValue;
 #[cfg(feature = "experimental-serializer")]
 pub use serializer::{to_bytes, to_bytes_with_info, to_vec, to_vec_with_info, Serializer};
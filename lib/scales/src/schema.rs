@@ -0,0 +1,375 @@
+//! [JSON Schema](https://json-schema.org) of the JSON input the [`Serializer`](crate::Serializer)
+//! accepts for a type of the registry.
+//!
+//! - structs are objects and tuples are arrays, the serializer takes the values of an
+//!   object in order so properties have to be given in the order of the schema
+//! - enums are `oneOf` their variants, a unit variant is its name and the others
+//!   an object with the variant name as its only key
+//! - `Option` is nullable
+//! - byte sequences and arrays are `0x` hex strings(with the `hex` feature) or arrays of numbers
+//! - integers and compact numbers are numbers or numeric strings, 64 and 128 bit
+//!   numbers don't fit in a JSON number without losing precision
+//!
+//! Recursive types are defined once under `$defs` and referenced by their type id.
+use crate::JsonValue;
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use scale_info::{
+    form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive as Primitive,
+};
+use serde_json::json;
+
+type TypeId = u32;
+type Type = scale_info::Type<PortableForm>;
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON Schema document of the given type
+pub fn json_schema(registry: &PortableRegistry, ty: TypeId) -> JsonValue {
+    let mut schema = JsonSchema::new(registry);
+    let root = schema.of_type(ty);
+    schema.document(root)
+}
+
+/// Builds the schemas of several types of a registry sharing the definitions
+/// of recursive types, e.g. the schema of the arguments of a call.
+pub struct JsonSchema<'reg> {
+    registry: &'reg PortableRegistry,
+    visiting: Vec<TypeId>,
+    recursive: BTreeSet<TypeId>,
+    defs: BTreeMap<String, JsonValue>,
+}
+
+impl<'reg> JsonSchema<'reg> {
+    pub fn new(registry: &'reg PortableRegistry) -> Self {
+        JsonSchema {
+            registry,
+            visiting: vec![],
+            recursive: BTreeSet::new(),
+            defs: BTreeMap::new(),
+        }
+    }
+
+    /// Wraps a schema built with this builder in a document with the definitions it refers to
+    pub fn document(self, schema: JsonValue) -> JsonValue {
+        let mut doc = json!({ "$schema": DIALECT });
+        if let JsonValue::Object(schema) = schema {
+            doc.as_object_mut().expect("object").extend(schema);
+        } else {
+            doc["allOf"] = json!([schema]);
+        }
+        if !self.defs.is_empty() {
+            doc["$defs"] = self.defs.into_iter().collect();
+        }
+        doc
+    }
+
+    /// Schema of a type of the registry
+    pub fn of_type(&mut self, id: TypeId) -> JsonValue {
+        let key = id.to_string();
+        if self.defs.contains_key(&key) || self.visiting.contains(&id) {
+            self.recursive.insert(id);
+            return json!({ "$ref": format!("#/$defs/{}", key) });
+        }
+        let Some(ty) = self.registry.resolve(id) else {
+            return JsonValue::Bool(false);
+        };
+
+        self.visiting.push(id);
+        let schema = self.build(ty);
+        self.visiting.pop();
+
+        if self.recursive.contains(&id) {
+            self.defs.insert(key.clone(), schema);
+            return json!({ "$ref": format!("#/$defs/{}", key) });
+        }
+        schema
+    }
+
+    /// Schema of a list of fields like the ones of a struct or an enum variant
+    pub fn of_fields(&mut self, fields: &[Field<PortableForm>]) -> JsonValue {
+        match fields {
+            [] => json!({ "type": "null" }),
+            // newtypes are transparent
+            [f] if f.name.is_none() => self.of_type(f.ty.id),
+            [f, ..] if f.name.is_none() => {
+                let ids = fields.iter().map(|f| f.ty.id).collect::<Vec<_>>();
+                self.tuple(&ids)
+            }
+            _ => {
+                let mut properties = serde_json::Map::new();
+                for f in fields {
+                    let mut schema = self.of_type(f.ty.id);
+                    let docs = docs(&f.docs);
+                    if !docs.is_empty() {
+                        if let JsonValue::Object(s) = &mut schema {
+                            s.insert("description".into(), docs.into());
+                        }
+                    }
+                    let name = f.name.as_deref().unwrap_or_default();
+                    properties.insert(name.into(), schema);
+                }
+                let required = properties.keys().cloned().collect::<Vec<_>>();
+                json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                    "additionalProperties": false,
+                })
+            }
+        }
+    }
+
+    fn build(&mut self, ty: &Type) -> JsonValue {
+        let name = ty.path.segments.last().map(AsRef::as_ref);
+        let mut schema = match &ty.type_def {
+            TypeDef::Composite(c) if name == Some("BTreeMap") => {
+                // maps are a sequence of key-value tuples, keys are object properties
+                let value = self
+                    .registry
+                    .resolve(c.fields[0].ty.id)
+                    .and_then(|seq| match &seq.type_def {
+                        TypeDef::Sequence(s) => self.registry.resolve(s.type_param.id),
+                        _ => None,
+                    })
+                    .and_then(|kv| match &kv.type_def {
+                        TypeDef::Tuple(t) => t.fields.last().map(|v| v.id),
+                        _ => None,
+                    });
+                let values = value.map_or(JsonValue::Bool(false), |v| self.of_type(v));
+                return json!({ "type": "object", "additionalProperties": values });
+            }
+            TypeDef::Composite(c) => self.of_fields(&c.fields),
+            TypeDef::Variant(v) if name == Some("Option") => {
+                let some = v
+                    .variants
+                    .iter()
+                    .find(|v| v.name == "Some")
+                    .and_then(|v| v.fields.first());
+                let some = some.map_or(JsonValue::Bool(false), |f| self.of_type(f.ty.id));
+                return json!({ "anyOf": [{ "type": "null" }, some] });
+            }
+            TypeDef::Variant(v) => {
+                let variants = v
+                    .variants
+                    .iter()
+                    .map(|v| {
+                        let mut variant = if v.fields.is_empty() {
+                            json!({ "const": v.name })
+                        } else {
+                            let fields = self.of_fields(&v.fields);
+                            json!({
+                                "type": "object",
+                                "properties": { v.name.to_string(): fields },
+                                "required": [v.name],
+                                "additionalProperties": false,
+                            })
+                        };
+                        variant["title"] = json!(v.name);
+                        let docs = docs(&v.docs);
+                        if !docs.is_empty() {
+                            variant["description"] = docs.into();
+                        }
+                        variant
+                    })
+                    .collect::<Vec<_>>();
+                json!({ "oneOf": variants })
+            }
+            TypeDef::Sequence(s) if self.is_u8(s.type_param.id) => bytes(None),
+            TypeDef::Sequence(s) => {
+                json!({ "type": "array", "items": self.of_type(s.type_param.id) })
+            }
+            TypeDef::Array(a) if self.is_u8(a.type_param.id) => bytes(Some(a.len)),
+            TypeDef::Array(a) => json!({
+                "type": "array",
+                "items": self.of_type(a.type_param.id),
+                "minItems": a.len,
+                "maxItems": a.len,
+            }),
+            TypeDef::Tuple(t) if t.fields.is_empty() => return json!({ "type": "null" }),
+            TypeDef::Tuple(t) => {
+                let ids = t.fields.iter().map(|f| f.id).collect::<Vec<_>>();
+                return self.tuple(&ids);
+            }
+            TypeDef::Primitive(p) => return primitive(p),
+            TypeDef::Compact(_) => return big_integer(false),
+            // not supported by the serializer
+            TypeDef::BitSequence(_) => return JsonValue::Bool(false),
+        };
+
+        if let (JsonValue::Object(s), Some(name)) = (&mut schema, name) {
+            s.entry("title").or_insert_with(|| name.into());
+            let docs = docs(&ty.docs);
+            if !docs.is_empty() {
+                s.entry("description").or_insert_with(|| docs.into());
+            }
+        }
+        schema
+    }
+
+    fn tuple(&mut self, ids: &[TypeId]) -> JsonValue {
+        let items = ids.iter().map(|id| self.of_type(*id)).collect::<Vec<_>>();
+        json!({
+            "type": "array",
+            "prefixItems": items,
+            "items": false,
+            "minItems": ids.len(),
+            "maxItems": ids.len(),
+        })
+    }
+
+    fn is_u8(&self, id: TypeId) -> bool {
+        matches!(
+            self.registry.resolve(id).map(|t| &t.type_def),
+            Some(TypeDef::Primitive(Primitive::U8))
+        )
+    }
+}
+
+fn primitive(p: &Primitive) -> JsonValue {
+    // like the big integers, the serializer also takes numbers written as strings
+    let int = |min: i64, max: u64| {
+        let pattern = if min < 0 { "^-?[0-9]+$" } else { "^[0-9]+$" };
+        json!({ "anyOf": [
+            { "type": "integer", "minimum": min, "maximum": max },
+            { "type": "string", "pattern": pattern },
+        ]})
+    };
+    match p {
+        Primitive::Bool => json!({ "type": "boolean" }),
+        Primitive::Str => json!({ "type": "string" }),
+        Primitive::U8 => int(0, u8::MAX.into()),
+        Primitive::U16 => int(0, u16::MAX.into()),
+        Primitive::U32 => int(0, u32::MAX.into()),
+        Primitive::I8 => int(i8::MIN.into(), i8::MAX as u64),
+        Primitive::I16 => int(i16::MIN.into(), i16::MAX as u64),
+        Primitive::I32 => int(i32::MIN.into(), i32::MAX as u64),
+        Primitive::U64 | Primitive::U128 => big_integer(false),
+        Primitive::I64 | Primitive::I128 => big_integer(true),
+        // not supported by the serializer
+        Primitive::Char | Primitive::U256 | Primitive::I256 => JsonValue::Bool(false),
+    }
+}
+
+fn big_integer(signed: bool) -> JsonValue {
+    let (number, pattern) = if signed {
+        (json!({ "type": "integer" }), "^-?[0-9]+$")
+    } else {
+        (json!({ "type": "integer", "minimum": 0 }), "^[0-9]+$")
+    };
+    json!({ "anyOf": [number, { "type": "string", "pattern": pattern }] })
+}
+
+fn bytes(len: Option<u32>) -> JsonValue {
+    let byte = json!({ "type": "integer", "minimum": 0, "maximum": 255 });
+    let mut array = json!({ "type": "array", "items": byte });
+    if let Some(len) = len {
+        array["minItems"] = len.into();
+        array["maxItems"] = len.into();
+    }
+    if cfg!(not(feature = "hex")) {
+        return array;
+    }
+    let pattern = match len {
+        Some(len) => format!("^0x[0-9a-fA-F]{{{}}}$", len * 2),
+        None => "^0x([0-9a-fA-F]{2})*$".into(),
+    };
+    json!({ "anyOf": [{ "type": "string", "pattern": pattern }, array] })
+}
+
+/// Docs of the registry are lines with the leading space of the comment
+fn docs<S: AsRef<str>>(lines: &[S]) -> String {
+    lines
+        .iter()
+        .map(|l| l.as_ref())
+        .map(|l| l.strip_prefix(' ').unwrap_or(l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scale_info::{meta_type, Registry, TypeInfo};
+
+    fn schema_of<T: TypeInfo + 'static>() -> JsonValue {
+        let mut reg = Registry::new();
+        let ty = reg.register_type(&meta_type::<T>()).id;
+        json_schema(&reg.into(), ty)
+    }
+
+    #[test]
+    fn struct_with_enums_and_bytes() {
+        #[allow(dead_code)]
+        #[derive(TypeInfo)]
+        struct Foo {
+            a: Vec<u8>,
+            b: Option<u16>,
+            c: Bar,
+            d: (bool, u64),
+        }
+        #[allow(dead_code)]
+        #[derive(TypeInfo)]
+        enum Bar {
+            A,
+            B([u8; 2]),
+            C { x: String },
+        }
+
+        let schema = schema_of::<Foo>();
+        assert_eq!(schema["$schema"], DIALECT);
+        assert_eq!(schema["title"], "Foo");
+        assert_eq!(schema["required"], json!(["a", "b", "c", "d"]));
+
+        let props = &schema["properties"];
+        assert_eq!(props["a"]["anyOf"][0]["pattern"], "^0x([0-9a-fA-F]{2})*$");
+        assert_eq!(
+            props["b"]["anyOf"],
+            json!([
+                { "type": "null" },
+                { "anyOf": [
+                    { "type": "integer", "minimum": 0, "maximum": 65535 },
+                    { "type": "string", "pattern": "^[0-9]+$" },
+                ]},
+            ])
+        );
+        let bar = &props["c"]["oneOf"];
+        assert_eq!(bar[0], json!({ "const": "A", "title": "A" }));
+        assert_eq!(bar[1]["required"], json!(["B"]));
+        assert_eq!(
+            bar[1]["properties"]["B"]["anyOf"][0]["pattern"],
+            "^0x[0-9a-fA-F]{4}$"
+        );
+        assert_eq!(
+            bar[2]["properties"]["C"]["properties"]["x"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(props["d"]["prefixItems"][0], json!({ "type": "boolean" }));
+        assert_eq!(
+            props["d"]["prefixItems"][1]["anyOf"][1],
+            json!({ "type": "string", "pattern": "^[0-9]+$" })
+        );
+    }
+
+    #[test]
+    fn recursive_types_are_referenced() {
+        #[allow(dead_code)]
+        #[derive(TypeInfo)]
+        struct Node {
+            value: u8,
+            children: Vec<Node>,
+        }
+
+        let schema = schema_of::<Node>();
+        let defs = schema["$defs"].as_object().unwrap();
+        assert_eq!(defs.len(), 1);
+        let (id, node) = defs.iter().next().unwrap();
+        let reference = format!("#/$defs/{}", id);
+        assert_eq!(schema["$ref"], reference);
+        assert_eq!(node["title"], "Node");
+        assert_eq!(node["properties"]["children"]["items"]["$ref"], reference);
+    }
+}
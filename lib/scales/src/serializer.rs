@@ -1,9 +1,12 @@
 use crate::prelude::*;
 use bytes::BufMut;
 use codec::Encode;
-use core::fmt::{self, Debug};
+use core::{
+    convert::TryFrom,
+    fmt::{self, Debug},
+};
 
-use scale_info::{PortableRegistry, TypeInfo};
+use scale_info::PortableRegistry;
 use serde::{ser, Serialize};
 
 use crate::{EnumVariant, SpecificType, TupleOrArray};
@@ -11,9 +14,6 @@ use crate::{EnumVariant, SpecificType, TupleOrArray};
 type TypeId = u32;
 type Result<T> = core::result::Result<T, Error>;
 
-#[derive(TypeInfo)]
-struct Noop;
-
 #[inline]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
@@ -143,10 +143,15 @@ where
         let type_def = self.resolve(ty);
 
         use codec::Compact;
+        let too_big = |_| Error::BadInput(format!("{} doesn't fit the compact type", v));
         let compact_buffer = match type_def {
-            SpecificType::U32 => Compact(v as u32).encode(),
-            SpecificType::U64 => Compact(v as u64).encode(),
+            SpecificType::U8 => Compact(u8::try_from(v).map_err(too_big)?).encode(),
+            SpecificType::U16 => Compact(u16::try_from(v).map_err(too_big)?).encode(),
+            SpecificType::U32 => Compact(u32::try_from(v).map_err(too_big)?).encode(),
+            SpecificType::U64 => Compact(u64::try_from(v).map_err(too_big)?).encode(),
             SpecificType::U128 => Compact(v).encode(),
+            // e.g. Compact<Perbill> is encoded as the compact of its inner number
+            SpecificType::StructNewType(ty) => return self.serialize_compact(ty, v),
             _ => todo!(),
         };
 
@@ -196,14 +201,13 @@ where
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        self.maybe_some()?;
         match self.ty {
             Some(SpecificType::I8) => self.serialize_i8(v as i8)?,
             Some(SpecificType::I16) => self.serialize_i16(v as i16)?,
             Some(SpecificType::I32) => self.serialize_i32(v as i32)?,
-            _ => {
-                self.maybe_some()?;
-                self.out.put_i64_le(v)
-            }
+            Some(SpecificType::I128) => self.out.put_i128_le(v.into()),
+            _ => self.out.put_i64_le(v),
         }
         Ok(())
     }
@@ -236,6 +240,8 @@ where
             Some(SpecificType::U8) => self.serialize_u8(v as u8)?,
             Some(SpecificType::U16) => self.serialize_u16(v as u16)?,
             Some(SpecificType::U32) => self.serialize_u32(v as u32)?,
+            Some(SpecificType::U128) => self.out.put_u128_le(v.into()),
+            Some(SpecificType::I128) => self.out.put_i128_le(v.into()),
             Some(SpecificType::Compact(ty)) => self.serialize_compact(ty, v as u128)?,
             _ => self.out.put_u64_le(v),
         }
@@ -304,6 +310,10 @@ where
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
+        // a `null` given for an Option is None
+        if matches!(&self.ty, Some(SpecificType::Variant(name, _, _)) if name == "Option") {
+            return self.serialize_none();
+        }
         self.maybe_some()?;
         Ok(())
     }
@@ -446,11 +456,16 @@ where
             Some(SpecificType::StructNewType(ty)) => match self.resolve(ty) {
                 // { "foo": "bar" } => "bar" might be a string wrapped in a type
                 SpecificType::Str => Ok(None),
-                ref ty => Err(Error::NotSupported(
-                    type_name_of_val(val),
-                    format!("{:?}", ty),
-                )),
+                ty => {
+                    self.ty = Some(ty);
+                    self.maybe_other(val)
+                }
             },
+            Some(SpecificType::Compact(ty)) => {
+                let n = val.parse().map_err(|_| Error::BadInput("compact".into()))?;
+                self.serialize_compact(ty, n)?;
+                Ok(Some(()))
+            }
             Some(SpecificType::U8) => {
                 let n = val.parse().map_err(|_| Error::BadInput("u8".into()))?;
                 self.out.put_u8(n);
@@ -511,6 +526,20 @@ where
                     Err(Error::BadInput("Hex string must start with 0x".into()))
                 }
             }
+            #[cfg(feature = "hex")]
+            Some(SpecificType::Tuple(TupleOrArray::Array(ty, len)))
+                if matches!(self.resolve(ty), SpecificType::U8) =>
+            {
+                let bytes = val
+                    .strip_prefix("0x")
+                    .ok_or_else(|| Error::BadInput("Hex string must start with 0x".into()))?;
+                let bytes = hex::decode(bytes).map_err(|e| Error::BadInput(e.to_string()))?;
+                if bytes.len() != len as usize {
+                    return Err(Error::BadInput(format!("Expected {} bytes", len)));
+                }
+                self.out.put_slice(&bytes);
+                Ok(Some(()))
+            }
             Some(ref ty) => Err(Error::NotSupported(
                 type_name_of_val(val),
                 format!("{:?}", ty),
@@ -1283,6 +1312,61 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn json_as_described_by_schema() -> Result<()> {
+        #[derive(Debug, Encode, TypeInfo, Serialize)]
+        struct Foo {
+            a: Option<u32>,
+            b: u128,
+            #[codec(compact)]
+            c: u128,
+            d: [u8; 4],
+            e: i128,
+        }
+        let foo = Foo {
+            a: None,
+            b: 1_000,
+            c: u128::MAX,
+            d: [0xde, 0xad, 0xbe, 0xef],
+            e: -5,
+        };
+        let (ty, reg) = register(&foo);
+
+        let input = serde_json::json!({
+            "a": null,
+            "b": 1_000,
+            "c": u128::MAX.to_string(),
+            "d": "0xdeadbeef",
+            "e": -5,
+        });
+        let out = to_vec_with_info(&input, Some((&reg, ty)))?;
+
+        assert_eq!(out, foo.encode());
+        Ok(())
+    }
+
+    #[test]
+    fn small_numbers_fit_their_type() -> Result<()> {
+        #[derive(Debug, Encode, TypeInfo, Serialize)]
+        struct Foo {
+            a: u16,
+            #[codec(compact)]
+            b: u8,
+        }
+        let foo = Foo { a: 300, b: 7 };
+        let (ty, reg) = register(&foo);
+
+        let out = to_vec_with_info(&serde_json::json!({ "a": "300", "b": 7 }), Some((&reg, ty)))?;
+        assert_eq!(out, foo.encode());
+
+        let too_big = serde_json::json!({ "a": 300, "b": 256 });
+        assert!(matches!(
+            to_vec_with_info(&too_big, Some((&reg, ty))),
+            Err(Error::BadInput(_))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_extrincic_call() -> Result<()> {
         let bytes = include_bytes!("registry.bin");
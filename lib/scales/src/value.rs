@@ -42,6 +42,11 @@ impl<'a> Value<'a> {
         self.ty_size(&self.data, self.ty_id)
     }
 
+    /// Id of the type of the value in the registry
+    pub fn ty_id(&self) -> u32 {
+        self.ty_id
+    }
+
     fn ty_size(&self, data: &[u8], ty: TypeId) -> usize {
         match &self.resolve(ty).type_def {
             TypeDef::Primitive(ref p) => match p {
@@ -5,9 +5,9 @@ use async_std::{
     task::block_on,
 };
 use codec::Decode;
-use opts::Opt;
+use opts::{Cmd, Opt};
 use structopt::StructOpt;
-use sube::{sube, Backend, Metadata};
+use sube::{sube, Metadata, Response};
 use url::Url;
 
 mod opts;
@@ -21,20 +21,35 @@ async fn run() -> Result<()> {
         .init()
         .unwrap();
 
-    let url = chain_string_to_url(&opt.chain)?;
+    let chain = chain_string_to_url(&opt.chain)?;
+    if let Some(Cmd::Typescript { out }) = opt.cmd {
+        let declarations = typescript(&chain, opt.metadata).await?;
+        match out {
+            Some(out) => async_std::fs::write(out, declarations).await?,
+            None => io::stdout().write_all(declarations.as_bytes()).await?,
+        }
+        return Ok(());
+    }
 
-    // let backend = sube::ws::Backend::new_ws2(url.as_str()).await?;
-    let backend = sube::http::Backend::new(url.as_str());
+    let input = opt
+        .input
+        .ok_or_else(|| anyhow!("Missing the QUERY/CALL to run"))?;
+    let mut url = chain.join(&input)?;
+    if opt.ss58 {
+        url.query_pairs_mut().append_key_only("ss58");
+    }
+    if opt.balances {
+        url.query_pairs_mut().append_key_only("balances");
+    }
 
-    let meta = if let Some(m) = opt.metadata {
-        get_meta_from_fs(&m)
+    let mut builder = sube(url.as_str());
+    if let Some(m) = opt.metadata {
+        let meta = get_meta_from_fs(&m)
             .await
-            .ok_or_else(|| anyhow!("Couldn't read Metadata from file"))?
-    } else {
-        backend.metadata().await?
-    };
-
-    let res = sube::<u8>(backend, &meta, &opt.input, None, |_, _| {}).await?;
+            .ok_or_else(|| anyhow!("Couldn't read Metadata from file"))?;
+        builder = builder.with_meta(meta);
+    }
+    let res = builder.await?;
 
     io::stdout().write_all(&opt.output.format(res)?).await?;
     writeln!(io::stdout()).await?;
@@ -60,7 +75,7 @@ fn chain_string_to_url(chain: &str) -> Result<Url> {
         && !chain.starts_with("http://")
         && !chain.starts_with("https://")
     {
-        ["wss", &chain].join("://")
+        ["wss", chain].join("://")
     } else {
         chain.into()
     };
@@ -81,6 +96,19 @@ fn chain_string_to_url(chain: &str) -> Result<Url> {
     Ok(url)
 }
 
+async fn typescript(chain: &Url, metadata: Option<PathBuf>) -> Result<String> {
+    if let Some(m) = metadata {
+        let meta = get_meta_from_fs(&m)
+            .await
+            .ok_or_else(|| anyhow!("Couldn't read Metadata from file"))?;
+        return Ok(sube::typescript::declarations(&meta));
+    }
+    match sube(chain.join("_meta")?.as_str()).await? {
+        Response::Meta(meta) => Ok(sube::typescript::declarations(meta)),
+        _ => Err(anyhow!("Couldn't get the chain metadata")),
+    }
+}
+
 async fn get_meta_from_fs(path: &PathBuf) -> Option<Metadata> {
     let mut m = Vec::new();
     let mut f = async_std::fs::File::open(path).await.ok()?;
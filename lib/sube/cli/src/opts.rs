@@ -16,6 +16,12 @@ pub(crate) struct Opt {
     /// Format for the output (json,json-pretty,scale,hex)
     #[structopt(short, long, default_value = "json")]
     pub output: Output,
+    /// Render account ids as SS58 addresses of the chain
+    #[structopt(long)]
+    pub ss58: bool,
+    /// Render balances in units of the chain's token
+    #[structopt(long)]
+    pub balances: bool,
     /// Use existing metadata from the filesystem(in SCALE format)
     #[structopt(short, long)]
     pub metadata: Option<PathBuf>,
@@ -24,8 +30,23 @@ pub(crate) struct Opt {
     #[structopt(short, long, parse(from_occurrences))]
     pub verbose: usize,
 
+    /// Path of the storage item, call or metadata to query, required without a subcommand
     #[structopt(value_name = "QUERY/CALL")]
-    pub input: String,
+    pub input: Option<String>,
+    #[structopt(subcommand)]
+    pub cmd: Option<Cmd>,
+}
+
+#[derive(StructOpt, Debug)]
+pub(crate) enum Cmd {
+    /// Generate TypeScript declarations(.d.ts) of the storage values, calls and events of the
+    /// chain
+    #[structopt(alias = "ts")]
+    Typescript {
+        /// File to write the declarations to instead of the standard output
+        #[structopt(short, long)]
+        out: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug)]
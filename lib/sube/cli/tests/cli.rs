@@ -0,0 +1,64 @@
+use std::process::{Command, Output};
+
+use sube_test_server::{fixtures, Chain, Fault, TestServer};
+
+fn server() -> TestServer {
+    let chain = Chain::new(fixtures::metadata()).with_storage([(
+        fixtures::storage_key("Balances", "TotalIssuance"),
+        1_000u128.to_le_bytes().into(),
+    )]);
+    TestServer::start(chain).expect("server started")
+}
+
+fn sube(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sube"))
+        .args(args)
+        .output()
+        .expect("sube runs")
+}
+
+#[test]
+fn queries_storage() {
+    let server = server();
+    let out = sube(&["-c", &server.http_url(), "balances/totalIssuance"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "1000");
+
+    let out = sube(&[
+        "-c",
+        &server.http_url(),
+        "-o",
+        "hex",
+        "balances/totalIssuance",
+    ]);
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "0xe8030000000000000000000000000000"
+    );
+}
+
+#[test]
+fn fails_on_node_errors() {
+    let server = server();
+    server.inject("state_getMetadata", Fault::Disconnect);
+    let out = sube(&["-c", &server.http_url(), "balances/totalIssuance"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn generates_typescript_declarations() {
+    let server = server();
+    let out = sube(&["-c", &server.http_url(), "typescript"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let declarations = String::from_utf8_lossy(&out.stdout);
+    assert!(declarations.contains("export namespace Balances {"));
+    assert!(declarations.contains("export type TotalIssuance = BigNum;"));
+}
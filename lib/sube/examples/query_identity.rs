@@ -6,8 +6,12 @@ use sube::{sube, Response};
 #[async_std::main]
 async fn main() -> sube::Result<()> {
     env_logger::init();
+    let node = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "wss://kreivo.io".into());
 
-    let result = sube!("ws://localhost:11004/identity/superOf/0x6d6f646c6b762f636d7479738501000000000000000000000000000000000000").await?;
+    let query = format!("{}/identity/superOf/0x6d6f646c6b762f636d7479738501000000000000000000000000000000000000", node);
+    let result = sube!(&query).await?;
 
     if let Response::Value(value) = result {
         let data = serde_json::to_value(&value).expect("to be serializable");
@@ -17,7 +21,7 @@ async fn main() -> sube::Result<()> {
         );
     }
 
-    let query = format!("ws://localhost:11004/identity/identityOf/0xbe6ed76ac48d5c7f1c5d2cab8a1d1e7a451dcc24b624b088ef554fd47ba21139");
+    let query = format!("{}/identity/identityOf/0xbe6ed76ac48d5c7f1c5d2cab8a1d1e7a451dcc24b624b088ef554fd47ba21139", node);
 
     let r = sube!(&query).await?;
 
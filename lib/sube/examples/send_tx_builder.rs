@@ -4,16 +4,16 @@ use std::{env, error::Error};
 use sube::{Bytes, SubeBuilder, Signer};
 type Wallet = libwallet::Wallet<vault::Simple<String>>;
 
+/// Phrase of the development accounts, used when none is given so the
+/// example signs with the same account every run
+const DEV_PHRASE: &str = "bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
 #[async_std::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let phrase = env::args().skip(1).collect::<Vec<_>>().join(" ");
-
-    let (vault, phrase) = if phrase.is_empty() {
-        vault::Simple::generate_with_phrase(&mut rand_core::OsRng)
-    } else {
-        let phrase: libwallet::Mnemonic = phrase.parse().expect("Invalid phrase");
-        (vault::Simple::from_phrase(&phrase), phrase)
-    };
+    let phrase = if phrase.is_empty() { DEV_PHRASE.into() } else { phrase };
+    let phrase: libwallet::Mnemonic = phrase.parse().expect("Invalid phrase");
+    let vault = vault::Simple::from_phrase(&phrase);
 
     let mut wallet = Wallet::new(vault);
     wallet.unlock(None, None).await?;
@@ -47,7 +47,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
             "value": 100000
         }))
         .with_signer(signer)
-        .await.map_err(|_| format!("Failed to send tx"));
+        .await
+        .map_err(|err| format!("Failed to send tx: {}", err))?;
 
     println!("{:?}", response);
     Ok(())
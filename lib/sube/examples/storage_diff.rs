@@ -0,0 +1,19 @@
+use sube::{sube, Response, Result};
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let result =
+        sube!("wss://rococo-rpc.polkadot.io/referenda/referendumInfoFor?diff=10000000&at=10000100")
+            .await?;
+
+    if let Response::Diff(diff) = result {
+        println!(
+            "Storage diff: {}",
+            serde_json::to_string_pretty(&diff).expect("to be serializable")
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,512 @@
+//! Memoization of storage reads for clients that repeat the same queries.
+//!
+//! Storage of a given block never changes so results are kept until they are
+//! evicted by newer ones, queries of the latest block expire after a TTL.
+//! Items of the latest block are read all at once when any of them is missing
+//! so the results of a query never mix blocks.
+//! The hash a block number resolves to expires too as the block could be
+//! replaced by a reorg before it's finalized.
+use crate::prelude::*;
+use crate::{
+    meta::BlockInfo, proof::ReadProof, Backend, ChainProperties, RawKey, RawValue, Result,
+};
+use alloc::collections::BTreeMap;
+use core::{num::NonZeroUsize, time::Duration};
+use lru::LruCache;
+use std::{sync::Mutex, time::Instant};
+
+type Hash = [u8; 32];
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Maximum number of cached storage items and key pages
+    pub capacity: NonZeroUsize,
+    /// Time the results of queries without a block and the hashes of the
+    /// block numbers used in queries are valid for
+    pub latest_ttl: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            capacity: NonZeroUsize::new(10_000).expect("non zero"),
+            latest_ttl: Duration::from_secs(6),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+enum At {
+    Latest,
+    Block(Hash),
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum Query {
+    Item(RawKey),
+    KeysPaged {
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+    },
+}
+
+#[derive(Clone)]
+enum Cached {
+    /// Missing items are cached too
+    Item(Option<RawValue>),
+    Keys(Vec<RawKey>),
+}
+
+struct Entry {
+    value: Cached,
+    expires: Option<Instant>,
+}
+
+/// A backend wrapper that memoizes the storage items and keys it reads,
+/// other queries are passed through.
+pub struct Cache<B> {
+    backend: B,
+    options: Options,
+    entries: Mutex<LruCache<(At, Query), Entry>>,
+    /// Hashes of the block numbers used in queries and when they expire
+    hashes: Mutex<LruCache<u32, (Hash, Instant)>>,
+}
+
+impl<B: Backend> Cache<B> {
+    pub fn new(backend: B) -> Self {
+        Self::with_options(backend, Options::default())
+    }
+
+    pub fn with_options(backend: B, options: Options) -> Self {
+        Cache {
+            backend,
+            entries: Mutex::new(LruCache::new(options.capacity)),
+            hashes: Mutex::new(LruCache::new(options.capacity)),
+            options,
+        }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().expect("not poisoned").clear();
+        self.hashes.lock().expect("not poisoned").clear();
+    }
+
+    async fn at(&self, block: Option<u32>) -> Result<At> {
+        let Some(number) = block else {
+            return Ok(At::Latest);
+        };
+        if let Some(&(hash, expires)) = self.hashes.lock().expect("not poisoned").get(&number) {
+            if expires > Instant::now() {
+                return Ok(At::Block(hash));
+            }
+        }
+        let hash = self.backend.block_info(Some(number)).await?.hash;
+        let expires = Instant::now() + self.options.latest_ttl;
+        self.hashes
+            .lock()
+            .expect("not poisoned")
+            .put(number, (hash, expires));
+        Ok(At::Block(hash))
+    }
+
+    fn get(&self, at: At, query: Query) -> Option<Cached> {
+        let mut entries = self.entries.lock().expect("not poisoned");
+        let key = (at, query);
+        match entries.get(&key) {
+            Some(entry) if entry.expires.is_none_or(|t| t > Instant::now()) => {
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                entries.pop(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, at: At, query: Query, value: Cached) {
+        let expires = match at {
+            At::Latest => Some(Instant::now() + self.options.latest_ttl),
+            At::Block(_) => None,
+        };
+        self.entries
+            .lock()
+            .expect("not poisoned")
+            .put((at, query), Entry { value, expires });
+    }
+}
+
+impl<B: Backend> Backend for Cache<B> {
+    async fn get_storage_items(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> Result<impl Iterator<Item = (RawKey, RawValue)>> {
+        let at = self.at(block).await?;
+
+        // items keep the order of the keys, misses are filled in after reading them
+        let mut items = Vec::with_capacity(keys.len());
+        let mut missing = vec![];
+        for key in keys {
+            match self.get(at, Query::Item(key.clone())) {
+                Some(Cached::Item(value)) => items.push((key, Some(value))),
+                _ => {
+                    missing.push(key.clone());
+                    items.push((key, None));
+                }
+            }
+        }
+        log::trace!(
+            "Cache hits {}, misses {}",
+            items.len() - missing.len(),
+            missing.len()
+        );
+        // the latest block may have changed since the hits were cached
+        if at == At::Latest && !missing.is_empty() {
+            missing = items.iter().map(|(key, _)| key.clone()).collect();
+            items.iter_mut().for_each(|(_, cached)| *cached = None);
+        }
+
+        if !missing.is_empty() {
+            let mut found = self
+                .backend
+                .get_storage_items(missing, block)
+                .await?
+                .collect::<BTreeMap<_, _>>();
+            for (key, cached) in items.iter_mut().filter(|(_, cached)| cached.is_none()) {
+                let value = found.remove(key);
+                self.put(at, Query::Item(key.clone()), Cached::Item(value.clone()));
+                *cached = Some(value);
+            }
+        }
+
+        Ok(items
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.flatten()?))))
+    }
+
+    async fn get_keys_paged(
+        &self,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        let at = self.at(block).await?;
+        let query = || Query::KeysPaged {
+            from: from.clone(),
+            size,
+            to: to.clone(),
+        };
+        if let Some(Cached::Keys(keys)) = self.get(at, query()) {
+            return Ok(keys);
+        }
+
+        let keys = self
+            .backend
+            .get_keys_paged(from.clone(), size, to.clone(), block)
+            .await?;
+        self.put(at, query(), Cached::Keys(keys.clone()));
+        Ok(keys)
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> Result<RawValue> {
+        self.backend
+            .get_child_storage_item(child_key, key, block)
+            .await
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<(RawKey, RawValue)>> {
+        self.backend
+            .get_child_storage_items(child_key, keys, block)
+            .await
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        self.backend
+            .get_child_keys_paged(child_key, from, size, to, block)
+            .await
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> Result<[u8; 32]> {
+        self.backend
+            .get_child_storage_hash(child_key, key, block)
+            .await
+    }
+
+    async fn get_read_proof(&self, keys: Vec<RawKey>, block: Option<u32>) -> Result<ReadProof> {
+        self.backend.get_read_proof(keys, block).await
+    }
+
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+        self.backend.submit(ext).await
+    }
+
+    async fn metadata(&self) -> Result<crate::Metadata> {
+        self.backend.metadata().await
+    }
+
+    async fn block_info(&self, at: Option<u32>) -> Result<BlockInfo> {
+        self.backend.block_info(at).await
+    }
+
+    async fn properties(&self) -> Result<ChainProperties> {
+        self.backend.properties().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Storage that counts the reads reaching it
+    #[derive(Default)]
+    struct Counter {
+        storage: BTreeMap<RawKey, RawValue>,
+        items: AtomicUsize,
+        keys: AtomicUsize,
+        blocks: AtomicUsize,
+    }
+
+    impl Counter {
+        fn with(items: &[(u8, u8)]) -> Self {
+            Counter {
+                storage: items.iter().map(|&(k, v)| (vec![k], vec![v])).collect(),
+                ..Default::default()
+            }
+        }
+
+        fn reads(&self) -> (usize, usize) {
+            (
+                self.items.load(Ordering::Relaxed),
+                self.keys.load(Ordering::Relaxed),
+            )
+        }
+    }
+
+    impl Backend for Counter {
+        async fn get_storage_items(
+            &self,
+            keys: Vec<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<impl Iterator<Item = (RawKey, RawValue)>> {
+            self.items.fetch_add(keys.len(), Ordering::Relaxed);
+            Ok(keys
+                .into_iter()
+                .filter_map(|k| Some((k.clone(), self.storage.get(&k)?.clone())))
+                .collect::<Vec<_>>()
+                .into_iter())
+        }
+
+        async fn get_keys_paged(
+            &self,
+            from: RawKey,
+            size: u16,
+            _to: Option<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<Vec<RawKey>> {
+            self.keys.fetch_add(1, Ordering::Relaxed);
+            Ok(self
+                .storage
+                .keys()
+                .filter(|k| k.starts_with(&from))
+                .take(size as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn get_child_storage_item(
+            &self,
+            _child_key: RawKey,
+            _key: RawKey,
+            _block: Option<u32>,
+        ) -> Result<RawValue> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn get_child_storage_items(
+            &self,
+            _child_key: RawKey,
+            _keys: Vec<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<Vec<(RawKey, RawValue)>> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn get_child_keys_paged(
+            &self,
+            _child_key: RawKey,
+            _from: RawKey,
+            _size: u16,
+            _to: Option<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<Vec<RawKey>> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn get_child_storage_hash(
+            &self,
+            _child_key: RawKey,
+            _key: RawKey,
+            _block: Option<u32>,
+        ) -> Result<[u8; 32]> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn get_read_proof(
+            &self,
+            _keys: Vec<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<ReadProof> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn submit(&self, _ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn metadata(&self) -> Result<crate::Metadata> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn block_info(&self, at: Option<u32>) -> Result<BlockInfo> {
+            self.blocks.fetch_add(1, Ordering::Relaxed);
+            let number = at.unwrap_or(100);
+            Ok(BlockInfo {
+                number: number as u64,
+                hash: [number as u8; 32],
+                parent: [0; 32],
+            })
+        }
+    }
+
+    async fn read(cache: &Cache<Counter>, keys: &[u8], block: Option<u32>) -> Vec<(u8, u8)> {
+        let keys = keys.iter().map(|&k| vec![k]).collect();
+        let items = cache.get_storage_items(keys, block).await.unwrap();
+        items.map(|(k, v)| (k[0], v[0])).collect()
+    }
+
+    #[async_std::test]
+    async fn repeated_reads_are_hits() {
+        let cache = Cache::new(Counter::with(&[(1, 10), (2, 20)]));
+
+        assert_eq!(read(&cache, &[1, 3], Some(5)).await, [(1, 10)]);
+        // missing items are remembered too, only the new key is read
+        assert_eq!(read(&cache, &[1, 2, 3], Some(5)).await, [(1, 10), (2, 20)]);
+        assert_eq!(cache.inner().reads(), (3, 0));
+        assert_eq!(cache.inner().blocks.load(Ordering::Relaxed), 1);
+
+        // other blocks have their own items
+        read(&cache, &[1], Some(6)).await;
+        assert_eq!(cache.inner().reads(), (4, 0));
+
+        for _ in 0..2 {
+            let keys = cache.get_keys_paged(vec![], 10, None, Some(5)).await;
+            assert_eq!(keys.unwrap(), [vec![1], vec![2]]);
+        }
+        assert_eq!(cache.inner().reads(), (4, 1));
+    }
+
+    #[async_std::test]
+    async fn items_keep_the_order_of_the_keys() {
+        let cache = Cache::new(Counter::with(&[(1, 10), (2, 20), (3, 30)]));
+
+        read(&cache, &[2], Some(5)).await;
+        let items = read(&cache, &[3, 2, 4, 1], Some(5)).await;
+        assert_eq!(items, [(3, 30), (2, 20), (1, 10)]);
+        assert_eq!(cache.inner().reads(), (4, 0));
+    }
+
+    #[async_std::test]
+    async fn least_recently_used_are_evicted() {
+        let options = Options {
+            capacity: NonZeroUsize::new(2).unwrap(),
+            ..Default::default()
+        };
+        let cache = Cache::with_options(Counter::with(&[(1, 10), (2, 20), (3, 30)]), options);
+
+        read(&cache, &[1], Some(5)).await;
+        read(&cache, &[2], Some(5)).await;
+        read(&cache, &[1], Some(5)).await;
+        assert_eq!(cache.inner().reads(), (2, 0));
+
+        // 2 is the least recently used
+        read(&cache, &[3], Some(5)).await;
+        read(&cache, &[1], Some(5)).await;
+        assert_eq!(cache.inner().reads(), (3, 0));
+        assert_eq!(read(&cache, &[2], Some(5)).await, [(2, 20)]);
+        assert_eq!(cache.inner().reads(), (4, 0));
+    }
+
+    #[async_std::test]
+    async fn latest_reads_expire() {
+        let options = Options {
+            latest_ttl: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let cache = Cache::with_options(Counter::with(&[(1, 10)]), options);
+
+        read(&cache, &[1], None).await;
+        read(&cache, &[1], Some(5)).await;
+        read(&cache, &[1], None).await;
+        assert_eq!(cache.inner().reads(), (2, 0));
+
+        async_std::task::sleep(Duration::from_millis(60)).await;
+        read(&cache, &[1], None).await;
+        assert_eq!(cache.inner().reads(), (3, 0));
+        // items of a block never expire, the hash of its number is looked up again
+        read(&cache, &[1], Some(5)).await;
+        assert_eq!(cache.inner().reads(), (3, 0));
+        assert_eq!(cache.inner().blocks.load(Ordering::Relaxed), 2);
+
+        cache.clear();
+        read(&cache, &[1], Some(5)).await;
+        assert_eq!(cache.inner().reads(), (4, 0));
+    }
+
+    #[async_std::test]
+    async fn latest_reads_dont_mix_blocks() {
+        let cache = Cache::new(Counter::with(&[(1, 10), (2, 20)]));
+
+        read(&cache, &[1], None).await;
+        // a miss reads the hits again along with it
+        assert_eq!(read(&cache, &[2, 1], None).await, [(2, 20), (1, 10)]);
+        assert_eq!(cache.inner().reads(), (3, 0));
+        read(&cache, &[1, 2], None).await;
+        assert_eq!(cache.inner().reads(), (3, 0));
+
+        // items of a block are only read once
+        read(&cache, &[1], Some(5)).await;
+        read(&cache, &[1, 2], Some(5)).await;
+        assert_eq!(cache.inner().reads(), (5, 0));
+    }
+}
@@ -0,0 +1,715 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use codec::{Compact, Decode};
+use core::ops::Bound;
+use no_std_async::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+
+use crate::hasher::blake2_256;
+use crate::meta::{self, BlockInfo, Metadata};
+use crate::proof::ReadProof;
+use crate::rpc::{RpcSubscribe, Subscription};
+use crate::{prelude::*, Backend, Error, RawKey, RawValue};
+
+/// Backend built on the `chainHead_v1_*` and `transaction_v1_*`/`transactionWatch_v1_*`
+/// functions of the new JSON-RPC spec, an alternative to `RpcClient` for nodes
+/// that deprecated the legacy `state_*`/`author_*` ones.
+///
+/// Blocks reported by the follow subscription stay pinned until they are
+/// finalized or pruned, its events are processed lazily whenever the backend
+/// is used and a new subscription is started if the node stopped the current one.
+pub struct ChainHeadClient<R> {
+    rpc: R,
+    follow: Mutex<Option<Follow>>,
+    descendants: Mutex<Option<Descendants>>,
+}
+
+/// Keys listed by the last `descendantsHashes` operation, the pages after
+/// the first one are taken from them instead of listing the keys again
+struct Descendants {
+    child_key: Option<RawKey>,
+    prefix: RawKey,
+    block: String,
+    keys: BTreeSet<RawKey>,
+}
+
+/// State of an active `chainHead_v1_follow` subscription
+struct Follow {
+    sub: Subscription,
+    stopped: bool,
+    best: String,
+    finalized: String,
+    pinned: BTreeSet<String>,
+    headers: BTreeMap<String, Header>,
+}
+
+#[derive(Clone, Copy)]
+struct Header {
+    number: u32,
+    parent: [u8; 32],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+enum FollowEvent {
+    #[serde(rename_all = "camelCase")]
+    Initialized {
+        finalized_block_hashes: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    NewBlock {
+        block_hash: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    BestBlockChanged {
+        best_block_hash: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Finalized {
+        finalized_block_hashes: Vec<String>,
+        pruned_block_hashes: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationCallDone {
+        operation_id: String,
+        output: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationStorageItems {
+        operation_id: String,
+        items: Vec<StorageItem>,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationWaitingForContinue {
+        operation_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationStorageDone {
+        operation_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationInaccessible {
+        operation_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    OperationError {
+        operation_id: String,
+        error: String,
+    },
+    Stop,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct StorageItem {
+    key: String,
+    value: Option<String>,
+    hash: Option<String>,
+}
+
+/// Kind of result expected from a `chainHead_v1_storage` item
+#[derive(Clone, Copy)]
+enum StorageQuery {
+    Value,
+    Hash,
+    DescendantsHashes,
+}
+
+impl StorageQuery {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StorageQuery::Value => "value",
+            StorageQuery::Hash => "hash",
+            StorageQuery::DescendantsHashes => "descendantsHashes",
+        }
+    }
+}
+
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(hex: &str) -> crate::Result<Vec<u8>> {
+    hex::decode(hex.trim_start_matches("0x")).map_err(|_| Error::BadInput)
+}
+
+fn hash_from_hex(hex: &str) -> crate::Result<[u8; 32]> {
+    from_hex(hex)?.try_into().map_err(|_| Error::BadInput)
+}
+
+impl Follow {
+    /// Keeps track of the pinned blocks, returns the ones that can be unpinned
+    fn apply(&mut self, event: &FollowEvent) -> Vec<String> {
+        match event {
+            // also received when the backend restores the subscription after reconnecting
+            FollowEvent::Initialized {
+                finalized_block_hashes,
+            } => {
+                let Some((last, older)) = finalized_block_hashes.split_last() else {
+                    return vec![];
+                };
+                self.best = last.clone();
+                self.finalized = last.clone();
+                self.pinned = BTreeSet::from([last.clone()]);
+                self.headers.clear();
+                older.to_vec()
+            }
+            FollowEvent::NewBlock { block_hash } => {
+                self.pinned.insert(block_hash.clone());
+                vec![]
+            }
+            FollowEvent::BestBlockChanged { best_block_hash } => {
+                self.best = best_block_hash.clone();
+                vec![]
+            }
+            FollowEvent::Finalized {
+                finalized_block_hashes,
+                pruned_block_hashes,
+            } => {
+                let Some(last) = finalized_block_hashes.last() else {
+                    return vec![];
+                };
+                let previous = core::mem::replace(&mut self.finalized, last.clone());
+                let unpin = core::iter::once(&previous)
+                    .chain(finalized_block_hashes)
+                    .chain(pruned_block_hashes)
+                    .filter(|hash| *hash != last && **hash != self.best)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for hash in &unpin {
+                    self.pinned.remove(hash);
+                    self.headers.remove(hash);
+                }
+                unpin
+            }
+            FollowEvent::Stop => {
+                self.stopped = true;
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl<R: RpcSubscribe> ChainHeadClient<R> {
+    pub fn new(rpc: R) -> Self {
+        ChainHeadClient {
+            rpc,
+            follow: Mutex::new(None),
+            descendants: Mutex::new(None),
+        }
+    }
+
+    /// Starts broadcasting a transaction without following its progress,
+    /// returns the id of the operation that can be given to `stop_broadcast`
+    pub async fn broadcast(&self, ext: impl AsRef<[u8]>) -> crate::Result<Option<String>> {
+        self.request("transaction_v1_broadcast", &[json!(to_hex(ext))])
+            .await
+    }
+
+    /// Stops the broadcasting of a transaction started with `broadcast`
+    pub async fn stop_broadcast(&self, operation_id: &str) -> crate::Result<()> {
+        self.request::<JsonValue>("transaction_v1_stop", &[json!(operation_id)])
+            .await?;
+        Ok(())
+    }
+
+    async fn request<T>(&self, method: &str, params: &[JsonValue]) -> crate::Result<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let params = params.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        self.rpc
+            .rpc(
+                method,
+                params
+                    .iter()
+                    .map(|s| s.as_ref())
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )
+            .await
+            .map_err(|e| Error::Node(e.to_string()))
+    }
+
+    /// Returns the active follow subscription, starting a new one if needed
+    async fn following<'f>(&self, follow: &'f mut Option<Follow>) -> crate::Result<&'f mut Follow> {
+        if follow.as_ref().is_some_and(|f| f.stopped) {
+            *follow = None;
+        }
+
+        if follow.is_none() {
+            let mut sub = self
+                .rpc
+                .subscribe("chainHead_v1_follow", &["false"])
+                .await
+                .map_err(|e| Error::Node(e.to_string()))?;
+
+            let initialized = loop {
+                let event = sub.next().await.ok_or(Error::ChainUnavailable)?;
+                match serde_json::from_value(event) {
+                    Ok(event @ FollowEvent::Initialized { .. }) => break event,
+                    Ok(FollowEvent::Stop) => return Err(Error::ChainUnavailable),
+                    _ => continue,
+                }
+            };
+
+            let mut new = Follow {
+                sub,
+                stopped: false,
+                best: String::new(),
+                finalized: String::new(),
+                pinned: BTreeSet::new(),
+                headers: BTreeMap::new(),
+            };
+            self.apply(&mut new, &initialized).await?;
+            if new.pinned.is_empty() {
+                return Err(Error::ChainUnavailable);
+            }
+            *follow = Some(new);
+        }
+
+        let follow = follow.as_mut().expect("follow subscription started");
+        // catch up with the events received since the last time
+        while let Some(event) = follow.sub.try_next() {
+            if let Ok(event) = serde_json::from_value(event) {
+                self.apply(follow, &event).await?;
+            }
+        }
+        if follow.stopped {
+            return Err(Error::Node("chainHead follow subscription stopped".into()));
+        }
+        Ok(follow)
+    }
+
+    async fn apply(&self, follow: &mut Follow, event: &FollowEvent) -> crate::Result<()> {
+        let unpin = follow.apply(event);
+        if !unpin.is_empty() {
+            self.request::<JsonValue>(
+                "chainHead_v1_unpin",
+                &[json!(follow.sub.id()), json!(unpin)],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the next event of the follow subscription keeping the pinned blocks up to date
+    async fn next_event(&self, follow: &mut Follow) -> crate::Result<FollowEvent> {
+        let Some(event) = follow.sub.next().await else {
+            follow.stopped = true;
+            return Err(Error::ChainUnavailable);
+        };
+        let event = serde_json::from_value(event).unwrap_or(FollowEvent::Other);
+        self.apply(follow, &event).await?;
+        if follow.stopped {
+            return Err(Error::Node("chainHead follow subscription stopped".into()));
+        }
+        Ok(event)
+    }
+
+    /// Id of an operation from the response of the functions that start one
+    fn operation_id(res: &JsonValue) -> crate::Result<(String, usize)> {
+        match res["result"].as_str() {
+            Some("started") => Ok((
+                res["operationId"]
+                    .as_str()
+                    .ok_or(Error::Node("missing operation id".into()))?
+                    .into(),
+                res["discardedItems"].as_u64().unwrap_or(0) as usize,
+            )),
+            Some("limitReached") => Err(Error::Node("chainHead operations limit reached".into())),
+            _ => Err(Error::Node(format!("unexpected response {}", res))),
+        }
+    }
+
+    async fn header(&self, follow: &mut Follow, hash: &str) -> crate::Result<Header> {
+        if let Some(header) = follow.headers.get(hash) {
+            return Ok(*header);
+        }
+        let header: Option<String> = self
+            .request(
+                "chainHead_v1_header",
+                &[json!(follow.sub.id()), json!(hash)],
+            )
+            .await?;
+        let header = from_hex(&header.ok_or(Error::BadBlockNumber)?)?;
+        let (parent, number) =
+            <([u8; 32], Compact<u32>)>::decode(&mut header.as_slice()).map_err(Error::Decode)?;
+        let header = Header {
+            number: number.0,
+            parent,
+        };
+        follow.headers.insert(hash.into(), header);
+        Ok(header)
+    }
+
+    /// Hash of a pinned block, the best block when no number is given
+    async fn block_hash(&self, follow: &mut Follow, block: Option<u32>) -> crate::Result<String> {
+        let Some(number) = block else {
+            return Ok(follow.best.clone());
+        };
+        // only the blocks pinned by the subscription can be accessed
+        self.pinned_hash(follow, number).await?.ok_or_else(|| {
+            Error::Node(format!(
+                "block {} is not pinned by the chainHead subscription",
+                number
+            ))
+        })
+    }
+
+    async fn pinned_hash(&self, follow: &mut Follow, number: u32) -> crate::Result<Option<String>> {
+        let pinned = follow.pinned.iter().cloned().collect::<Vec<_>>();
+        for hash in pinned {
+            if self.header(follow, &hash).await?.number == number {
+                return Ok(Some(hash));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn storage(
+        &self,
+        child_key: Option<&RawKey>,
+        items: Vec<(RawKey, StorageQuery)>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<StorageItem>> {
+        let mut follow = self.follow.lock().await;
+        let follow = self.following(&mut follow).await?;
+        let hash = self.block_hash(follow, block).await?;
+        self.storage_at(follow, &hash, child_key, items).await
+    }
+
+    async fn storage_at(
+        &self,
+        follow: &mut Follow,
+        hash: &str,
+        child_key: Option<&RawKey>,
+        items: Vec<(RawKey, StorageQuery)>,
+    ) -> crate::Result<Vec<StorageItem>> {
+        let child_key = child_key.map(to_hex);
+
+        let mut pending = items
+            .iter()
+            .map(|(key, ty)| json!({ "key": to_hex(key), "type": ty.as_str() }))
+            .collect::<Vec<_>>();
+        let mut results = vec![];
+
+        while !pending.is_empty() {
+            let res: JsonValue = self
+                .request(
+                    "chainHead_v1_storage",
+                    &[
+                        json!(follow.sub.id()),
+                        json!(hash),
+                        json!(pending),
+                        json!(child_key),
+                    ],
+                )
+                .await?;
+            let (operation, discarded) = Self::operation_id(&res)?;
+            // items the node couldn't take are requested again
+            let retry = pending.split_off(pending.len().saturating_sub(discarded));
+
+            loop {
+                match self.next_event(follow).await? {
+                    FollowEvent::OperationStorageItems {
+                        operation_id,
+                        items,
+                    } if operation_id == operation => results.extend(items),
+                    FollowEvent::OperationWaitingForContinue { operation_id }
+                        if operation_id == operation =>
+                    {
+                        self.request::<JsonValue>(
+                            "chainHead_v1_continue",
+                            &[json!(follow.sub.id()), json!(operation)],
+                        )
+                        .await?;
+                    }
+                    FollowEvent::OperationStorageDone { operation_id }
+                        if operation_id == operation =>
+                    {
+                        break
+                    }
+                    FollowEvent::OperationInaccessible { operation_id }
+                        if operation_id == operation =>
+                    {
+                        return Err(Error::Node("storage inaccessible".into()))
+                    }
+                    FollowEvent::OperationError {
+                        operation_id,
+                        error,
+                    } if operation_id == operation => return Err(Error::Node(error)),
+                    _ => continue,
+                }
+            }
+            pending = retry;
+        }
+
+        Ok(results)
+    }
+
+    /// A page of the keys under the prefix, the spec has no pagination so all the keys
+    /// are listed for the first page and the next ones are taken from them
+    async fn descendant_keys(
+        &self,
+        child_key: Option<&RawKey>,
+        prefix: RawKey,
+        size: u16,
+        start: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        let mut follow = self.follow.lock().await;
+        let follow = self.following(&mut follow).await?;
+        let hash = self.block_hash(follow, block).await?;
+
+        let mut descendants = self.descendants.lock().await;
+        let listed = descendants.as_ref().is_some_and(|d| {
+            start.is_some()
+                && d.child_key.as_ref() == child_key
+                && d.prefix == prefix
+                && d.block == hash
+        });
+        if !listed {
+            let items = self
+                .storage_at(
+                    follow,
+                    &hash,
+                    child_key,
+                    vec![(prefix.clone(), StorageQuery::DescendantsHashes)],
+                )
+                .await?;
+            *descendants = Some(Descendants {
+                child_key: child_key.cloned(),
+                prefix,
+                block: hash,
+                keys: items
+                    .iter()
+                    .map(|item| from_hex(&item.key))
+                    .collect::<crate::Result<_>>()?,
+            });
+        }
+
+        let keys = &descendants.as_ref().expect("keys listed").keys;
+        let from = match start {
+            Some(start) => Bound::Excluded(start),
+            None => Bound::Unbounded,
+        };
+        Ok(keys
+            .range((from, Bound::Unbounded))
+            .take(size as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn call(&self, function: &str, params: &[u8]) -> crate::Result<Vec<u8>> {
+        let mut follow = self.follow.lock().await;
+        let follow = self.following(&mut follow).await?;
+        let hash = follow.best.clone();
+
+        let res: JsonValue = self
+            .request(
+                "chainHead_v1_call",
+                &[
+                    json!(follow.sub.id()),
+                    json!(hash),
+                    json!(function),
+                    json!(to_hex(params)),
+                ],
+            )
+            .await?;
+        let (operation, _) = Self::operation_id(&res)?;
+
+        loop {
+            match self.next_event(follow).await? {
+                FollowEvent::OperationCallDone {
+                    operation_id,
+                    output,
+                } if operation_id == operation => return from_hex(&output),
+                FollowEvent::OperationInaccessible { operation_id }
+                    if operation_id == operation =>
+                {
+                    return Err(Error::Node("runtime call inaccessible".into()))
+                }
+                FollowEvent::OperationError {
+                    operation_id,
+                    error,
+                } if operation_id == operation => return Err(Error::Node(error)),
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl<R: RpcSubscribe> Backend for ChainHeadClient<R> {
+    async fn get_storage_items(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<impl Iterator<Item = (RawKey, RawValue)>> {
+        let items = self
+            .storage(
+                None,
+                keys.into_iter()
+                    .map(|key| (key, StorageQuery::Value))
+                    .collect(),
+                block,
+            )
+            .await?;
+
+        Ok(items
+            .into_iter()
+            // keys without a value are not present in the storage
+            .filter_map(|item| Some((from_hex(&item.key).ok()?, from_hex(&item.value?).ok()?)))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    async fn get_keys_paged(
+        &self,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        self.descendant_keys(None, from, size, to, block).await
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<RawValue> {
+        let items = self
+            .storage(Some(&child_key), vec![(key, StorageQuery::Value)], block)
+            .await?;
+        items
+            .into_iter()
+            .find_map(|item| item.value)
+            .ok_or(Error::StorageKeyNotFound)
+            .and_then(|v| from_hex(&v))
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<(RawKey, RawValue)>> {
+        let items = self
+            .storage(
+                Some(&child_key),
+                keys.into_iter()
+                    .map(|key| (key, StorageQuery::Value))
+                    .collect(),
+                block,
+            )
+            .await?;
+        Ok(items
+            .into_iter()
+            .filter_map(|item| Some((from_hex(&item.key).ok()?, from_hex(&item.value?).ok()?)))
+            .collect())
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        self.descendant_keys(Some(&child_key), from, size, to, block)
+            .await
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<[u8; 32]> {
+        let items = self
+            .storage(Some(&child_key), vec![(key, StorageQuery::Hash)], block)
+            .await?;
+        items
+            .into_iter()
+            .find_map(|item| item.hash)
+            .ok_or(Error::StorageKeyNotFound)
+            .and_then(|h| hash_from_hex(&h))
+    }
+
+    async fn get_read_proof(
+        &self,
+        _keys: Vec<RawKey>,
+        _block: Option<u32>,
+    ) -> crate::Result<ReadProof> {
+        Err(Error::Node(
+            "read proofs are not part of the chainHead API".into(),
+        ))
+    }
+
+    /// Submits the extrinsic and watches it until it's included in the best chain,
+    /// the API doesn't return the hash so it's computed with the usual blake2 hasher
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> crate::Result<[u8; 32]> {
+        let hash = blake2_256(ext.as_ref());
+        let ext = json!(to_hex(ext)).to_string();
+        let mut watch = self
+            .rpc
+            .subscribe("transactionWatch_v1_submitAndWatch", &[&ext])
+            .await
+            .map_err(|e| Error::Node(e.to_string()))?;
+
+        loop {
+            let event = watch
+                .next()
+                .await
+                .ok_or(Error::Node("transaction watch closed".into()))?;
+            log::debug!("Transaction event {}", event);
+            match event["event"].as_str() {
+                Some("bestChainBlockIncluded") if !event["block"].is_null() => break,
+                Some("finalized") => return Ok(hash),
+                Some("invalid" | "error" | "dropped") => {
+                    return Err(Error::Node(
+                        event["error"].as_str().unwrap_or_default().into(),
+                    ))
+                }
+                _ => continue,
+            }
+        }
+
+        self.request::<JsonValue>("transactionWatch_v1_unwatch", &[json!(watch.id())])
+            .await?;
+        Ok(hash)
+    }
+
+    async fn metadata(&self) -> crate::Result<Metadata> {
+        let res = self.call("Metadata_metadata", &[]).await?;
+        let meta = Vec::<u8>::decode(&mut res.as_slice()).map_err(Error::Decode)?;
+        meta::from_bytes(&mut meta.as_slice()).map_err(|_| Error::BadMetadata)
+    }
+
+    async fn block_info(&self, at: Option<u32>) -> crate::Result<BlockInfo> {
+        let mut follow = self.follow.lock().await;
+        let follow = self.following(&mut follow).await?;
+        // the genesis is rarely pinned but its hash is part of the chain spec
+        if at == Some(0) && self.pinned_hash(follow, 0).await?.is_none() {
+            let genesis: String = self.request("chainSpec_v1_genesisHash", &[]).await?;
+            return Ok(BlockInfo {
+                number: 0,
+                hash: hash_from_hex(&genesis)?,
+                parent: [0; 32],
+            });
+        }
+        let hash = self.block_hash(follow, at).await?;
+        let header = self.header(follow, &hash).await?;
+
+        Ok(BlockInfo {
+            number: header.number as u64,
+            hash: hash_from_hex(&hash)?,
+            parent: header.parent,
+        })
+    }
+}
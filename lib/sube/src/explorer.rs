@@ -0,0 +1,223 @@
+//! Browsable views of the metadata under the `_meta` path.
+//!
+//! - `_meta` and `_meta/registry` are the whole metadata and type registry
+//! - `_meta/pallets` lists the pallets with the number of items of each kind
+//! - `_meta/<pallet>` has the storage, calls, events, errors and constants of a pallet
+//! - `_meta/<pallet>/<kind>[/<name>]` is one of those lists or a single item of it
+//! - `_meta/<pallet>/<kind>/<name>/schema` is the JSON Schema of the arguments of a call,
+//!   the fields of an event or error or the value of a storage item or constant
+//!
+//! Types are shown with readable names(`type`), the name given in the runtime
+//! source(`typeName`) and their id in the registry(`typeId`).
+use crate::meta::{self, EntryTy as _, EntryType, Meta as _, PalletMeta, StorageEntry};
+use crate::prelude::*;
+use crate::util::same_name;
+use crate::{json, Error, JsonValue, Metadata, Response, Result, Value};
+use scale_info::{form::PortableForm, Field, TypeDef};
+use scales::JsonSchema;
+
+/// Answers a path relative to `_meta`
+pub(crate) fn query<'m>(meta: &'m Metadata, path: &str) -> Result<Response<'m>> {
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+    let (pallet, kind, name) = (segments.next(), segments.next(), segments.next());
+    let schema = match segments.next() {
+        None => false,
+        Some("schema") => true,
+        Some(_) => return Err(Error::BadInput),
+    };
+    if segments.next().is_some() {
+        return Err(Error::BadInput);
+    }
+
+    let info = match (pallet, kind) {
+        (None, _) => return Ok(Response::Meta(meta)),
+        (Some("registry"), None) => return Ok(Response::Registry(&meta.types)),
+        (Some("pallets"), None) => {
+            return Ok(Response::Json(
+                meta.pallets.iter().map(|p| summary(meta, p)).collect(),
+            ))
+        }
+        (Some(pallet), _) => {
+            let pallet = meta
+                .pallet_by_name(pallet)
+                .ok_or_else(|| Error::PalletNotFound(pallet.into()))?;
+            if let (true, Some(kind), Some(name)) = (schema, kind, name) {
+                return item_schema(meta, pallet, kind, name).map(Response::Json);
+            }
+            pallet_info(meta, pallet)
+        }
+    };
+
+    let Some(kind) = kind else {
+        return Ok(Response::Json(info));
+    };
+    let items = info.get(kind).ok_or(Error::BadInput)?;
+    let Some(name) = name else {
+        return Ok(Response::Json(items.clone()));
+    };
+    items
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .find(|i| i["name"].as_str().is_some_and(|n| same_name(n, name)))
+        })
+        .map(|item| Response::Json(item.clone()))
+        .ok_or(Error::BadInput)
+}
+
+fn summary(meta: &Metadata, pallet: &PalletMeta) -> JsonValue {
+    let info = pallet_info(meta, pallet);
+    let count = |kind: &str| info[kind].as_array().map_or(0, Vec::len);
+    json!({
+        "name": pallet.name,
+        "index": pallet.index,
+        "storage": count("storage"),
+        "calls": count("calls"),
+        "events": count("events"),
+        "errors": count("errors"),
+        "constants": count("constants"),
+    })
+}
+
+fn pallet_info(meta: &Metadata, pallet: &PalletMeta) -> JsonValue {
+    let storage = pallet
+        .storage
+        .iter()
+        .flat_map(|s| s.entries.iter())
+        .map(|e| storage_entry(meta, e))
+        .collect::<Vec<_>>();
+    let constants = pallet
+        .constants
+        .iter()
+        .map(|c| {
+            let value: JsonValue = Value::new(c.value.clone(), c.ty.id, &meta.types).into();
+            json!({
+                "name": c.name,
+                "type": meta::type_name(&meta.types, c.ty.id),
+                "typeId": c.ty.id,
+                "value": value,
+                "docs": docs(&c.docs),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "name": pallet.name,
+        "index": pallet.index,
+        "storage": storage,
+        "calls": variants(meta, pallet.calls.as_ref().map(|c| c.ty.id), "args"),
+        "events": variants(meta, pallet.event.as_ref().map(|e| e.ty.id), "fields"),
+        "errors": variants(meta, pallet.error.as_ref().map(|e| e.ty.id), "fields"),
+        "constants": constants,
+    })
+}
+
+/// JSON Schema of the input the serializer takes for an item of a pallet
+fn item_schema(meta: &Metadata, pallet: &PalletMeta, kind: &str, name: &str) -> Result<JsonValue> {
+    let mut schema = JsonSchema::new(&meta.types);
+    let root = match kind {
+        "storage" => pallet
+            .storage
+            .iter()
+            .flat_map(|s| s.entries.iter())
+            .find(|e| same_name(&e.name, name))
+            .map(|e| schema.of_type(e.ty.value_ty())),
+        "constants" => pallet
+            .constants
+            .iter()
+            .find(|c| same_name(&c.name, name))
+            .map(|c| schema.of_type(c.ty.id)),
+        "calls" | "events" | "errors" => {
+            let ty = match kind {
+                "calls" => pallet.calls.as_ref().map(|c| c.ty.id),
+                "events" => pallet.event.as_ref().map(|e| e.ty.id),
+                _ => pallet.error.as_ref().map(|e| e.ty.id),
+            };
+            match ty
+                .and_then(|ty| meta.types.resolve(ty))
+                .map(|t| &t.type_def)
+            {
+                Some(TypeDef::Variant(v)) => v
+                    .variants
+                    .iter()
+                    .find(|v| same_name(&v.name, name))
+                    .map(|v| schema.of_fields(&v.fields)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    root.map(|root| schema.document(root))
+        .ok_or(Error::BadInput)
+}
+
+fn storage_entry(meta: &Metadata, entry: &StorageEntry) -> JsonValue {
+    let name = |id| meta::type_name(&meta.types, id);
+    let (value, keys, hashers) = match &entry.ty {
+        EntryType::Plain(ty) => (ty.id, vec![], vec![]),
+        EntryType::Map {
+            hashers,
+            key,
+            value,
+        } => {
+            let keys = match meta.types.resolve(key.id).map(|t| &t.type_def) {
+                Some(TypeDef::Tuple(t)) if hashers.len() > 1 => {
+                    t.fields.iter().map(|f| name(f.id)).collect()
+                }
+                _ => vec![name(key.id)],
+            };
+            let hashers = hashers.iter().map(|h| format!("{:?}", h)).collect();
+            (value.id, keys, hashers)
+        }
+    };
+    json!({
+        "name": entry.name,
+        "modifier": format!("{:?}", entry.modifier),
+        "type": name(value),
+        "typeId": value,
+        "keys": keys,
+        "hashers": hashers,
+        "docs": docs(&entry.docs),
+    })
+}
+
+/// Calls, events and errors are the variants of an enum in the registry
+fn variants(meta: &Metadata, ty: Option<u32>, fields_key: &str) -> Vec<JsonValue> {
+    let Some(TypeDef::Variant(v)) = ty
+        .and_then(|ty| meta.types.resolve(ty))
+        .map(|t| &t.type_def)
+    else {
+        return vec![];
+    };
+    v.variants
+        .iter()
+        .map(|v| {
+            let mut variant = json!({
+                "name": v.name,
+                "index": v.index,
+                "docs": docs(&v.docs),
+            });
+            variant[fields_key] = v.fields.iter().map(|f| field(meta, f)).collect();
+            variant
+        })
+        .collect()
+}
+
+fn field(meta: &Metadata, field: &Field<PortableForm>) -> JsonValue {
+    json!({
+        "name": field.name,
+        "type": meta::type_name(&meta.types, field.ty.id),
+        "typeName": field.type_name,
+        "typeId": field.ty.id,
+    })
+}
+
+/// Doc comments come in lines that keep the space after the `///`
+fn docs(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|l| l.strip_prefix(' ').unwrap_or(l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
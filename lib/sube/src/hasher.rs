@@ -37,6 +37,11 @@ pub fn hash<I: AsRef<[u8]>>(hasher: &Hasher, input: I) -> Vec<u8> {
     }
 }
 
+/// Blake2 256 hash of raw bytes(i.e. not treating `0x` prefixed input as hex)
+pub fn blake2_256(input: impl AsRef<[u8]>) -> [u8; 32] {
+    Blake2b::<U32>::digest(input.as_ref()).into()
+}
+
 fn twox_hash_concat(input: &[u8]) -> Vec<u8> {
     let mut dest = [0; 8];
     let mut h = twox_hash::XxHash64::with_seed(0);
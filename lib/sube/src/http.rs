@@ -1,16 +1,23 @@
 use crate::prelude::*;
 use crate::rpc::{self, Rpc, RpcResult};
+use crate::{BackendConfig, Error};
 use core::{convert::TryInto, fmt};
 use jsonrpc::{
     error::{standard_error, StandardError},
-    serde_json::value::to_raw_value,
+    serde_json::value::{to_raw_value, RawValue},
 };
-use reqwest::Client;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::{Certificate, Proxy};
 use serde::Deserialize;
 pub use url::Url;
 
-#[derive(Debug)]
-pub struct Backend(Url);
+/// HTTP backend, clones share the same pool of connections
+#[derive(Clone, Debug)]
+pub struct Backend(Url, Client);
 
 impl Backend {
     pub fn new<U>(url: U) -> Self
@@ -18,7 +25,116 @@ impl Backend {
         U: TryInto<Url>,
         <U as TryInto<Url>>::Error: fmt::Debug,
     {
-        Backend(url.try_into().expect("Url"))
+        Self::with_client(url, Client::new())
+    }
+
+    /// Backend that reuses the connection pool of an existing client
+    pub fn with_client<U>(url: U, client: Client) -> Self
+    where
+        U: TryInto<Url>,
+        <U as TryInto<Url>>::Error: fmt::Debug,
+    {
+        Backend(url.try_into().expect("Url"), client)
+    }
+
+    /// Backend with custom headers, credentials, timeouts, proxy or
+    /// root certificates
+    pub fn with_config<U>(url: U, config: &BackendConfig) -> Result<Self, Error>
+    where
+        U: TryInto<Url>,
+        <U as TryInto<Url>>::Error: fmt::Debug,
+    {
+        let mut headers = HeaderMap::new();
+        for (name, value) in config.all_headers() {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::Platform(e.to_string()))?;
+            let value =
+                HeaderValue::from_str(&value).map_err(|e| Error::Platform(e.to_string()))?;
+            headers.append(name, value);
+        }
+        let builder = Client::builder().default_headers(headers);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = {
+            let mut builder = builder;
+            if let Some(timeout) = config.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if let Some(timeout) = config.connect_timeout {
+                builder = builder.connect_timeout(timeout);
+            }
+            if let Some(proxy) = &config.proxy {
+                let proxy = Proxy::all(proxy).map_err(|e| Error::Platform(e.to_string()))?;
+                builder = builder.proxy(proxy);
+            }
+            for pem in &config.root_certs {
+                let certs = Certificate::from_pem_bundle(pem)
+                    .map_err(|e| Error::Platform(e.to_string()))?;
+                for cert in certs {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+            builder
+        };
+        // the browser is in charge of the connection
+        #[cfg(target_arch = "wasm32")]
+        if config.timeout.is_some()
+            || config.connect_timeout.is_some()
+            || config.proxy.is_some()
+            || !config.root_certs.is_empty()
+        {
+            return Err(Error::Platform(
+                "only headers can be configured in the browser".into(),
+            ));
+        }
+
+        let client = builder
+            .build()
+            .map_err(|e| Error::Platform(e.to_string()))?;
+        Ok(Self::with_client(url, client))
+    }
+
+    async fn post<T>(&self, body: &impl serde::Serialize) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let res = self
+            .1
+            .post(self.0.to_string())
+            .json(body)
+            .send()
+            .await
+            .map_err(|err| rpc::error::Error::Transport(Box::new(err)))?;
+
+        let status = res.status();
+        if status.is_success() {
+            return res.json::<T>().await.map_err(|err| {
+                standard_error(
+                    StandardError::ParseError,
+                    Some(to_raw_value(&err.to_string()).unwrap()),
+                )
+                .into()
+            });
+        }
+
+        log::debug!("RPC HTTP status: {}", res.status());
+        // gateways answer for nodes that are down or overloaded
+        if matches!(status.as_u16(), 502..=504) {
+            let err = format!("node unavailable: {}", status);
+            return Err(rpc::error::Error::Transport(err.into()));
+        }
+        let err = res
+            .text()
+            .await
+            .unwrap_or_else(|_| status.canonical_reason().expect("to have a message").into());
+
+        let err = to_raw_value(&err).expect("error string");
+
+        Err(if status.is_client_error() {
+            standard_error(StandardError::InvalidRequest, Some(err)).into()
+        } else {
+            standard_error(StandardError::InternalError, Some(err)).into()
+        })
     }
 }
 
@@ -30,45 +146,49 @@ impl Rpc for Backend {
     {
         log::info!("RPC `{}` to {}", method, &self.0);
 
-        let res = Client::new()
-            .post(self.0.to_string())
-            .json(&rpc::Request {
-                id: 1.into(),
+        self.post::<rpc::Response>(&rpc::Request {
+            id: 1.into(),
+            jsonrpc: Some("2.0"),
+            method,
+            params: &Self::convert_params(params),
+        })
+        .await?
+        .result::<T>()
+    }
+
+    /// Sends all the requests in a single JSON-RPC batch array
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        log::info!("RPC batch of {} requests to {}", requests.len(), &self.0);
+
+        let params = requests
+            .iter()
+            .map(|(_, params)| Self::convert_params(params))
+            .collect::<Vec<_>>();
+        let batch = requests
+            .iter()
+            .zip(&params)
+            .enumerate()
+            .map(|(id, ((method, _), params))| rpc::Request {
+                id: id.into(),
                 jsonrpc: Some("2.0"),
                 method,
-                params: &Self::convert_params(params),
+                params,
             })
-            .send()
-            .await
-            .map_err(|err| rpc::error::Error::Transport(Box::new(err)))?;
-
-        let status = res.status();
-        let res = if status.is_success() {
-            res.json::<rpc::Response>()
-                .await
-                .map_err(|err| {
-                    standard_error(
-                        StandardError::ParseError,
-                        Some(to_raw_value(&err.to_string()).unwrap()),
-                    )
-                })?
-                .result::<T>()?
-        } else {
-            log::debug!("RPC HTTP status: {}", res.status());
-            let err = res
-                .text()
-                .await
-                .unwrap_or_else(|_| status.canonical_reason().expect("to have a message").into());
-
-            let err = to_raw_value(&err).expect("error string");
-
-            return Err(if status.is_client_error() {
-                standard_error(StandardError::InvalidRequest, Some(err)).into()
-            } else {
-                standard_error(StandardError::InternalError, Some(err)).into()
-            });
-        };
+            .collect::<Vec<_>>();
 
-        Ok(res)
+        let responses = self.post::<Vec<rpc::Response>>(&batch).await?;
+        // responses can come in any order
+        let mut results = vec![None; requests.len()];
+        for res in responses {
+            let id = res.id.as_u64().ok_or(rpc::error::Error::NonceMismatch)? as usize;
+            let slot = results
+                .get_mut(id)
+                .ok_or(rpc::error::Error::NonceMismatch)?;
+            *slot = Some(rpc::raw_result(res)?);
+        }
+        results
+            .into_iter()
+            .map(|res| res.ok_or(rpc::error::Error::WrongBatchResponseSize))
+            .collect()
     }
 }
@@ -0,0 +1,411 @@
+//! Human friendly forms of chain values, account ids as SS58 addresses and
+//! balances in units of the chain's token(e.g. `"1.5 KSM"`).
+//!
+//! The registry types tell where accounts and balances are expected in the
+//! JSON of call arguments and decoded values, balances are the numeric fields
+//! with a `Balance` type name like `T::Balance` or `BalanceOf<T>`.
+use crate::prelude::*;
+use crate::util::same_name;
+use crate::{json, ss58, Error, JsonValue, MapKey, Metadata, Response, Result, Value};
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+
+/// Native token of a chain, its decimals give the value of one unit
+/// in the smallest denomination(planck)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl Token {
+    pub fn new(symbol: impl Into<String>, decimals: u8) -> Self {
+        Token {
+            symbol: symbol.into(),
+            decimals,
+        }
+    }
+
+    /// Formats an amount of planck in token units, e.g. `1.5 KSM`
+    pub fn format(&self, amount: u128) -> String {
+        let Some(unit) = 10u128.checked_pow(self.decimals.into()) else {
+            return format!("{} {}", amount, self.symbol);
+        };
+        let (units, fraction) = (amount / unit, amount % unit);
+        if fraction == 0 {
+            return format!("{} {}", units, self.symbol);
+        }
+        let fraction = format!("{:0width$}", fraction, width = self.decimals as usize);
+        format!(
+            "{}.{} {}",
+            units,
+            fraction.trim_end_matches('0'),
+            self.symbol
+        )
+    }
+
+    /// Parses an amount in token units like `1.5 KSM` into planck,
+    /// the symbol is required and compared ignoring its case
+    pub fn parse(&self, amount: &str) -> Option<u128> {
+        let amount = amount.trim();
+        let split = amount.len().checked_sub(self.symbol.len())?;
+        let (number, symbol) = (amount.get(..split)?, amount.get(split..)?);
+        if !symbol.eq_ignore_ascii_case(&self.symbol) {
+            return None;
+        }
+        let number = number.trim().replace('_', "");
+        let (units, fraction) = number.split_once('.').unwrap_or((&number, ""));
+        let decimals = u32::from(self.decimals);
+        if (units.is_empty() && fraction.is_empty()) || fraction.len() > decimals as usize {
+            return None;
+        }
+        let digits = |s: &str| match s {
+            "" => Some(0),
+            s if s.bytes().all(|b| b.is_ascii_digit()) => s.parse::<u128>().ok(),
+            _ => None,
+        };
+        let units = digits(units)?.checked_mul(10u128.checked_pow(decimals)?)?;
+        let fraction =
+            digits(fraction)?.checked_mul(10u128.checked_pow(decimals - fraction.len() as u32)?)?;
+        units.checked_add(fraction)
+    }
+}
+
+/// How [`render`] presents the decoded values of a response
+#[derive(Clone, Debug, Default)]
+pub struct Format {
+    /// Accounts are shown as SS58 addresses with this prefix
+    pub ss58: Option<u16>,
+    /// Balances are shown in units of this token
+    pub token: Option<Token>,
+}
+
+impl Format {
+    /// Accounts as SS58 addresses with the `SS58Prefix` of the chain
+    pub fn with_ss58(self, meta: &Metadata) -> Self {
+        Format {
+            ss58: Some(ss58::prefix(meta)),
+            ..self
+        }
+    }
+
+    pub fn with_token(self, token: Option<Token>) -> Self {
+        Format { token, ..self }
+    }
+}
+
+/// Converts a query response to JSON with its accounts and balances in the
+/// given format, responses without decoded values are unchanged
+pub fn render<'m>(res: Response<'m>, meta: &'m Metadata, format: &Format) -> Response<'m> {
+    let json = |v: Value| {
+        let ty = v.ty_id();
+        let mut json = v.into();
+        render_value(&meta.types, ty, &mut json, format);
+        json
+    };
+    let keys = |keys: Vec<MapKey>| {
+        keys.into_iter()
+            .map(|key| match key {
+                MapKey::Value(v) => json(v),
+                key => json!(key),
+            })
+            .collect::<Vec<_>>()
+    };
+    let entry = |(k, value): (Vec<MapKey>, Value)| json!([keys(k), json(value)]);
+    match res {
+        Response::Value(v) => Response::Json(json(v)),
+        Response::ValueSet(set) => Response::Json(set.into_iter().map(entry).collect()),
+        Response::Diff(diff) => Response::Json(json!({
+            "added": diff.added.into_iter().map(entry).collect::<Vec<_>>(),
+            "removed": diff.removed.into_iter().map(entry).collect::<Vec<_>>(),
+            "changed": diff
+                .changed
+                .into_iter()
+                .map(|(k, old, new)| json!([keys(k), json(old), json(new)]))
+                .collect::<Vec<_>>(),
+        })),
+        res => res,
+    }
+}
+
+/// Replaces the accounts and balances of a JSON value of the given type with their human form
+pub(crate) fn render_value(
+    registry: &PortableRegistry,
+    ty: u32,
+    value: &mut JsonValue,
+    format: &Format,
+) {
+    let _ = visit(registry, ty, value, &mut |kind, value| {
+        match (kind, format) {
+            (
+                Kind::Balance,
+                Format {
+                    token: Some(token), ..
+                },
+            ) => {
+                if let Ok(amount) = value.to_string().parse::<u128>() {
+                    *value = token.format(amount).into();
+                }
+            }
+            (
+                Kind::Account,
+                Format {
+                    ss58: Some(prefix), ..
+                },
+            ) => {
+                if let Some(account) = account_from_json(value) {
+                    *value = ss58::encode(*prefix, &account).into();
+                }
+            }
+            (
+                Kind::MultiAddress,
+                Format {
+                    ss58: Some(prefix), ..
+                },
+            ) => {
+                if let Some(id) = value.as_object_mut().and_then(|o| variant_mut(o, "Id")) {
+                    if let Some(account) = account_from_json(id) {
+                        *id = ss58::encode(*prefix, &account).into();
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    });
+}
+
+/// Replaces the accounts and amounts given as strings in a JSON value of the given
+/// type with what the encoder expects, as other `0x` hex strings of byte types.
+/// Amounts like `1.5 KSM` need the token of the chain.
+pub(crate) fn parse_input(
+    registry: &PortableRegistry,
+    ty: u32,
+    value: &mut JsonValue,
+    token: Option<&Token>,
+) -> Result<()> {
+    visit(registry, ty, value, &mut |kind, value| {
+        match (kind, value) {
+            (Kind::Account, value @ JsonValue::String(_)) => {
+                *value = account_json(&ss58::account(value.as_str().expect("string"))?);
+            }
+            (Kind::MultiAddress, value @ JsonValue::String(_)) => {
+                let account = ss58::account(value.as_str().expect("string"))?;
+                *value = json!({ "Id": account_json(&account) });
+            }
+            (Kind::MultiAddress, JsonValue::Object(o)) => {
+                if let Some(id @ JsonValue::String(_)) = variant_mut(o, "Id") {
+                    *id = account_json(&ss58::account(id.as_str().expect("string"))?);
+                }
+            }
+            (Kind::Balance, value) if is_amount(value) => {
+                let amount = value.as_str().expect("string");
+                let planck = token
+                    .and_then(|t| t.parse(amount))
+                    .ok_or_else(|| Error::Encode(format!("Can't read amount {}", amount)))?;
+                *value = serde_json::to_value(planck).expect("u128 to JSON");
+            }
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+/// Whether the JSON value of the given type has amounts in token units
+pub(crate) fn has_amounts(registry: &PortableRegistry, ty: u32, value: &JsonValue) -> bool {
+    let mut found = false;
+    let _ = visit(registry, ty, &mut value.clone(), &mut |kind, value| {
+        found |= matches!(kind, Kind::Balance) && is_amount(value);
+        Ok(())
+    });
+    found
+}
+
+/// Amounts in planck are numbers or numeric strings, the rest have a token symbol
+fn is_amount(value: &JsonValue) -> bool {
+    value
+        .as_str()
+        .is_some_and(|s| s.chars().any(|c| c.is_alphabetic()))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Kind {
+    Account,
+    MultiAddress,
+    Balance,
+}
+
+/// Kind of the account types, balances are only known by the name of the field holding them
+pub(crate) fn kind(registry: &PortableRegistry, ty: u32) -> Option<Kind> {
+    let ty = registry.resolve(ty)?;
+    match ty.path.segments.last()?.as_str() {
+        "AccountId32" => Some(Kind::Account),
+        "MultiAddress" => Some(Kind::MultiAddress),
+        _ => None,
+    }
+}
+
+fn is_balance(registry: &PortableRegistry, field: &Field<PortableForm>) -> bool {
+    let Some(type_name) = &field.type_name else {
+        return false;
+    };
+    let name = type_name.rsplit("::").next().unwrap_or_default();
+    let name = name.split('<').next().unwrap_or_default();
+    if !(name.ends_with("Balance") || name == "BalanceOf") {
+        return false;
+    }
+    let ty = match registry.resolve(field.ty.id).map(|t| &t.type_def) {
+        Some(TypeDef::Compact(c)) => registry.resolve(c.type_param.id).map(|t| &t.type_def),
+        ty => ty,
+    };
+    matches!(
+        ty,
+        Some(TypeDef::Primitive(
+            TypeDefPrimitive::U32 | TypeDefPrimitive::U64 | TypeDefPrimitive::U128
+        ))
+    )
+}
+
+/// Walks a JSON value along its registry type calling `f` with the accounts and balances
+fn visit<F>(registry: &PortableRegistry, ty_id: u32, value: &mut JsonValue, f: &mut F) -> Result<()>
+where
+    F: FnMut(Kind, &mut JsonValue) -> Result<()>,
+{
+    if let Some(kind) = kind(registry, ty_id) {
+        return f(kind, value);
+    }
+    let Some(ty) = registry.resolve(ty_id) else {
+        return Ok(());
+    };
+    match (&ty.type_def, value) {
+        (TypeDef::Sequence(s), value @ JsonValue::String(_)) => {
+            hex_bytes(registry, s.type_param.id, value)
+        }
+        (TypeDef::Array(a), value @ JsonValue::String(_)) => {
+            hex_bytes(registry, a.type_param.id, value)
+        }
+        (TypeDef::Composite(c), value) => visit_fields(registry, &c.fields, value, f),
+        // options are the inner value or null
+        (TypeDef::Variant(v), value) if ty.path.segments.last().is_some_and(|s| s == "Option") => {
+            match v.variants.iter().find(|v| v.name == "Some") {
+                Some(some) if !value.is_null() => visit_fields(registry, &some.fields, value, f),
+                _ => Ok(()),
+            }
+        }
+        (TypeDef::Variant(v), JsonValue::Object(o)) => {
+            for (name, inner) in o.iter_mut() {
+                if let Some(variant) = v.variants.iter().find(|v| same_name(&v.name, name)) {
+                    visit_fields(registry, &variant.fields, inner, f)?;
+                }
+            }
+            Ok(())
+        }
+        (TypeDef::Sequence(s), JsonValue::Array(items)) => items
+            .iter_mut()
+            .try_for_each(|i| visit(registry, s.type_param.id, i, f)),
+        (TypeDef::Array(a), JsonValue::Array(items)) => items
+            .iter_mut()
+            .try_for_each(|i| visit(registry, a.type_param.id, i, f)),
+        (TypeDef::Tuple(t), JsonValue::Array(items)) => t
+            .fields
+            .iter()
+            .zip(items)
+            .try_for_each(|(ty, i)| visit(registry, ty.id, i, f)),
+        _ => Ok(()),
+    }
+}
+
+fn visit_fields<F>(
+    registry: &PortableRegistry,
+    fields: &[Field<PortableForm>],
+    value: &mut JsonValue,
+    f: &mut F,
+) -> Result<()>
+where
+    F: FnMut(Kind, &mut JsonValue) -> Result<()>,
+{
+    let mut visit_field = |field: &Field<PortableForm>, value: &mut JsonValue| {
+        if is_balance(registry, field) {
+            f(Kind::Balance, value)
+        } else {
+            visit(registry, field.ty.id, value, f)
+        }
+    };
+    match (fields, value) {
+        ([field], value) if field.name.is_none() => visit_field(field, value),
+        (fields, JsonValue::Object(o)) => fields.iter().try_for_each(|field| {
+            match field.name.as_deref().and_then(|name| variant_mut(o, name)) {
+                Some(inner) => visit_field(field, inner),
+                None => Ok(()),
+            }
+        }),
+        (fields, JsonValue::Array(items)) => fields
+            .iter()
+            .zip(items)
+            .try_for_each(|(field, i)| visit_field(field, i)),
+        _ => Ok(()),
+    }
+}
+
+/// `0x` hex strings given for sequences or arrays of bytes
+fn hex_bytes(registry: &PortableRegistry, item_ty: u32, value: &mut JsonValue) -> Result<()> {
+    let is_byte = registry
+        .resolve(item_ty)
+        .is_some_and(|ty| matches!(ty.type_def, TypeDef::Primitive(TypeDefPrimitive::U8)));
+    let JsonValue::String(s) = value else {
+        return Ok(());
+    };
+    if let (true, Some(hex)) = (is_byte, s.strip_prefix("0x")) {
+        let bytes = hex::decode(hex).map_err(|_| Error::BadInput)?;
+        *value = bytes.into();
+    }
+    Ok(())
+}
+
+fn account_json(account: &[u8; 32]) -> JsonValue {
+    account.to_vec().into()
+}
+
+fn account_from_json(value: &JsonValue) -> Option<[u8; 32]> {
+    let bytes = value
+        .as_array()?
+        .iter()
+        .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect::<Option<Vec<_>>>()?;
+    bytes.try_into().ok()
+}
+
+fn variant_mut<'a>(
+    obj: &'a mut serde_json::Map<String, JsonValue>,
+    name: &str,
+) -> Option<&'a mut JsonValue> {
+    obj.iter_mut()
+        .find(|(k, _)| same_name(k, name))
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_amounts() {
+        let ksm = Token::new("KSM", 12);
+        assert_eq!(ksm.format(1_500_000_000_000), "1.5 KSM");
+        assert_eq!(ksm.format(2_000_000_000_000), "2 KSM");
+        assert_eq!(ksm.format(1), "0.000000000001 KSM");
+        assert_eq!(Token::new("UNIT", 0).format(7), "7 UNIT");
+    }
+
+    #[test]
+    fn parses_amounts() {
+        let ksm = Token::new("KSM", 12);
+        assert_eq!(ksm.parse("1.5 KSM"), Some(1_500_000_000_000));
+        assert_eq!(ksm.parse("2ksm"), Some(2_000_000_000_000));
+        assert_eq!(ksm.parse(".25 KSM"), Some(250_000_000_000));
+        assert_eq!(ksm.parse("1_000 KSM"), Some(1_000_000_000_000_000));
+        assert_eq!(ksm.parse("1.5 DOT"), None);
+        assert_eq!(ksm.parse("1.5"), None);
+        assert_eq!(ksm.parse("0.0000000000001 KSM"), None);
+        assert_eq!(ksm.parse("1e3 KSM"), None);
+    }
+}
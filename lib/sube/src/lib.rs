@@ -15,13 +15,14 @@ compile_error!("Enable one of the metadata versions");
 #[macro_use]
 extern crate alloc;
 
+use alloc::collections::BTreeMap;
 pub use codec;
 use codec::Encode;
 pub use core::fmt::Display;
 use core::iter::Empty;
 
 pub use frame_metadata::RuntimeMetadataPrefixed;
-pub use signer::{Signer, SignerFn, Bytes};
+pub use signer::{Bytes, Scheme, Signer, SignerFn};
 
 pub use meta::Metadata;
 #[cfg(feature = "v14")]
@@ -32,7 +33,7 @@ use core::fmt;
 use hasher::hash;
 // use meta::Meta;
 use meta_ext::{self as meta, Meta as _};
-use meta_ext::{KeyValue, StorageKey};
+use meta_ext::{EntryTy as _, StorageKey};
 use prelude::*;
 #[cfg(feature = "v14")]
 use scale_info::PortableRegistry;
@@ -50,17 +51,54 @@ mod prelude {
 /// Surf based backend
 #[cfg(any(feature = "http", feature = "http-web"))]
 pub mod http;
-/// Tungstenite based backend
-#[cfg(feature = "ws")]
+/// Websocket backend, runs on async-std or on tokio with the `ws-tokio` feature
+#[cfg(any(feature = "ws", feature = "ws-tokio"))]
 pub mod ws;
+/// Backend using the chainHead and transaction functions of the new JSON-RPC spec
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+pub mod chain_head;
+/// Smoldot based light client backend
+#[cfg(feature = "light")]
+pub mod light;
+
+#[cfg(feature = "pool")]
+pub mod pool;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
+#[cfg(feature = "middleware")]
+pub mod middleware;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(feature = "replay")]
+pub mod replay;
 
 pub mod builder;
 pub use builder::SubeBuilder;
+pub mod config;
+pub use config::BackendConfig;
+pub mod explorer;
 mod hasher;
+pub mod human;
 pub mod meta_ext;
+pub mod proof;
 mod signer;
-
-#[cfg(any(feature = "http", feature = "http-web", feature = "ws"))]
+pub mod ss58;
+pub mod typescript;
+
+#[cfg(any(
+    feature = "http",
+    feature = "http-web",
+    feature = "ws",
+    feature = "ws-tokio",
+    feature = "light",
+    feature = "pool",
+    feature = "middleware",
+    feature = "replay"
+))]
 pub mod rpc;
 pub mod util;
 
@@ -71,14 +109,158 @@ pub fn sube(url: &str) -> builder::SubeBuilder<(), ()> {
     builder::SubeBuilder::default().with_url(url)
 }
 
+/// Queries several storage items of a chain at the same block in one request,
+/// the paths are relative to the url, e.g. `sube_many("wss://node", &["system/number", ..])`
+pub async fn sube_many<'a>(url: &'a str, paths: &[&str]) -> Result<Vec<Response<'a>>> {
+    sube(url).query_many(paths).await
+}
+
 pub type Result<T> = core::result::Result<T, Error>;
 
-async fn query<'m>(
+/// Query the storage or constants of a chain with a human friendly path,
+/// e.g. `system/account/0x...`, raw keys can be queried with `_raw/0x<key>`
+/// and looked up without reaching the chain with `_key/0x<key>`.
+pub async fn query<'m>(
     chain: &impl Backend,
     meta: &'m Metadata,
     path: &str,
     block: Option<u32>,
 ) -> Result<Response<'m>> {
+    let path = path.trim_matches('/');
+    if let Some(child_path) = path.strip_prefix("_child/") {
+        return query_child(chain, meta, child_path, block).await;
+    }
+    if path == "_system/properties" {
+        return Ok(Response::Properties(chain.properties().await?));
+    }
+
+    match resolve(meta, path)? {
+        Resolved::Ready(res) => Ok(res),
+        Resolved::Item(item) => {
+            let value = match chain.get_storage_item(item.key.clone(), block).await {
+                Ok(value) => Some(value),
+                Err(Error::StorageKeyNotFound) => None,
+                Err(e) => return Err(e),
+            };
+            item.response(meta, value)
+        }
+        Resolved::Partial(key_res, entry) => {
+            let res = chain
+                .get_keys_paged(key_res.key(), 1000, None, block)
+                .await?;
+            let result = chain.get_storage_items(res, block).await?;
+
+            let value = result
+                .into_iter()
+                .map(|(key, data)| {
+                    (
+                        decode_map_keys(meta, entry, &key_res, &key),
+                        Value::new(data, key_res.ty, &meta.types),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            Ok(Response::ValueSet(value))
+        }
+    }
+}
+
+/// Query several storage items in a single request so their values come
+/// from the same block, the responses are in the order of the paths.
+/// Constants and `_key` lookups are resolved with the metadata, paths of
+/// maps missing some of their keys and child tries are not supported.
+pub async fn query_many<'m>(
+    chain: &impl Backend,
+    meta: &'m Metadata,
+    paths: &[&str],
+    block: Option<u32>,
+) -> Result<Vec<Response<'m>>> {
+    let resolved = paths
+        .iter()
+        .map(|path| {
+            let path = path.trim_matches('/');
+            if path.starts_with("_child/") {
+                return Err(Error::BadInput);
+            }
+            resolve(meta, path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let keys = resolved
+        .iter()
+        .filter_map(|r| match r {
+            Resolved::Item(item) => Some(item.key.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let values = if keys.is_empty() {
+        BTreeMap::new()
+    } else {
+        chain.get_storage_items(keys, block).await?.collect()
+    };
+
+    resolved
+        .into_iter()
+        .map(|r| match r {
+            Resolved::Ready(res) => Ok(res),
+            Resolved::Item(item) => {
+                let value = values.get(&item.key).cloned();
+                item.response(meta, value)
+            }
+            Resolved::Partial(..) => Err(Error::BadInput),
+        })
+        .collect()
+}
+
+/// What a query path refers to before reaching the chain
+enum Resolved<'m> {
+    /// Answered with the metadata alone
+    Ready(Response<'m>),
+    Item(ItemQuery<'m>),
+    /// Storage map with some of its keys, all the matching items are queried
+    Partial(StorageKey, &'m meta::StorageEntry),
+}
+
+/// A single storage item, values are decoded with the type of its entry if known
+struct ItemQuery<'m> {
+    key: RawKey,
+    entry: Option<&'m meta::StorageEntry>,
+    ty: Option<u32>,
+}
+
+impl<'m> ItemQuery<'m> {
+    fn response(self, meta: &'m Metadata, value: Option<RawValue>) -> Result<Response<'m>> {
+        Ok(match (value, self.ty, self.entry) {
+            (Some(value), Some(ty), _) => Response::Value(Value::new(value, ty, &meta.types)),
+            (Some(value), None, _) => Response::Raw(value),
+            (None, _, Some(entry)) => default_value(meta, entry),
+            (None, _, None) => return Err(Error::StorageKeyNotFound),
+        })
+    }
+}
+
+fn resolve<'m>(meta: &'m Metadata, path: &str) -> Result<Resolved<'m>> {
+    if let Some(meta_path) = path.strip_prefix("_meta") {
+        if meta_path.is_empty() || meta_path.starts_with('/') {
+            return explorer::query(meta, meta_path).map(Resolved::Ready);
+        }
+    }
+    if let Some(key) = path.strip_prefix("_key/") {
+        let key = util::decode_hex(key).ok_or(Error::BadKey)?;
+        return storage_key_info(meta, &key)
+            .map(|info| Resolved::Ready(Response::Key(info)))
+            .ok_or(Error::StorageKeyNotFound);
+    }
+    if let Some(key) = path.strip_prefix("_raw/") {
+        let key = util::decode_hex(key).ok_or(Error::BadKey)?;
+        let entry = meta::lookup_storage_key(meta, &key).map(|(_, e, _)| e);
+        return Ok(Resolved::Item(ItemQuery {
+            key,
+            entry,
+            ty: entry.map(|e| e.ty.value_ty()),
+        }));
+    }
+
     let (pallet, item_or_call, mut keys) = parse_uri(path).ok_or(Error::BadInput)?;
     let pallet = meta
         .pallet_by_name(&pallet)
@@ -92,52 +274,213 @@ async fn query<'m>(
             .find(|c| c.name == const_name)
             .ok_or_else(|| Error::ConstantNotFound(const_name))?;
 
-        return Ok(Response::Value(Value::new(
+        return Ok(Resolved::Ready(Response::Value(Value::new(
             const_meta.value.clone(),
             const_meta.ty.id,
             &meta.types,
-        )));
+        ))));
     }
 
-    if let Ok(key_res) = StorageKey::build_with_registry(&meta.types, pallet, &item_or_call, &keys)
-    {
-        if !key_res.is_partial() {
-            let res = chain.get_storage_item(key_res.key(), block).await?;
-            return Ok(Response::Value(Value::new(res, key_res.ty, &meta.types)));
-        }
-
-        let res = chain.get_keys_paged(key_res.key(), 1000, None).await?;
-        let result = chain.get_storage_items(res, block).await?;
-
-        let value = result
-            .into_iter()
-            .map(|(key, data)| {
-                let key = &key[(key_res.pallet.len() + key_res.call.len())..];
-                let mut offset = 16; // TODO it depends on the hasher used to encode the key, then the size could change
-                let keys = key_res
-                    .args
-                    .iter()
-                    .map(|arg| match arg {
-                        KeyValue::Empty(type_id) | KeyValue::Value((type_id, _, _, _)) => {
-                            let hashed = &key[offset..];
-                            let value = Value::new(hashed.to_vec(), *type_id, &meta.types);
-                            offset += value.size() + 16;
-                            value
-                        }
-                    })
-                    .collect::<Vec<Value<'m>>>();
-
-                let value = Value::new(data.to_vec(), key_res.ty, &meta.types);
-                (keys, value)
-            })
-            .collect::<Vec<_>>();
+    let key_res = StorageKey::build_with_registry(&meta.types, pallet, &item_or_call, &keys)
+        .map_err(|_| Error::ChainUnavailable)?;
+    let entry = meta::storage_entry(pallet, &item_or_call).ok_or(Error::CantFindMethodInPallet)?;
+    if key_res.is_partial() {
+        return Ok(Resolved::Partial(key_res, entry));
+    }
+    Ok(Resolved::Item(ItemQuery {
+        key: key_res.key(),
+        entry: Some(entry),
+        ty: Some(key_res.ty),
+    }))
+}
 
-        Ok(Response::ValueSet(value))
-    } else {
-        Err(Error::ChainUnavailable)
+/// The value a storage entry resolves to when its key is not in the storage,
+/// `ValueQuery` entries use their metadata default while `OptionQuery` ones are `null`(`Response::Void`).
+fn default_value<'m>(meta: &'m Metadata, entry: &meta::StorageEntry) -> Response<'m> {
+    match entry.modifier {
+        meta::EntryModifier::Default => Response::Value(Value::new(
+            entry.default.clone(),
+            entry.ty.value_ty(),
+            &meta.types,
+        )),
+        meta::EntryModifier::Optional => Response::Void,
     }
 }
 
+/// Queries a child trie with paths of the form `<child_key>[/<raw_key>[/<type>|_hash]]`.
+/// Without a key the child trie keys are listed with their raw values,
+/// an optional registry type (id or name) is used to decode the value.
+async fn query_child<'m>(
+    chain: &impl Backend,
+    meta: &'m Metadata,
+    path: &str,
+    block: Option<u32>,
+) -> Result<Response<'m>> {
+    let mut segments = path.split('/');
+    let child_key = segments
+        .next()
+        .map(meta::child_storage_key)
+        .ok_or(Error::BadInput)??;
+
+    let Some(key) = segments.next() else {
+        let items = all_child_items(chain, child_key, block).await?;
+        return Ok(Response::RawSet(items));
+    };
+
+    let key = util::decode_hex(key).ok_or(Error::BadKey)?;
+    match segments.next() {
+        Some("_hash") => {
+            let hash = chain.get_child_storage_hash(child_key, key, block).await?;
+            Ok(Response::Raw(hash.to_vec()))
+        }
+        Some(ty) => {
+            let ty = meta::type_id_by_name(&meta.types, ty).ok_or(Error::BadInput)?;
+            let value = chain.get_child_storage_item(child_key, key, block).await?;
+            Ok(Response::Value(Value::new(value, ty, &meta.types)))
+        }
+        None => Ok(Response::Raw(
+            chain.get_child_storage_item(child_key, key, block).await?,
+        )),
+    }
+}
+
+/// Fetches the keys of a child trie page by page along with their values
+async fn all_child_items(
+    chain: &impl Backend,
+    child_key: RawKey,
+    block: Option<u32>,
+) -> Result<Vec<(RawKey, RawValue)>> {
+    const PAGE_SIZE: u16 = 1000;
+    let mut items = vec![];
+    let mut start = None;
+    loop {
+        let page = chain
+            .get_child_keys_paged(child_key.clone(), vec![], PAGE_SIZE, start, block)
+            .await?;
+        let is_last = page.len() < PAGE_SIZE as usize;
+        start = page.last().cloned();
+        if !page.is_empty() {
+            items.extend(
+                chain
+                    .get_child_storage_items(child_key.clone(), page, block)
+                    .await?,
+            );
+        }
+        if is_last || start.is_none() {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+/// Reverse lookup of a raw storage key, the map keys are decoded when
+/// their hasher is transparent, otherwise only their hash is returned.
+pub fn storage_key_info<'m>(meta: &'m Metadata, key: &[u8]) -> Option<StorageKeyInfo<'m>> {
+    let (pallet, entry, parts) = meta::lookup_storage_key(meta, key)?;
+    Some(StorageKeyInfo {
+        pallet: pallet.name.clone(),
+        item: entry.name.clone(),
+        keys: parts.into_iter().map(|p| MapKey::new(meta, p)).collect(),
+    })
+}
+
+/// Decodes the map keys of a full storage key of the entry queried with the (partial) key,
+/// keys of opaque hashers are only their hash
+fn decode_map_keys<'m>(
+    meta: &'m Metadata,
+    entry: &meta::StorageEntry,
+    key_res: &StorageKey,
+    key: &[u8],
+) -> Vec<MapKey<'m>> {
+    let rest = key
+        .get(key_res.pallet.len() + key_res.call.len()..)
+        .unwrap_or_default();
+    meta::map_key_parts(meta, entry, rest)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| MapKey::new(meta, p))
+        .collect()
+}
+
+/// Fetches every key under `prefix` at the given block, following the pages of `get_keys_paged`
+async fn all_keys(chain: &impl Backend, prefix: RawKey, block: Option<u32>) -> Result<Vec<RawKey>> {
+    const PAGE_SIZE: u16 = 1000;
+    let mut keys = vec![];
+    let mut start = None;
+    loop {
+        let page = chain
+            .get_keys_paged(prefix.clone(), PAGE_SIZE, start, block)
+            .await?;
+        let is_last = page.len() < PAGE_SIZE as usize;
+        start = page.last().cloned();
+        keys.extend(page);
+        if is_last || start.is_none() {
+            break;
+        }
+    }
+    Ok(keys)
+}
+
+/// Fetches the values of all the keys under `prefix` at the given block
+async fn all_items(
+    chain: &impl Backend,
+    prefix: RawKey,
+    block: Option<u32>,
+) -> Result<BTreeMap<RawKey, RawValue>> {
+    let keys = all_keys(chain, prefix, block).await?;
+    let mut items = BTreeMap::new();
+    for keys in keys.chunks(1000) {
+        items.extend(chain.get_storage_items(keys.to_vec(), block).await?);
+    }
+    Ok(items)
+}
+
+/// Compares the storage under a `pallet/item` prefix between two blocks,
+/// the `from` block is the reference and `to` defaults to the latest block.
+async fn diff<'m>(
+    chain: &impl Backend,
+    meta: &'m Metadata,
+    path: &str,
+    from: u32,
+    to: Option<u32>,
+) -> Result<Response<'m>> {
+    let (pallet, item, keys) = parse_uri(path).ok_or(Error::BadInput)?;
+    let pallet = meta
+        .pallet_by_name(&pallet)
+        .ok_or_else(|| Error::PalletNotFound(pallet))?;
+    let key_res = StorageKey::build_with_registry(&meta.types, pallet, &item, &keys)?;
+    let entry = meta::storage_entry(pallet, &item).ok_or(Error::CantFindMethodInPallet)?;
+
+    let mut old = all_items(chain, key_res.key(), Some(from)).await?;
+    let new = all_items(chain, key_res.key(), to).await?;
+
+    let item = |key: &[u8], data: RawValue| {
+        (
+            decode_map_keys(meta, entry, &key_res, key),
+            Value::new(data, key_res.ty, &meta.types),
+        )
+    };
+
+    let mut diff = StorageDiff::default();
+    for (key, data) in new {
+        match old.remove(&key) {
+            None => diff.added.push(item(&key, data)),
+            Some(old_data) if old_data != data => {
+                let (keys, old_value) = item(&key, old_data);
+                diff.changed
+                    .push((keys, old_value, Value::new(data, key_res.ty, &meta.types)));
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .into_iter()
+        .map(|(key, data)| item(&key, data))
+        .collect();
+
+    Ok(Response::Diff(diff))
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ExtrinsicBody<Body> {
     pub nonce: Option<u64>,
@@ -163,7 +506,8 @@ pub struct Data {
     flags: u128,
 }
 
-async fn submit<'m, V>(
+/// Encodes, signs and sends an extrinsic calling the `pallet/call` of the path
+pub async fn submit<'m, V>(
     chain: impl Backend,
     meta: &'m Metadata,
     path: &str,
@@ -182,46 +526,40 @@ where
 
     let mut encoded_call = vec![pallet.index];
 
-    let call_data = scales::to_vec_with_info(
-        &json!({
-            &item_or_call.to_lowercase(): &tx_data.body
-        }),
-        (type_registry, calls_ty).into(),
-    )
+    // accounts can be given as SS58 addresses and balances in token units
+    let mut call = json!({
+        &item_or_call.to_lowercase(): &tx_data.body
+    });
+    let token = if human::has_amounts(type_registry, calls_ty, &call) {
+        chain.properties().await?.token()
+    } else {
+        None
+    };
+    human::parse_input(type_registry, calls_ty, &mut call, token.as_ref())?;
+
+    let call_data = scales::to_vec_with_info(&call, (type_registry, calls_ty).into())
     .map_err(|e| Error::Encode(e.to_string()))?;
 
     encoded_call.extend(&call_data);
 
     let from_account = signer.account();
+    let address = signer::encode_address(meta, from_account.as_ref())?;
+    let mut genesis_block = None;
 
     let extra_params = {
         // ImmortalEra
         let era = 0u8;
 
         // Impl. Note: in a real-world use case, you should store your account's nonce somewhere else
-        let nonce = {
-            if let Some(nonce) = tx_data.nonce {
-                Ok(nonce)
-            } else {
-                let response = query(
-                    &chain,
-                    meta,
-                    &format!("system/account/0x{}", hex::encode(from_account.as_ref())),
-                    None,
-                )
-                .await?;
-
-                match response {
-                    Response::Value(value) => {
-                        let str = serde_json::to_string(&value).expect("wrong account info");
-                        let account_info: AccountInfo =
-                            serde_json::from_str(&str).expect("it must serialize");
-                        Ok(account_info.nonce)
-                    }
-                    _ => Err(Error::AccountNotFound),
-                }
+        let nonce = match tx_data.nonce {
+            Some(nonce) => nonce,
+            None => {
+                let (account, genesis) =
+                    account_with_genesis(&chain, meta, from_account.as_ref()).await?;
+                genesis_block = Some(genesis);
+                account.nonce
             }
-        }?;
+        };
 
         let tip: u128 = 0;
 
@@ -272,7 +610,11 @@ where
             "System_Version.transaction_version is not a number".into(),
         ))? as u32;
 
-        let genesis_block: Vec<u8> = chain.block_info(Some(0u32)).await?.into();
+        let genesis_block: Vec<u8> = match genesis_block {
+            Some(genesis) => genesis,
+            None => chain.block_info(Some(0u32)).await?,
+        }
+        .into();
 
         [
             spec_version.to_le_bytes().to_vec(),
@@ -297,16 +639,16 @@ where
     };
 
     let signature = signer.sign(payload).await?;
+    let signature = signer::encode_signature(meta, signer.scheme(), signature.as_ref())?;
 
     let extrinsic_call = {
         let encoded_inner = [
             // header: "is signed" (1 byte) + transaction protocol version (7 bytes)
             vec![0b10000000 + 4u8],
             // signer
-            vec![0x00],
-            from_account.as_ref().to_vec(),
+            address,
             // signature
-            [vec![0x01], signature.as_ref().to_vec()].concat(),
+            signature,
             // extra
             extra_params,
             // call data
@@ -322,9 +664,46 @@ where
         [len, encoded_inner].concat()
     };
 
-    chain.submit(&extrinsic_call).await?;
+    let hash = chain.submit(&extrinsic_call).await?;
 
-    Ok(Response::Void)
+    Ok(Response::Submitted {
+        hash,
+        extrinsic: extrinsic_call,
+    })
+}
+
+/// Info of the account submitting an extrinsic and the genesis block it
+/// signs for, fetched together to save a round trip
+async fn account_with_genesis(
+    chain: &impl Backend,
+    meta: &Metadata,
+    account: &[u8],
+) -> Result<(AccountInfo, meta::BlockInfo)> {
+    let path = format!("system/account/0x{}", hex::encode(account));
+    let (pallet, item, keys) = parse_uri(&path).ok_or(Error::BadInput)?;
+    let pallet = meta
+        .pallet_by_name(&pallet)
+        .ok_or(Error::PalletNotFound(pallet))?;
+    let key = StorageKey::build_with_registry(&meta.types, pallet, &item, &keys)?;
+    let entry = meta::storage_entry(pallet, &item).ok_or(Error::CantFindMethodInPallet)?;
+
+    let (items, genesis) = chain
+        .get_storage_items_with_block_info(vec![key.key()], None, Some(0))
+        .await?;
+    let info = match items.into_iter().next() {
+        Some((_, value)) => Response::Value(Value::new(value, key.ty, &meta.types)),
+        None => default_value(meta, entry),
+    };
+
+    match info {
+        Response::Value(value) => {
+            let str = serde_json::to_string(&value).expect("wrong account info");
+            let account_info: AccountInfo =
+                serde_json::from_str(&str).expect("it must serialize");
+            Ok((account_info, genesis))
+        }
+        _ => Err(Error::AccountNotFound),
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -332,9 +711,23 @@ where
 pub enum Response<'m> {
     Void,
     Value(scales::Value<'m>),
-    ValueSet(Vec<(Vec<scales::Value<'m>>, scales::Value<'m>)>),
+    ValueSet(Vec<(Vec<MapKey<'m>>, scales::Value<'m>)>),
+    Diff(StorageDiff<'m>),
+    Raw(#[serde(serialize_with = "util::serialize_hex")] RawValue),
+    RawSet(#[serde(serialize_with = "util::serialize_hex_pairs")] Vec<(RawKey, RawValue)>),
+    Key(StorageKeyInfo<'m>),
     Meta(&'m Metadata),
     Registry(&'m PortableRegistry),
+    /// Decoded values converted to JSON, e.g. with their accounts as SS58 addresses
+    Json(JsonValue),
+    Properties(ChainProperties),
+    /// An extrinsic sent to the chain, kept to track or resubmit it
+    Submitted {
+        #[serde(serialize_with = "util::serialize_hex")]
+        hash: [u8; 32],
+        #[serde(serialize_with = "util::serialize_hex")]
+        extrinsic: Vec<u8>,
+    },
 }
 
 impl From<Response<'_>> for Vec<u8> {
@@ -344,11 +737,126 @@ impl From<Response<'_>> for Vec<u8> {
             Response::Meta(m) => m.encode(),
             Response::Registry(r) => r.encode(),
             Response::ValueSet(r) => r.encode(),
+            Response::Diff(d) => (d.added, d.removed, d.changed).encode(),
+            Response::Raw(v) => v,
+            Response::RawSet(r) => r.encode(),
+            Response::Key(k) => (k.pallet, k.item, k.keys).encode(),
+            Response::Submitted { hash, extrinsic } => (hash, extrinsic).encode(),
+            Response::Json(j) => serde_json::to_vec(&j).expect("JSON value"),
+            Response::Properties(p) => p.into(),
             Response::Void => vec![0],
         }
     }
 }
 
+/// Name and version of the chain reported by the node with the properties of
+/// its native token, chains with several tokens list the native one first
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainProperties {
+    pub chain: String,
+    pub version: String,
+    pub token_symbol: Option<String>,
+    pub token_decimals: Option<u8>,
+    pub ss58_format: Option<u16>,
+}
+
+impl ChainProperties {
+    /// Reads the token and address format from the result of `system_properties`
+    pub fn new(chain: String, version: String, properties: &JsonValue) -> Self {
+        let first = |v: &JsonValue| match v {
+            JsonValue::Array(values) => values.first().cloned(),
+            v => Some(v.clone()),
+        };
+        ChainProperties {
+            chain,
+            version,
+            token_symbol: first(&properties["tokenSymbol"])
+                .and_then(|s| s.as_str().map(String::from)),
+            token_decimals: first(&properties["tokenDecimals"])
+                .and_then(|d| d.as_u64())
+                .and_then(|d| d.try_into().ok()),
+            ss58_format: properties["ss58Format"]
+                .as_u64()
+                .and_then(|f| f.try_into().ok()),
+        }
+    }
+
+    /// The native token, to parse and format balances
+    pub fn token(&self) -> Option<human::Token> {
+        Some(human::Token::new(
+            self.token_symbol.clone()?,
+            self.token_decimals?,
+        ))
+    }
+}
+
+impl From<ChainProperties> for Vec<u8> {
+    fn from(p: ChainProperties) -> Self {
+        (
+            p.chain,
+            p.version,
+            p.token_symbol,
+            p.token_decimals,
+            p.ss58_format,
+        )
+            .encode()
+    }
+}
+
+/// Storage entries that were added, removed or changed between two blocks.
+/// Entries are listed with their decoded map keys, changed entries
+/// carry both the old and the new value.
+#[derive(Serialize, Debug, Default)]
+pub struct StorageDiff<'m> {
+    pub added: Vec<(Vec<MapKey<'m>>, scales::Value<'m>)>,
+    pub removed: Vec<(Vec<MapKey<'m>>, scales::Value<'m>)>,
+    pub changed: Vec<(Vec<MapKey<'m>>, scales::Value<'m>, scales::Value<'m>)>,
+}
+
+/// The storage entry a raw key belongs to and its map keys
+#[derive(Serialize, Debug)]
+pub struct StorageKeyInfo<'m> {
+    pub pallet: String,
+    pub item: String,
+    pub keys: Vec<MapKey<'m>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum MapKey<'m> {
+    Value(scales::Value<'m>),
+    /// The key is hashed with an opaque hasher
+    Hash(#[serde(serialize_with = "util::serialize_hex")] Vec<u8>),
+}
+
+impl<'m> MapKey<'m> {
+    fn new(meta: &'m Metadata, part: meta::MapKeyPart) -> Self {
+        match part.value {
+            Some(value) => MapKey::Value(Value::new(value, part.ty, &meta.types)),
+            None => MapKey::Hash(part.hash),
+        }
+    }
+}
+
+impl Encode for MapKey<'_> {
+    fn size_hint(&self) -> usize {
+        self.as_ref().len()
+    }
+    fn using_encoded<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(self.as_ref())
+    }
+}
+
+impl AsRef<[u8]> for MapKey<'_> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Self::Value(v) => v.as_ref(),
+            Self::Hash(h) => h,
+        }
+    }
+}
+
 fn parse_uri(uri: &str) -> Option<(String, String, Vec<String>)> {
     let mut path = uri.trim_matches('/').split('/');
     let pallet = path.next().map(to_camel)?;
@@ -361,7 +869,7 @@ fn parse_uri(uri: &str) -> Option<(String, String, Vec<String>)> {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct StorageChangeSet {
     block: String,
-    changes: Vec<[String; 2]>,
+    changes: Vec<(String, Option<String>)>,
 }
 
 pub type RawKey = Vec<u8>;
@@ -373,7 +881,7 @@ pub type RawValue = Vec<u8>;
 /// pub trait Backend {
 ///     async fn query_bytes(&self, key: &StorageKey) -> Result<Vec<u8>>;
 ///
-///     async fn submit<T>(&self, ext: T) -> Result<()>
+///     async fn submit<T>(&self, ext: T) -> Result<[u8; 32]>
 ///     where
 ///         T: AsRef<[u8]>;
 ///
@@ -396,19 +904,179 @@ pub trait Backend {
             .ok_or(Error::StorageKeyNotFound)
     }
 
+    /// Fetch storage items together with the info of a block, backends
+    /// that can batch requests do it in a single round trip
+    async fn get_storage_items_with_block_info(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+        info_at: Option<u32>,
+    ) -> crate::Result<(Vec<(RawKey, RawValue)>, meta::BlockInfo)> {
+        let items = self.get_storage_items(keys, block).await?.collect();
+        Ok((items, self.block_info(info_at).await?))
+    }
+
     async fn get_keys_paged(
         &self,
         from: RawKey,
         size: u16,
         to: Option<RawKey>,
+        block: Option<u32>,
     ) -> crate::Result<Vec<RawValue>>;
 
-    /// Send a signed extrinsic to the blockchain
-    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<()>;
+    /// Fetch a value stored in the child trie identified by the prefixed `child_key`
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<RawValue>;
+
+    /// Fetch several values of a child trie at once, keys without a value are left out
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<(RawKey, RawValue)>>;
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>>;
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<[u8; 32]>;
+
+    /// Fetch the trie nodes that prove the values of the given keys
+    async fn get_read_proof(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<proof::ReadProof>;
+
+    /// Send a signed extrinsic to the blockchain, returns its hash
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<[u8; 32]>;
 
     async fn metadata(&self) -> Result<Metadata>;
 
     async fn block_info(&self, at: Option<u32>) -> Result<meta::BlockInfo>;
+
+    /// Name, version and token of the chain, not every backend knows them
+    async fn properties(&self) -> Result<ChainProperties> {
+        Err(Error::ChainUnavailable)
+    }
+}
+
+/// Backends can be shared by reference, e.g. to keep using a backend after
+/// giving it to a function that takes `impl Backend`
+impl<B: Backend> Backend for &B {
+    async fn get_storage_items(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<impl Iterator<Item = (RawKey, RawValue)>> {
+        (**self).get_storage_items(keys, block).await
+    }
+
+    async fn get_storage_item(&self, key: RawKey, block: Option<u32>) -> crate::Result<RawValue> {
+        (**self).get_storage_item(key, block).await
+    }
+
+    async fn get_storage_items_with_block_info(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+        info_at: Option<u32>,
+    ) -> crate::Result<(Vec<(RawKey, RawValue)>, meta::BlockInfo)> {
+        (**self)
+            .get_storage_items_with_block_info(keys, block, info_at)
+            .await
+    }
+
+    async fn get_keys_paged(
+        &self,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        (**self).get_keys_paged(from, size, to, block).await
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<RawValue> {
+        (**self).get_child_storage_item(child_key, key, block).await
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<(RawKey, RawValue)>> {
+        (**self)
+            .get_child_storage_items(child_key, keys, block)
+            .await
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        (**self)
+            .get_child_keys_paged(child_key, from, size, to, block)
+            .await
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> crate::Result<[u8; 32]> {
+        (**self).get_child_storage_hash(child_key, key, block).await
+    }
+
+    async fn get_read_proof(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> crate::Result<proof::ReadProof> {
+        (**self).get_read_proof(keys, block).await
+    }
+
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+        (**self).submit(ext).await
+    }
+
+    async fn metadata(&self) -> Result<Metadata> {
+        (**self).metadata().await
+    }
+
+    async fn block_info(&self, at: Option<u32>) -> Result<meta::BlockInfo> {
+        (**self).block_info(at).await
+    }
+
+    async fn properties(&self) -> Result<ChainProperties> {
+        (**self).properties().await
+    }
 }
 
 /// A Dummy backend for offline querying of metadata
@@ -428,12 +1096,59 @@ impl Backend for Offline {
         _from: RawKey,
         _size: u16,
         _to: Option<RawKey>,
+        _block: Option<u32>,
+    ) -> crate::Result<Vec<RawKey>> {
+        Err(Error::ChainUnavailable)
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        _child_key: RawKey,
+        _key: RawKey,
+        _block: Option<u32>,
+    ) -> crate::Result<RawValue> {
+        Err(Error::ChainUnavailable)
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        _child_key: RawKey,
+        _keys: Vec<RawKey>,
+        _block: Option<u32>,
+    ) -> crate::Result<Vec<(RawKey, RawValue)>> {
+        Err(Error::ChainUnavailable)
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        _child_key: RawKey,
+        _from: RawKey,
+        _size: u16,
+        _to: Option<RawKey>,
+        _block: Option<u32>,
     ) -> crate::Result<Vec<RawKey>> {
         Err(Error::ChainUnavailable)
     }
 
+    async fn get_child_storage_hash(
+        &self,
+        _child_key: RawKey,
+        _key: RawKey,
+        _block: Option<u32>,
+    ) -> crate::Result<[u8; 32]> {
+        Err(Error::ChainUnavailable)
+    }
+
+    async fn get_read_proof(
+        &self,
+        _keys: Vec<RawKey>,
+        _block: Option<u32>,
+    ) -> crate::Result<proof::ReadProof> {
+        Err(Error::ChainUnavailable)
+    }
+
     /// Send a signed extrinsic to the blockchain
-    async fn submit(&self, _ext: impl AsRef<[u8]>) -> Result<()> {
+    async fn submit(&self, _ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
         Err(Error::ChainUnavailable)
     }
 
@@ -451,6 +1166,7 @@ pub enum Error {
     ChainUnavailable,
     BadInput,
     BadKey,
+    BadAddress,
     BadMetadata,
     Decode(codec::Error),
     Encode(String),
@@ -470,7 +1186,8 @@ pub enum Error {
     CantDecodeReponseForMeta,
     CantDecodeRawQueryResponse,
     CantFindMethodInPallet,
-    BadBlockNumber
+    BadBlockNumber,
+    BadProof,
 }
 
 impl fmt::Display for Error {
@@ -487,3 +1204,641 @@ impl std::error::Error for Error {}
 
 #[cfg(feature = "no_std")]
 impl core::error::Error for Error {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use core::{cell::RefCell, marker::PhantomData};
+    use frame_metadata::v14::{
+        ExtrinsicMetadata, PalletCallMetadata, PalletConstantMetadata, PalletMetadata,
+        PalletStorageMetadata, RuntimeMetadataV14, StorageEntryMetadata, StorageEntryModifier,
+        StorageEntryType,
+    };
+    use meta::Hasher;
+    use scale_info::{meta_type, MetaType, TypeInfo};
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct AccountId32([u8; 32]);
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct AccountInfo {
+        nonce: u32,
+        consumers: u32,
+        providers: u32,
+        sufficients: u32,
+        data: AccountData,
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct AccountData {
+        free: u128,
+        reserved: u128,
+        frozen: u128,
+        flags: u128,
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct RuntimeVersion {
+        spec_version: u32,
+        transaction_version: u32,
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    enum MultiAddress {
+        Id(AccountId32),
+        Index(#[codec(compact)] u32),
+        Raw(Vec<u8>),
+        Address32([u8; 32]),
+        Address20([u8; 20]),
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    enum MultiSignature {
+        Ed25519([u8; 64]),
+        Sr25519([u8; 64]),
+        Ecdsa([u8; 65]),
+    }
+
+    /// Signed extensions sube encodes, an immortal era, the nonce, the tip
+    /// and the mode of the metadata hash check
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct Extra {
+        era: u8,
+        #[codec(compact)]
+        nonce: u32,
+        #[codec(compact)]
+        tip: u128,
+        mode: u8,
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code, non_camel_case_types)]
+    enum SystemCall {
+        remark { remark: Vec<u8> },
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    enum RuntimeCall {
+        System(SystemCall),
+    }
+
+    #[derive(TypeInfo)]
+    #[allow(dead_code)]
+    struct UncheckedExtrinsic<Address, Call, Signature, Extra>(
+        Vec<u8>,
+        PhantomData<(Address, Call, Signature, Extra)>,
+    );
+
+    /// Metadata of a runtime with a `System` pallet storing the block `Number`
+    /// and the `Account`s that can sign its `remark` call and a `Maps` pallet
+    /// with storage maps using different hashers:
+    ///
+    /// - `Values`: `Twox64Concat` `u32` keys to `u64` values, `42` by default
+    /// - `Optional`: `Blake2_128Concat` `u32` keys to `u32` values, an `OptionQuery`
+    /// - `Hashed`: `Blake2_128` `u32` keys to `u32` values, the keys can't be decoded
+    /// - `Double`: `Twox64Concat` `u32` and `Blake2_128Concat` `u16` keys to `u32` values
+    ///
+    /// Extrinsics are signed with a `MultiAddress` and a `MultiSignature`.
+    pub(crate) fn metadata() -> Metadata {
+        metadata_signed_with::<MultiAddress, MultiSignature>()
+    }
+
+    /// Metadata like [`metadata`] of a runtime with other address and signature types
+    pub(crate) fn metadata_signed_with<Address, Signature>() -> Metadata
+    where
+        Address: TypeInfo + 'static,
+        Signature: TypeInfo + 'static,
+    {
+        metadata_with_extrinsic::<UncheckedExtrinsic<Address, RuntimeCall, Signature, Extra>>()
+    }
+
+    /// Metadata like [`metadata`] with another extrinsic type, e.g. one without
+    /// the type params of metadata with simplified types
+    pub(crate) fn metadata_with_extrinsic<X: TypeInfo + 'static>() -> Metadata {
+        let pallet = |name, index, entries| PalletMetadata {
+            name,
+            storage: Some(PalletStorageMetadata {
+                prefix: name,
+                entries,
+            }),
+            calls: None,
+            event: None,
+            constants: vec![],
+            error: None,
+            index,
+        };
+        let u32_map =
+            |name, hashers, key, default| map(name, hashers, key, meta_type::<u32>(), default);
+        let system = PalletMetadata {
+            calls: Some(PalletCallMetadata {
+                ty: meta_type::<SystemCall>(),
+            }),
+            constants: vec![PalletConstantMetadata {
+                name: "Version",
+                ty: meta_type::<RuntimeVersion>(),
+                value: (100u32, 1u32).encode(),
+                docs: vec![],
+            }],
+            ..pallet(
+                "System",
+                0,
+                vec![
+                    plain("Number", meta_type::<u32>(), 4),
+                    map(
+                        "Account",
+                        vec![Hasher::Blake2_128Concat],
+                        meta_type::<AccountId32>(),
+                        meta_type::<AccountInfo>(),
+                        Some(vec![0; 80]),
+                    ),
+                ],
+            )
+        };
+        let pallets = vec![
+            system,
+            pallet(
+                "Maps",
+                2,
+                vec![
+                    map(
+                        "Values",
+                        vec![Hasher::Twox64Concat],
+                        meta_type::<u32>(),
+                        meta_type::<u64>(),
+                        Some(42u64.encode()),
+                    ),
+                    u32_map(
+                        "Optional",
+                        vec![Hasher::Blake2_128Concat],
+                        meta_type::<u32>(),
+                        None,
+                    ),
+                    u32_map(
+                        "Hashed",
+                        vec![Hasher::Blake2_128],
+                        meta_type::<u32>(),
+                        Some(0u32.encode()),
+                    ),
+                    u32_map(
+                        "Double",
+                        vec![Hasher::Twox64Concat, Hasher::Blake2_128Concat],
+                        meta_type::<(u32, u16)>(),
+                        Some(0u32.encode()),
+                    ),
+                ],
+            ),
+        ];
+        let extrinsic = ExtrinsicMetadata {
+            ty: meta_type::<X>(),
+            version: 4,
+            signed_extensions: vec![],
+        };
+        RuntimeMetadataV14::new(pallets, extrinsic, meta_type::<()>())
+    }
+
+    fn plain(name: &'static str, ty: MetaType, size: usize) -> StorageEntryMetadata {
+        StorageEntryMetadata {
+            name,
+            modifier: StorageEntryModifier::Default,
+            ty: StorageEntryType::Plain(ty),
+            default: vec![0; size],
+            docs: vec![],
+        }
+    }
+
+    /// Storage map, entries without a default are an `OptionQuery`
+    fn map(
+        name: &'static str,
+        hashers: Vec<Hasher>,
+        key: MetaType,
+        value: MetaType,
+        default: Option<Vec<u8>>,
+    ) -> StorageEntryMetadata {
+        let modifier = match default {
+            Some(_) => StorageEntryModifier::Default,
+            None => StorageEntryModifier::Optional,
+        };
+        StorageEntryMetadata {
+            name,
+            modifier,
+            ty: StorageEntryType::Map {
+                hashers,
+                key,
+                value,
+            },
+            default: default.unwrap_or_else(|| vec![0]),
+            docs: vec![],
+        }
+    }
+
+    /// Key of a storage item without map keys, e.g. `storage_key("System", "Number")`
+    pub(crate) fn storage_key(pallet: &str, item: &str) -> RawKey {
+        [hash(&Hasher::Twox128, pallet), hash(&Hasher::Twox128, item)].concat()
+    }
+
+    /// Key of a storage map item given its map keys and their hashers
+    pub(crate) fn map_key(pallet: &str, item: &str, keys: &[(Hasher, Vec<u8>)]) -> RawKey {
+        let keys = keys.iter().map(|(hasher, key)| hash(hasher, key));
+        [storage_key(pallet, item), keys.collect::<Vec<_>>().concat()].concat()
+    }
+
+    pub(crate) fn value_key(n: u32) -> RawKey {
+        map_key("Maps", "Values", &[(Hasher::Twox64Concat, n.encode())])
+    }
+
+    /// Storage of a chain keeping a full copy of it for every block, it
+    /// remembers the methods called on it
+    #[derive(Default)]
+    pub(crate) struct Chain {
+        blocks: Vec<BTreeMap<RawKey, RawValue>>,
+        child: BTreeMap<RawKey, BTreeMap<RawKey, RawValue>>,
+        calls: RefCell<Vec<&'static str>>,
+    }
+
+    impl Chain {
+        pub(crate) fn new(items: impl IntoIterator<Item = (RawKey, RawValue)>) -> Self {
+            Chain {
+                blocks: vec![items.into_iter().collect()],
+                ..Default::default()
+            }
+        }
+
+        /// Adds a block on top of the latest one, items changed to `None` are removed
+        pub(crate) fn new_block(
+            mut self,
+            changes: impl IntoIterator<Item = (RawKey, Option<RawValue>)>,
+        ) -> Self {
+            let mut storage = self.blocks.last().cloned().unwrap_or_default();
+            for (key, value) in changes {
+                match value {
+                    Some(value) => storage.insert(key, value),
+                    None => storage.remove(&key),
+                };
+            }
+            self.blocks.push(storage);
+            self
+        }
+
+        pub(crate) fn with_child_storage(
+            mut self,
+            child_key: &str,
+            items: impl IntoIterator<Item = (RawKey, RawValue)>,
+        ) -> Self {
+            let child_key = meta::child_storage_key(child_key).expect("valid child key");
+            self.child.insert(child_key, items.into_iter().collect());
+            self
+        }
+
+        pub(crate) fn calls(&self, method: &str) -> usize {
+            self.calls.borrow().iter().filter(|m| **m == method).count()
+        }
+
+        fn call(&self, method: &'static str) {
+            self.calls.borrow_mut().push(method);
+        }
+
+        fn storage(&self, block: Option<u32>) -> Result<&BTreeMap<RawKey, RawValue>> {
+            match block {
+                Some(n) => self.blocks.get(n as usize).ok_or(Error::BadBlockNumber),
+                None => self.blocks.last().ok_or(Error::BadBlockNumber),
+            }
+        }
+    }
+
+    /// Keys under the prefix that come after the start key
+    fn page(
+        items: &BTreeMap<RawKey, RawValue>,
+        prefix: &[u8],
+        size: u16,
+        start: Option<RawKey>,
+    ) -> Vec<RawKey> {
+        items
+            .keys()
+            .filter(|k| k.starts_with(prefix) && start.as_ref().is_none_or(|s| *k > s))
+            .take(size as usize)
+            .cloned()
+            .collect()
+    }
+
+    impl Backend for Chain {
+        async fn get_storage_items(
+            &self,
+            keys: Vec<RawKey>,
+            block: Option<u32>,
+        ) -> Result<impl Iterator<Item = (RawKey, RawValue)>> {
+            self.call("items");
+            let storage = self.storage(block)?;
+            Ok(keys
+                .into_iter()
+                .filter_map(|k| Some((k.clone(), storage.get(&k)?.clone())))
+                .collect::<Vec<_>>()
+                .into_iter())
+        }
+
+        async fn get_keys_paged(
+            &self,
+            from: RawKey,
+            size: u16,
+            to: Option<RawKey>,
+            block: Option<u32>,
+        ) -> Result<Vec<RawKey>> {
+            self.call("keys");
+            Ok(page(self.storage(block)?, &from, size, to))
+        }
+
+        async fn get_child_storage_item(
+            &self,
+            child_key: RawKey,
+            key: RawKey,
+            _block: Option<u32>,
+        ) -> Result<RawValue> {
+            self.call("child_item");
+            let child = self.child.get(&child_key);
+            let value = child.and_then(|items| items.get(&key));
+            value.cloned().ok_or(Error::StorageKeyNotFound)
+        }
+
+        async fn get_child_storage_items(
+            &self,
+            child_key: RawKey,
+            keys: Vec<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<Vec<(RawKey, RawValue)>> {
+            self.call("child_items");
+            let Some(child) = self.child.get(&child_key) else {
+                return Ok(vec![]);
+            };
+            Ok(keys
+                .into_iter()
+                .filter_map(|k| Some((k.clone(), child.get(&k)?.clone())))
+                .collect())
+        }
+
+        async fn get_child_keys_paged(
+            &self,
+            child_key: RawKey,
+            from: RawKey,
+            size: u16,
+            to: Option<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<Vec<RawKey>> {
+            self.call("child_keys");
+            let child = self.child.get(&child_key);
+            Ok(child
+                .map(|items| page(items, &from, size, to))
+                .unwrap_or_default())
+        }
+
+        async fn get_child_storage_hash(
+            &self,
+            child_key: RawKey,
+            key: RawKey,
+            block: Option<u32>,
+        ) -> Result<[u8; 32]> {
+            let value = self.get_child_storage_item(child_key, key, block).await?;
+            Ok(hasher::blake2_256(&value))
+        }
+
+        async fn get_read_proof(
+            &self,
+            _keys: Vec<RawKey>,
+            _block: Option<u32>,
+        ) -> Result<proof::ReadProof> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn submit(&self, _ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+            Err(Error::ChainUnavailable)
+        }
+
+        async fn metadata(&self) -> Result<Metadata> {
+            Ok(metadata())
+        }
+
+        async fn block_info(&self, at: Option<u32>) -> Result<meta::BlockInfo> {
+            let number = at.unwrap_or(self.blocks.len() as u32 - 1);
+            Ok(meta::BlockInfo {
+                number: number.into(),
+                hash: [number as u8; 32],
+                parent: [number.saturating_sub(1) as u8; 32],
+            })
+        }
+    }
+
+    pub(crate) fn to_json(res: Result<Response>) -> Result<JsonValue> {
+        Ok(serde_json::to_value(res?).expect("responses are JSON"))
+    }
+
+    #[async_std::test]
+    async fn diff_between_blocks() {
+        let chain = Chain::new((1..=3u32).map(|n| (value_key(n), (n as u64 * 10).encode())))
+            .new_block([
+                (value_key(2), Some(21u64.encode())),
+                (value_key(3), None),
+                (value_key(4), Some(40u64.encode())),
+            ]);
+        let meta = metadata();
+
+        let changes = to_json(diff(&chain, &meta, "maps/values", 0, None).await).unwrap();
+        assert_eq!(changes["added"], json!([[[4], 40]]));
+        assert_eq!(changes["removed"], json!([[[3], 30]]));
+        assert_eq!(changes["changed"], json!([[[2], 20, 21]]));
+
+        let changes = to_json(diff(&chain, &meta, "maps/values", 0, Some(0)).await);
+        assert_eq!(
+            changes.unwrap(),
+            json!({ "added": [], "removed": [], "changed": [] })
+        );
+    }
+
+    #[async_std::test]
+    async fn diff_follows_the_key_pages() {
+        let chain = Chain::new((0..1500u32).map(|n| (value_key(n), 1u64.encode()))).new_block([
+            (value_key(1499), Some(2u64.encode())),
+            (value_key(1500), Some(3u64.encode())),
+        ]);
+        let meta = metadata();
+
+        let changes = to_json(diff(&chain, &meta, "maps/values", 0, None).await).unwrap();
+        assert_eq!(changes["added"], json!([[[1500], 3]]));
+        assert_eq!(changes["removed"], json!([]));
+        assert_eq!(changes["changed"], json!([[[1499], 1, 2]]));
+        // two pages of keys for each block
+        assert_eq!(chain.calls("keys"), 4);
+    }
+
+    #[async_std::test]
+    async fn child_trie_items_are_listed_by_pages() {
+        let items = (0..1500u32).map(|n| (n.to_be_bytes().to_vec(), n.encode()));
+        let chain = Chain::new([]).with_child_storage(":child_storage:default:kids", items);
+        let meta = metadata();
+
+        let items = to_json(query(&chain, &meta, "_child/kids", None).await).unwrap();
+        let items = items.as_array().unwrap();
+        assert_eq!(items.len(), 1500);
+        assert_eq!(items[1499], json!(["0x000005db", "0xdb050000"]));
+
+        assert_eq!(chain.calls("child_keys"), 2);
+        assert_eq!(chain.calls("child_items"), 2);
+        assert_eq!(chain.calls("child_item"), 0);
+    }
+
+    #[async_std::test]
+    async fn child_trie_values() {
+        let chain = Chain::new([])
+            .with_child_storage(":child_storage:default:kids", [(vec![1], 7u32.encode())]);
+        let meta = metadata();
+        let query = |path| query(&chain, &meta, path, None);
+
+        let value = to_json(query("_child/kids/0x01").await);
+        assert_eq!(value.unwrap(), json!("0x07000000"));
+        let value =
+            query("_child/0x3a6368696c645f73746f726167653a64656661756c743a6b696473/0x01/u32");
+        assert_eq!(to_json(value.await).unwrap(), json!(7));
+        let hash = to_json(query("_child/kids/0x01/_hash").await).unwrap();
+        assert_eq!(hash.as_str().map(str::len), Some(66));
+
+        let missing = query("_child/kids/0x02").await;
+        assert!(
+            matches!(missing, Err(Error::StorageKeyNotFound)),
+            "{:?}",
+            missing
+        );
+        let empty = to_json(query("_child/nobody").await).unwrap();
+        assert_eq!(empty, json!([]));
+    }
+
+    fn hex_key(key: &[u8]) -> String {
+        format!("0x{}", hex::encode(key))
+    }
+
+    #[async_std::test]
+    async fn map_keys_are_decoded_by_their_hasher() {
+        let hashed_key = hash(&Hasher::Blake2_128, 5u32.encode());
+        let double = [
+            (Hasher::Twox64Concat, 7u32.encode()),
+            (Hasher::Blake2_128Concat, 9u16.encode()),
+        ];
+        let chain = Chain::new([
+            (value_key(1), 10u64.encode()),
+            (map_key("Maps", "Double", &double), 79u32.encode()),
+            (
+                map_key("Maps", "Hashed", &[(Hasher::Blake2_128, 5u32.encode())]),
+                5u32.encode(),
+            ),
+        ]);
+        let meta = metadata();
+        let query = |path| query(&chain, &meta, path, None);
+
+        let values = to_json(query("maps/values").await);
+        assert_eq!(values.unwrap(), json!([[[1], 10]]));
+        let double = to_json(query("maps/double/7").await);
+        assert_eq!(double.unwrap(), json!([[[7, 9], 79]]));
+        // opaque hashers only give the hash of the key
+        let hashed = to_json(query("maps/hashed").await);
+        assert_eq!(hashed.unwrap(), json!([[[hex_key(&hashed_key)], 5]]));
+    }
+
+    #[async_std::test]
+    async fn storage_keys_are_looked_up_in_the_metadata() {
+        let chain = Chain::new([]);
+        let meta = metadata();
+        let lookup = |key: RawKey| {
+            let path = format!("_key/{}", hex_key(&key));
+            let (chain, meta) = (&chain, &meta);
+            async move { to_json(query(chain, meta, &path, None).await) }
+        };
+
+        let info = lookup(value_key(1)).await.unwrap();
+        assert_eq!(
+            info,
+            json!({ "pallet": "Maps", "item": "Values", "keys": [1] })
+        );
+
+        let key = [(Hasher::Blake2_128Concat, 3u32.encode())];
+        let info = lookup(map_key("Maps", "Optional", &key)).await;
+        assert_eq!(info.unwrap()["keys"], json!([3]));
+
+        let key = [(Hasher::Blake2_128, 5u32.encode())];
+        let info = lookup(map_key("Maps", "Hashed", &key)).await;
+        let hash = hash(&Hasher::Blake2_128, 5u32.encode());
+        assert_eq!(info.unwrap()["keys"], json!([hex_key(&hash)]));
+
+        let info = lookup(storage_key("System", "Number")).await;
+        assert_eq!(info.unwrap()["keys"], json!([]));
+
+        let unknown = lookup(storage_key("Nope", "Nothing")).await;
+        assert!(
+            matches!(unknown, Err(Error::StorageKeyNotFound)),
+            "{:?}",
+            unknown
+        );
+        assert!(storage_key_info(&meta, &[0; 32]).is_none());
+        // nothing is read from the chain
+        assert!(chain.calls.borrow().is_empty());
+    }
+
+    #[async_std::test]
+    async fn raw_keys_are_decoded_when_known() {
+        let unknown_key = storage_key("Nope", "Nothing");
+        let chain = Chain::new([
+            (value_key(1), 10u64.encode()),
+            (unknown_key.clone(), vec![1, 2]),
+        ]);
+        let meta = metadata();
+        let raw = |key: &[u8]| {
+            let path = format!("_raw/{}", hex_key(key));
+            let (chain, meta) = (&chain, &meta);
+            async move { to_json(query(chain, meta, &path, None).await) }
+        };
+
+        assert_eq!(raw(&value_key(1)).await.unwrap(), json!(10));
+        // missing items of a known entry get its default
+        assert_eq!(raw(&value_key(2)).await.unwrap(), json!(42));
+
+        assert_eq!(raw(&unknown_key).await.unwrap(), json!("0x0102"));
+        let missing = raw(&[0; 32]).await;
+        assert!(
+            matches!(missing, Err(Error::StorageKeyNotFound)),
+            "{:?}",
+            missing
+        );
+        let bad = query(&chain, &meta, "_raw/0xnothex", None).await;
+        assert!(matches!(bad, Err(Error::BadKey)), "{:?}", bad);
+    }
+
+    #[async_std::test]
+    async fn missing_items_get_the_default_of_their_entry() {
+        let optional = |n: u32| {
+            map_key(
+                "Maps",
+                "Optional",
+                &[(Hasher::Blake2_128Concat, n.encode())],
+            )
+        };
+        let chain = Chain::new([
+            (value_key(1), 10u64.encode()),
+            (optional(1), 11u32.encode()),
+        ]);
+        let meta = metadata();
+        let (chain, meta) = (&chain, &meta);
+        let query = |path| async move { to_json(query(chain, meta, path, None).await).unwrap() };
+
+        assert_eq!(query("maps/values/1").await, json!(10));
+        // `ValueQuery` items are the default value of the metadata
+        assert_eq!(query("maps/values/2").await, json!(42));
+        assert_eq!(query("system/number").await, json!(0));
+
+        assert_eq!(query("maps/optional/1").await, json!(11));
+        // `OptionQuery` items don't have a value
+        assert_eq!(query("maps/optional/2").await, json!(null));
+    }
+}
@@ -0,0 +1,168 @@
+use alloc::sync::Arc;
+use core::{
+    iter,
+    num::NonZeroU32,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use async_std::task::spawn;
+use jsonrpc::{
+    error::{standard_error, StandardError},
+    serde_json,
+};
+use no_std_async::Mutex;
+use serde::Deserialize;
+use smoldot_light::{
+    platform::DefaultPlatform, AddChainConfig, AddChainConfigJsonRpc, ChainId, Client,
+    JsonRpcResponses,
+};
+
+use crate::{
+    prelude::*,
+    rpc::{self, Router, Rpc, RpcResult, RpcSubscribe, Subscription},
+    Error,
+};
+
+type Id = u32;
+type Platform = Arc<DefaultPlatform>;
+
+const MAX_PENDING_REQUESTS: u32 = 128;
+const MAX_SUBSCRIPTIONS: u32 = 1024;
+
+/// Light client backend that syncs the chain with an embedded smoldot node
+/// and verifies everything it gets from the network instead of trusting a
+/// remote RPC node.
+pub struct Backend {
+    client: Mutex<Client<Platform>>,
+    chain_id: ChainId,
+    next_id: AtomicU32,
+    messages: Arc<Mutex<Router>>,
+}
+
+impl Rpc for Backend {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        log::info!("RPC `{}` (ID={})", method, id);
+
+        let recv = self.messages.lock().await.request(id);
+        self.send(id, method, params).await?;
+
+        // wait for the matching response to arrive
+        let res = recv
+            .await
+            .map_err(|err| {
+                log::error!("Error receiving message: {:?}", err);
+                standard_error(StandardError::InternalError, None)
+            })?
+            .result()?;
+
+        Ok(res)
+    }
+}
+
+impl RpcSubscribe for Backend {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        log::info!("RPC subscription `{}` (ID={})", method, id);
+
+        let (recv, sub) = self.messages.lock().await.subscription(id);
+        self.send(id, method, params).await?;
+
+        // the subscription id is set by the router with the response
+        recv.await
+            .map_err(|err| {
+                log::error!("Error receiving message: {:?}", err);
+                standard_error(StandardError::InternalError, None)
+            })?
+            .result::<serde_json::Value>()?;
+
+        Ok(sub)
+    }
+}
+
+impl Backend {
+    /// Starts a light client for the chain described by the given chain
+    /// specification (the JSON content, not a path).
+    pub fn new(chain_spec: &str) -> Result<Self, Error> {
+        let platform = DefaultPlatform::new(
+            env!("CARGO_PKG_NAME").into(),
+            env!("CARGO_PKG_VERSION").into(),
+        );
+        let mut client = Client::new(platform);
+
+        let chain = client
+            .add_chain(AddChainConfig {
+                user_data: (),
+                specification: chain_spec,
+                database_content: "",
+                potential_relay_chains: iter::empty(),
+                json_rpc: AddChainConfigJsonRpc::Enabled {
+                    max_pending_requests: NonZeroU32::new(MAX_PENDING_REQUESTS).expect("non zero"),
+                    max_subscriptions: MAX_SUBSCRIPTIONS,
+                },
+            })
+            .map_err(|err| Error::Platform(err.to_string()))?;
+
+        let backend = Backend {
+            client: Mutex::new(client),
+            chain_id: chain.chain_id,
+            next_id: AtomicU32::new(1),
+            messages: Arc::new(Mutex::new(Router::default())),
+        };
+
+        backend.process_responses(
+            chain
+                .json_rpc_responses
+                .expect("json rpc is enabled for the chain"),
+        );
+        Ok(backend)
+    }
+
+    /// Starts a light client reading the chain specification from a local file
+    pub async fn from_file(path: &str) -> Result<Self, Error> {
+        log::trace!("Light client using chain spec {}", path);
+        let chain_spec = async_std::fs::read_to_string(path)
+            .await
+            .map_err(|err| Error::Platform(err.to_string()))?;
+        Self::new(&chain_spec)
+    }
+
+    async fn send(&self, id: Id, method: &str, params: &[&str]) -> RpcResult<()> {
+        let msg = serde_json::to_string(&rpc::Request {
+            id: id.into(),
+            jsonrpc: Some("2.0"),
+            method,
+            params: &Self::convert_params(params),
+        })
+        .expect("Request is serializable");
+
+        log::debug!("RPC Request {} ...", &msg);
+
+        let sent = self
+            .client
+            .lock()
+            .await
+            .json_rpc_request(msg, self.chain_id);
+        if let Err(err) = sent {
+            log::error!("Error queueing request: {}", err);
+            self.messages.lock().await.cancel(id);
+            return Err(standard_error(StandardError::InternalError, None).into());
+        }
+        Ok(())
+    }
+
+    fn process_responses(&self, mut responses: JsonRpcResponses<Platform>) {
+        let messages = self.messages.clone();
+        spawn(async move {
+            while let Some(msg) = responses.next().await {
+                log::trace!("Got light client message {}", msg);
+                messages.lock().await.route(&msg);
+            }
+
+            log::warn!("Light client chain removed");
+        });
+    }
+}
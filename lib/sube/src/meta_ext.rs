@@ -17,7 +17,9 @@ mod v14 {
     pub type Metadata = RuntimeMetadataV14;
     pub type PalletMeta = PalletMetadata<PortableForm>;
     pub type EntryType = StorageEntryType<PortableForm>;
+    pub type StorageEntry = StorageEntryMetadata<PortableForm>;
     pub type Hasher = StorageHasher;
+    pub type EntryModifier = StorageEntryModifier;
     pub use scale_info::PortableRegistry;
     pub type Type = scale_info::Type<PortableForm>;
 }
@@ -44,6 +46,174 @@ impl From<BlockInfo> for Vec<u8> {
     }
 }
 
+const CHILD_STORAGE_PREFIX: &[u8] = b":child_storage:";
+const DEFAULT_CHILD_STORAGE_PREFIX: &[u8] = b":child_storage:default:";
+
+/// Builds the prefixed storage key of a child trie from a hex encoded key or a plain
+/// text identifier, keys without a `:child_storage:` prefix are assumed to be default child tries.
+pub fn child_storage_key(key: &str) -> crate::Result<Vec<u8>> {
+    let key = match key.strip_prefix("0x") {
+        Some(k) => hex::decode(k).map_err(|_| crate::Error::BadKey)?,
+        None => key.as_bytes().to_vec(),
+    };
+    Ok(if key.starts_with(CHILD_STORAGE_PREFIX) {
+        key
+    } else {
+        [DEFAULT_CHILD_STORAGE_PREFIX, &key].concat()
+    })
+}
+
+/// Finds a type in the registry by its id or by its name, the name can be the full
+/// path(e.g. `sp_core::crypto::AccountId32`), just the last segment or the readable
+/// name of types without a path like `u32` or `Vec<u8>`.
+pub fn type_id_by_name(registry: &PortableRegistry, name: &str) -> Option<TypeId> {
+    if let Ok(id) = name.parse::<TypeId>() {
+        return registry.resolve(id).map(|_| id);
+    }
+    registry
+        .types
+        .iter()
+        .find(|t| {
+            let path = &t.ty.path.segments;
+            path.join("::") == name || path.last().is_some_and(|s| s == name)
+        })
+        .or_else(|| {
+            registry
+                .types
+                .iter()
+                .find(|t| t.ty.path.segments.is_empty() && type_name(registry, t.id) == name)
+        })
+        .map(|t| t.id)
+}
+
+/// Readable name of a registry type like `Vec<u8>`, `Option<AccountId32>` or `(u32, u128)`
+pub fn type_name(registry: &PortableRegistry, id: TypeId) -> String {
+    use scale_info::TypeDef;
+    let Some(ty) = registry.resolve(id) else {
+        return id.to_string();
+    };
+    let names = |ids: &mut dyn Iterator<Item = TypeId>| {
+        ids.map(|id| type_name(registry, id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if let Some(name) = ty.path.segments.last() {
+        let params = names(&mut ty.type_params.iter().filter_map(|p| p.ty.map(|t| t.id)));
+        return if params.is_empty() {
+            name.clone()
+        } else {
+            format!("{}<{}>", name, params)
+        };
+    }
+    match &ty.type_def {
+        TypeDef::Sequence(s) => format!("Vec<{}>", type_name(registry, s.type_param.id)),
+        TypeDef::Array(a) => format!("[{}; {}]", type_name(registry, a.type_param.id), a.len),
+        TypeDef::Tuple(t) => format!("({})", names(&mut t.fields.iter().map(|f| f.id))),
+        TypeDef::Primitive(p) => format!("{:?}", p).to_lowercase(),
+        TypeDef::Compact(c) => format!("Compact<{}>", type_name(registry, c.type_param.id)),
+        TypeDef::BitSequence(_) => "BitVec".into(),
+        TypeDef::Composite(_) | TypeDef::Variant(_) => id.to_string(),
+    }
+}
+
+/// Finds a storage entry of a pallet by its name
+pub fn storage_entry<'m>(pallet: &'m PalletMeta, item: &str) -> Option<&'m StorageEntry> {
+    pallet
+        .storage
+        .as_ref()
+        .and_then(|s| s.entries.iter().find(|e| e.name == item))
+}
+
+/// A part of a storage map key, the encoded key value is only
+/// available when the hasher is transparent(`*Concat` or `Identity`)
+#[derive(Clone, Debug)]
+pub struct MapKeyPart {
+    pub hasher: Hasher,
+    pub ty: TypeId,
+    pub hash: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+
+/// Finds the storage entry a raw storage key belongs to by matching its
+/// twox128 pallet and item prefixes and splits the rest of the key in its map key parts.
+pub fn lookup_storage_key<'m>(
+    meta: &'m Metadata,
+    key: &[u8],
+) -> Option<(&'m PalletMeta, &'m StorageEntry, Vec<MapKeyPart>)> {
+    if key.len() < 32 {
+        return None;
+    }
+    let (pallet_hash, rest) = key.split_at(16);
+    let (item_hash, rest) = rest.split_at(16);
+
+    let (pallet, storage) = meta.pallets.iter().find_map(|p| {
+        p.storage
+            .as_ref()
+            .filter(|s| hash(&Hasher::Twox128, &s.prefix) == pallet_hash)
+            .map(|s| (p, s))
+    })?;
+    let entry = storage
+        .entries
+        .iter()
+        .find(|e| hash(&Hasher::Twox128, &e.name) == item_hash)?;
+
+    let parts = map_key_parts(meta, entry, rest)?;
+    Some((pallet, entry, parts))
+}
+
+/// Splits the part of a storage key after the pallet and item prefixes in the
+/// map keys of the entry, transparent hashers also give the encoded key value
+pub fn map_key_parts(
+    meta: &Metadata,
+    entry: &StorageEntry,
+    mut rest: &[u8],
+) -> Option<Vec<MapKeyPart>> {
+    let EntryType::Map { hashers, key, .. } = &entry.ty else {
+        return Some(vec![]);
+    };
+    let key_types = match hashers.len() {
+        1 => vec![key.id],
+        _ => extract_touple_type(key.id, meta.types.resolve(key.id)?),
+    };
+    let mut parts = vec![];
+    for (hasher, ty) in hashers.iter().zip(key_types) {
+        let hash_len = hasher_size(hasher);
+        let value_len = match hasher {
+            Hasher::Blake2_128Concat | Hasher::Twox64Concat | Hasher::Identity => {
+                Some(scales::Value::new(rest.get(hash_len..)?.to_vec(), ty, &meta.types).size())
+            }
+            _ => None,
+        };
+        let (hash, tail) = rest.split_at(hash_len.min(rest.len()));
+        let (value, tail) = match value_len {
+            Some(len) if len <= tail.len() => {
+                let (value, tail) = tail.split_at(len);
+                (Some(value.to_vec()), tail)
+            }
+            Some(_) => return None,
+            None => (None, tail),
+        };
+        parts.push(MapKeyPart {
+            hasher: hasher.clone(),
+            ty,
+            hash: hash.to_vec(),
+            value,
+        });
+        rest = tail;
+    }
+    Some(parts)
+}
+
+/// Size in bytes of the hash a hasher prepends to a storage map key
+fn hasher_size(hasher: &Hasher) -> usize {
+    match hasher {
+        Hasher::Blake2_128 | Hasher::Blake2_128Concat | Hasher::Twox128 => 16,
+        Hasher::Blake2_256 | Hasher::Twox256 => 32,
+        Hasher::Twox64Concat => 8,
+        Hasher::Identity => 0,
+    }
+}
+
 /// An extension trait for a decoded metadata object that provides
 /// convenient methods to navigate and extract data from it.
 pub trait Meta {
@@ -123,11 +293,7 @@ impl StorageKey {
         item: &str,
         map_keys: &[T],
     ) -> crate::Result<Self> {
-        let entry = meta
-            .storage
-            .as_ref()
-            .and_then(|s| s.entries.iter().find(|e| e.name == item))
-            .ok_or(crate::Error::CantFindMethodInPallet)?;
+        let entry = storage_entry(meta, item).ok_or(crate::Error::CantFindMethodInPallet)?;
         log::trace!(
             "map_keys={}",
             map_keys
@@ -157,6 +323,8 @@ fn extract_touple_type(key_id: u32, type_info: &Type) -> Vec<TypeId> {
 }
 
 pub trait EntryTy {
+    fn value_ty(&self) -> TypeId;
+
     fn key<T: AsRef<str>>(
         &self,
         registry: &PortableRegistry,
@@ -211,6 +379,8 @@ pub trait EntryTy {
 
                         let hasher = hasher.borrow();
                         let mut out = vec![];
+                        let account = crate::ss58::hex_key(portable_reg, type_id, k);
+                        let k = account.as_deref().unwrap_or(k);
 
                         if let Some(k) = k.strip_prefix("0x") {
                             let value = hex::decode(k).expect("str must be encoded");
@@ -236,6 +406,8 @@ pub trait EntryTy {
                 .flat_map(|(i, type_id)| {
                     let k = map_keys.get(i).expect("to exist in map_keys").as_ref();
                     let mut out = vec![];
+                    let account = crate::ss58::hex_key(portable_reg, type_id, k);
+                    let k = account.as_deref().unwrap_or(k);
                     if let Some(k) = k.strip_prefix("0x") {
                         let value = hex::decode(k).expect("str must be hex encoded");
                         let _ = to_bytes_with_info(&mut out, &value, Some((portable_reg, type_id)));
@@ -272,6 +444,12 @@ pub trait EntryTy {
 }
 
 impl EntryTy for EntryType {
+    fn value_ty(&self) -> TypeId {
+        match self {
+            Self::Plain(ty) | Self::Map { value: ty, .. } => ty.id,
+        }
+    }
+
     fn key<T: AsRef<str>>(
         &self,
         registry: &PortableRegistry,
@@ -0,0 +1,696 @@
+//! Layers that add behavior around the requests of an [`Rpc`] backend.
+//!
+//! Layers are stacked on top of a transport with [`RpcExt::with`], the
+//! last layer added is the first to see a request, e.g.
+//! `RpcClient(http.with(Retry::default()).with(RateLimit::per_second(20)))`
+//! retries the requests that passed the rate limiter. The backends created
+//! by the builder stack the layers of the [`Middleware`] in their
+//! [`BackendConfig`](crate::BackendConfig).
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{future::Future, time::Duration};
+use std::{sync::Mutex, time::Instant};
+
+use async_io::Timer;
+use jsonrpc::serde_json::value::RawValue;
+use serde::Deserialize;
+
+use crate::prelude::*;
+use crate::rpc::{self, Rpc, RpcResult};
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+use crate::rpc::{RpcSubscribe, Subscription};
+
+/// Wraps an Rpc backend to handle its requests
+pub trait Layer<R> {
+    type Rpc: Rpc;
+
+    fn layer(self, inner: R) -> Self::Rpc;
+}
+
+pub trait RpcExt: Rpc + Sized {
+    fn with<L: Layer<Self>>(self, layer: L) -> L::Rpc {
+        layer.layer(self)
+    }
+}
+
+impl<R: Rpc> RpcExt for R {}
+
+/// Prefixes of the methods that only read from the node, they are safe to repeat
+pub const IDEMPOTENT_METHODS: &[&str] = &[
+    "state_",
+    "childstate_",
+    "chain_",
+    "system_",
+    "rpc_methods",
+    "chainHead_v1_",
+    "chainSpec_v1_",
+];
+
+/// Retries requests that failed with a transport error, waiting an
+/// exponential backoff between attempts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Retry {
+    pub max_retries: u32,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Prefixes of the methods that are retried, other requests like
+    /// `author_submitExtrinsic` could have reached the node before failing
+    /// and are not sent again
+    pub methods: Vec<String>,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_retries: 3,
+            min_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            methods: IDEMPOTENT_METHODS.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+impl Retry {
+    /// Retries the requests of the method too, e.g. a custom RPC that only reads
+    pub fn allow(mut self, method: impl Into<String>) -> Self {
+        self.methods.push(method.into());
+        self
+    }
+
+    fn retries(&self, method: &str) -> bool {
+        self.methods.iter().any(|m| method.starts_with(m.as_str()))
+    }
+
+    async fn run<T, F, Fut>(&self, methods: &[&str], request: F) -> RpcResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = RpcResult<T>>,
+    {
+        if !methods.iter().all(|m| self.retries(m)) {
+            return request().await;
+        }
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(rpc::error::Error::Transport(err)) if attempt < self.max_retries => {
+                    let backoff = self
+                        .min_backoff
+                        .saturating_mul(2u32.saturating_pow(attempt))
+                        .min(self.max_backoff);
+                    attempt += 1;
+                    log::warn!(
+                        "RPC `{}` failed ({}), retrying in {:?}",
+                        name(methods),
+                        err,
+                        backoff
+                    );
+                    Timer::after(backoff).await;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Name of a request in logs and spans
+fn name<'a>(methods: &[&'a str]) -> &'a str {
+    match methods {
+        [method] => method,
+        _ => "batch",
+    }
+}
+
+impl<R: Rpc> Layer<R> for Retry {
+    type Rpc = Retrying<R>;
+
+    fn layer(self, inner: R) -> Self::Rpc {
+        Retrying {
+            inner,
+            policy: self,
+        }
+    }
+}
+
+pub struct Retrying<R> {
+    inner: R,
+    policy: Retry,
+}
+
+impl<R: Rpc> Rpc for Retrying<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.policy
+            .run(&[method], || self.inner.rpc(method, params))
+            .await
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        let methods = requests.iter().map(|(m, _)| *m).collect::<Vec<_>>();
+        self.policy
+            .run(&methods, || self.inner.rpc_batch(requests))
+            .await
+    }
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl<R: RpcSubscribe> RpcSubscribe for Retrying<R> {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        self.policy
+            .run(&[method], || self.inner.subscribe(method, params))
+            .await
+    }
+}
+
+/// Client side limit of the requests sent per second, requests over the
+/// limit wait for their turn
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub requests_per_second: u32,
+    /// Requests that can be sent at once after some time without requests
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn per_second(requests: u32) -> Self {
+        RateLimit {
+            requests_per_second: requests,
+            burst: requests,
+        }
+    }
+}
+
+impl<R: Rpc> Layer<R> for RateLimit {
+    type Rpc = RateLimited<R>;
+
+    fn layer(self, inner: R) -> Self::Rpc {
+        RateLimited {
+            inner,
+            limiter: Limiter::new(self),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket shared by the requests of a rate limited transport
+struct Limiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+pub struct RateLimited<R> {
+    inner: R,
+    limiter: Limiter,
+}
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Self {
+        Limiter {
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                updated: Instant::now(),
+            }),
+            limit,
+        }
+    }
+
+    async fn acquire(&self, requests: usize) {
+        let rate = self.limit.requests_per_second.max(1) as f64;
+        let burst = self.limit.burst.max(1) as f64;
+        for _ in 0..requests {
+            loop {
+                let wait = {
+                    let mut bucket = self.bucket.lock().expect("not poisoned");
+                    let now = Instant::now();
+                    let refill = now.duration_since(bucket.updated).as_secs_f64() * rate;
+                    bucket.tokens = (bucket.tokens + refill).min(burst);
+                    bucket.updated = now;
+                    if bucket.tokens >= 1.0 {
+                        bucket.tokens -= 1.0;
+                        break;
+                    }
+                    Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+                };
+                log::trace!("RPC rate limited for {:?}", wait);
+                Timer::after(wait).await;
+            }
+        }
+    }
+}
+
+impl<R: Rpc> Rpc for RateLimited<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.limiter.acquire(1).await;
+        self.inner.rpc(method, params).await
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        self.limiter.acquire(requests.len()).await;
+        self.inner.rpc_batch(requests).await
+    }
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl<R: RpcSubscribe> RpcSubscribe for RateLimited<R> {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        self.limiter.acquire(1).await;
+        self.inner.subscribe(method, params).await
+    }
+}
+
+/// Receives the outcome of every request, implement it to forward the
+/// measurements to the metrics system of the application
+pub trait Recorder {
+    fn record(&self, method: &str, latency: Duration, failed: bool);
+}
+
+impl<M: Recorder + ?Sized> Recorder for Arc<M> {
+    fn record(&self, method: &str, latency: Duration, failed: bool) {
+        (**self).record(method, latency, failed)
+    }
+}
+
+/// Runs a request recording its outcome once for each of its methods
+async fn measure<T>(
+    recorder: &impl Recorder,
+    methods: &[&str],
+    request: impl Future<Output = RpcResult<T>>,
+) -> RpcResult<T> {
+    let start = Instant::now();
+    let res = request.await;
+    let latency = start.elapsed();
+    for method in methods {
+        recorder.record(method, latency, res.is_err());
+    }
+    res
+}
+
+/// Measures the latency and errors of the requests of each RPC method
+pub struct Metrics<M>(pub M);
+
+impl<R: Rpc, M: Recorder> Layer<R> for Metrics<M> {
+    type Rpc = Metered<R, M>;
+
+    fn layer(self, inner: R) -> Self::Rpc {
+        Metered {
+            inner,
+            recorder: self.0,
+        }
+    }
+}
+
+pub struct Metered<R, M> {
+    inner: R,
+    recorder: M,
+}
+
+impl<R: Rpc, M: Recorder> Rpc for Metered<R, M> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        measure(&self.recorder, &[method], self.inner.rpc(method, params)).await
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        let methods = requests.iter().map(|(m, _)| *m).collect::<Vec<_>>();
+        measure(&self.recorder, &methods, self.inner.rpc_batch(requests)).await
+    }
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl<R: RpcSubscribe, M: Recorder> RpcSubscribe for Metered<R, M> {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        measure(
+            &self.recorder,
+            &[method],
+            self.inner.subscribe(method, params),
+        )
+        .await
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub requests: u64,
+    pub errors: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+/// In memory recorder that keeps the stats of each RPC method
+#[derive(Default)]
+pub struct Counters(Mutex<BTreeMap<String, Stats>>);
+
+impl Counters {
+    pub fn snapshot(&self) -> BTreeMap<String, Stats> {
+        self.0.lock().expect("not poisoned").clone()
+    }
+}
+
+impl Recorder for Counters {
+    fn record(&self, method: &str, latency: Duration, failed: bool) {
+        let mut counters = self.0.lock().expect("not poisoned");
+        let stats = counters.entry(method.into()).or_default();
+        stats.requests += 1;
+        stats.errors += failed as u64;
+        stats.total_latency += latency;
+        stats.max_latency = stats.max_latency.max(latency);
+    }
+}
+
+/// Recorder that emits the measurements through the `metrics` crate to the
+/// exporter installed by the application, as the `sube_rpc_requests_total`
+/// and `sube_rpc_errors_total` counters and the
+/// `sube_rpc_request_duration_seconds` histogram labeled with the method
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Facade;
+
+#[cfg(feature = "metrics")]
+impl Recorder for Facade {
+    fn record(&self, method: &str, latency: Duration, failed: bool) {
+        let method = method.to_string();
+        metrics::counter!("sube_rpc_requests_total", "method" => method.clone()).increment(1);
+        if failed {
+            metrics::counter!("sube_rpc_errors_total", "method" => method.clone()).increment(1);
+        }
+        metrics::histogram!("sube_rpc_request_duration_seconds", "method" => method)
+            .record(latency.as_secs_f64());
+    }
+}
+
+/// Runs every request in a `tracing` span named after the RPC method
+#[cfg(feature = "tracing")]
+pub struct Tracing;
+
+#[cfg(feature = "tracing")]
+impl<R: Rpc> Layer<R> for Tracing {
+    type Rpc = Traced<R>;
+
+    fn layer(self, inner: R) -> Self::Rpc {
+        Traced(inner)
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub struct Traced<R>(R);
+
+#[cfg(feature = "tracing")]
+async fn traced<T>(method: &str, request: impl Future<Output = RpcResult<T>>) -> RpcResult<T> {
+    use tracing::Instrument as _;
+
+    let span = tracing::info_span!("rpc", method);
+    async {
+        let res = request.await;
+        if let Err(err) = &res {
+            tracing::warn!(error = %err, "request failed");
+        }
+        res
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(feature = "tracing")]
+impl<R: Rpc> Rpc for Traced<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        traced(method, self.0.rpc(method, params)).await
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        traced("batch", self.0.rpc_batch(requests)).await
+    }
+}
+
+#[cfg(all(
+    feature = "tracing",
+    any(feature = "ws", feature = "ws-tokio", feature = "light")
+))]
+impl<R: RpcSubscribe> RpcSubscribe for Traced<R> {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        traced(method, self.0.subscribe(method, params)).await
+    }
+}
+
+/// Layers the builder adds to the transports of the nodes it connects to,
+/// e.g. `sube(url).with_config(BackendConfig::default().with_middleware(..))`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Middleware {
+    pub retry: Option<Retry>,
+    pub rate_limit: Option<RateLimit>,
+    /// Report the requests through the `metrics` crate
+    #[cfg(feature = "metrics")]
+    pub metrics: bool,
+    /// Run the requests in `tracing` spans
+    #[cfg(feature = "tracing")]
+    pub tracing: bool,
+}
+
+impl Middleware {
+    pub fn is_empty(&self) -> bool {
+        *self == Middleware::default()
+    }
+}
+
+impl<R: Rpc> Layer<R> for Middleware {
+    type Rpc = Stack<R>;
+
+    fn layer(self, inner: R) -> Self::Rpc {
+        Stack {
+            inner,
+            limiter: self.rate_limit.clone().map(Limiter::new),
+            middleware: self,
+        }
+    }
+}
+
+/// The layers of a [`Middleware`] around a transport, retries go through
+/// the rate limiter and each attempt is measured
+pub struct Stack<R> {
+    inner: R,
+    middleware: Middleware,
+    limiter: Option<Limiter>,
+}
+
+impl<R> Stack<R> {
+    async fn call<T, F, Fut>(&self, methods: &[&str], request: F) -> RpcResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = RpcResult<T>>,
+    {
+        let attempt = || async {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(methods.len()).await;
+            }
+            #[cfg(feature = "tracing")]
+            let request = || async {
+                if self.middleware.tracing {
+                    traced(name(methods), request()).await
+                } else {
+                    request().await
+                }
+            };
+            #[cfg(feature = "metrics")]
+            if self.middleware.metrics {
+                return measure(&Facade, methods, request()).await;
+            }
+            request().await
+        };
+        match &self.middleware.retry {
+            Some(retry) => retry.run(methods, attempt).await,
+            None => attempt().await,
+        }
+    }
+}
+
+impl<R: Rpc> Rpc for Stack<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.call(&[method], || self.inner.rpc(method, params))
+            .await
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        let methods = requests.iter().map(|(m, _)| *m).collect::<Vec<_>>();
+        self.call(&methods, || self.inner.rpc_batch(requests)).await
+    }
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl<R: RpcSubscribe> RpcSubscribe for Stack<R> {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        self.call(&[method], || self.inner.subscribe(method, params))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc::{error::RpcError, serde_json};
+
+    type Fault = fn() -> rpc::error::Error;
+
+    fn disconnect() -> rpc::error::Error {
+        rpc::error::Error::Transport("connection lost".into())
+    }
+
+    fn node_error() -> rpc::error::Error {
+        rpc::error::Error::Rpc(RpcError {
+            code: -32000,
+            message: "boom".into(),
+            data: None,
+        })
+    }
+
+    /// Node answering `"ok"` once the faults injected for a method are used up
+    #[derive(Default)]
+    struct Node {
+        faults: Mutex<Vec<(&'static str, Fault)>>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl Node {
+        fn inject(&self, method: &'static str, fault: Fault) {
+            self.faults.lock().unwrap().push((method, fault));
+        }
+
+        fn count(&self, method: &str) -> usize {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().filter(|m| *m == method).count()
+        }
+    }
+
+    impl Rpc for &Node {
+        async fn rpc<T>(&self, method: &str, _params: &[&str]) -> RpcResult<T>
+        where
+            T: for<'de> Deserialize<'de>,
+        {
+            self.requests.lock().unwrap().push(method.into());
+            let mut faults = self.faults.lock().unwrap();
+            if let Some(i) = faults.iter().position(|(m, _)| *m == method) {
+                return Err((faults.remove(i).1)());
+            }
+            Ok(serde_json::from_str("\"ok\"")?)
+        }
+    }
+
+    fn retry(max_retries: u32) -> Retry {
+        Retry {
+            max_retries,
+            min_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn transport_errors_are_retried() {
+        let node = Node::default();
+        let rpc = (&node).with(retry(3));
+
+        node.inject("system_chain", disconnect);
+        node.inject("system_chain", disconnect);
+        let chain = rpc.rpc::<String>("system_chain", &[]).await;
+        assert_eq!(chain.unwrap(), "ok");
+        assert_eq!(node.count("system_chain"), 3);
+    }
+
+    #[async_std::test]
+    async fn retries_give_up_after_the_max() {
+        let node = Node::default();
+        let rpc = (&node).with(retry(2));
+
+        for _ in 0..3 {
+            node.inject("system_chain", disconnect);
+        }
+        let chain = rpc.rpc::<String>("system_chain", &[]).await;
+        assert!(matches!(chain, Err(rpc::error::Error::Transport(_))));
+        assert_eq!(node.count("system_chain"), 3);
+    }
+
+    #[async_std::test]
+    async fn node_errors_are_not_retried() {
+        let node = Node::default();
+        let rpc = (&node).with(retry(3));
+
+        node.inject("system_chain", node_error);
+        let chain = rpc.rpc::<String>("system_chain", &[]).await;
+        assert!(matches!(chain, Err(rpc::error::Error::Rpc(_))));
+        assert_eq!(node.count("system_chain"), 1);
+    }
+
+    #[async_std::test]
+    async fn submissions_are_not_retried() {
+        let node = Node::default();
+        let rpc = (&node).with(retry(3));
+
+        node.inject("author_submitExtrinsic", disconnect);
+        let res = rpc
+            .rpc::<String>("author_submitExtrinsic", &["\"0x00\""])
+            .await;
+        assert!(matches!(res, Err(rpc::error::Error::Transport(_))));
+        assert_eq!(node.count("author_submitExtrinsic"), 1);
+
+        // unless they are allowed
+        let rpc = (&node).with(retry(3).allow("author_submitExtrinsic"));
+        node.inject("author_submitExtrinsic", disconnect);
+        let res = rpc
+            .rpc::<String>("author_submitExtrinsic", &["\"0x00\""])
+            .await;
+        assert!(res.is_ok());
+        assert_eq!(node.count("author_submitExtrinsic"), 3);
+    }
+
+    #[async_std::test]
+    async fn metrics_see_the_requests_of_the_layers_below() {
+        let node = Node::default();
+        let (attempts, calls) = (Arc::new(Counters::default()), Arc::new(Counters::default()));
+        let rpc = (&node)
+            .with(Metrics(attempts.clone()))
+            .with(retry(3))
+            .with(Metrics(calls.clone()));
+
+        node.inject("system_chain", disconnect);
+        rpc.rpc::<String>("system_chain", &[]).await.unwrap();
+        node.inject("system_chain", node_error);
+        assert!(rpc.rpc::<String>("system_chain", &[]).await.is_err());
+
+        let attempts = attempts.snapshot()["system_chain"];
+        assert_eq!((attempts.requests, attempts.errors), (3, 2));
+        let calls = calls.snapshot()["system_chain"];
+        assert_eq!((calls.requests, calls.errors), (2, 1));
+        assert!(calls.max_latency >= attempts.max_latency);
+    }
+
+    #[async_std::test]
+    async fn middleware_stacks_its_layers() {
+        let node = Node::default();
+        let rpc = (&node).with(Middleware {
+            retry: Some(retry(3)),
+            rate_limit: Some(RateLimit::per_second(1000)),
+            ..Default::default()
+        });
+
+        node.inject("state_getMetadata", disconnect);
+        let batch = rpc
+            .rpc_batch(&[("state_getMetadata", &[]), ("system_chain", &[])])
+            .await;
+        assert_eq!(batch.unwrap().len(), 2);
+        assert_eq!(node.count("state_getMetadata"), 2);
+        assert_eq!(node.count("system_chain"), 1);
+    }
+}
@@ -0,0 +1,407 @@
+//! In memory backend to test code built on sube without a node.
+//!
+//! Storage is a plain key/value map seeded with raw entries or JSON values
+//! set through sube paths, it's the same for every block. Extrinsics
+//! given to `submit` are decoded with the metadata and recorded.
+use crate::hasher::blake2_256;
+use crate::prelude::*;
+use crate::{
+    meta::{self, BlockInfo, Meta as _},
+    meta_ext::StorageKey,
+    parse_uri,
+    proof::ReadProof,
+    Backend, ChainProperties, Error, JsonValue, Metadata, RawKey, RawValue, Result, Value,
+};
+use alloc::collections::BTreeMap;
+use codec::{Compact, Decode};
+use core::ops::Bound;
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// An extrinsic received by the mock, the signer, signed extensions and
+/// call are the JSON representation of their types in the metadata
+#[derive(Clone, Debug, Serialize)]
+pub struct Extrinsic {
+    pub signer: Option<JsonValue>,
+    pub extra: Option<JsonValue>,
+    pub call: JsonValue,
+    #[serde(skip)]
+    pub raw: Vec<u8>,
+}
+
+pub struct MockBackend {
+    meta: Metadata,
+    storage: Mutex<BTreeMap<RawKey, RawValue>>,
+    child_storage: Mutex<BTreeMap<RawKey, BTreeMap<RawKey, RawValue>>>,
+    block: Mutex<u32>,
+    submitted: Mutex<Vec<Extrinsic>>,
+    properties: Option<ChainProperties>,
+}
+
+impl MockBackend {
+    pub fn new(meta: Metadata) -> Self {
+        MockBackend {
+            meta,
+            storage: Mutex::new(BTreeMap::new()),
+            child_storage: Mutex::new(BTreeMap::new()),
+            block: Mutex::new(0),
+            submitted: Mutex::new(vec![]),
+            properties: None,
+        }
+    }
+
+    /// Seeds the storage with raw key/value pairs
+    pub fn with_storage(self, items: impl IntoIterator<Item = (RawKey, RawValue)>) -> Self {
+        self.storage.lock().expect("not poisoned").extend(items);
+        self
+    }
+
+    /// Chain name, version and token reported by `Backend::properties`
+    pub fn with_properties(self, properties: ChainProperties) -> Self {
+        MockBackend {
+            properties: Some(properties),
+            ..self
+        }
+    }
+
+    pub fn meta(&self) -> &Metadata {
+        &self.meta
+    }
+
+    /// Sets the value of a storage item given its path and JSON representation,
+    /// e.g. `mock.set("system/account/0x..", json!({ "nonce": 1, .. }))`
+    pub fn set(&self, path: &str, value: JsonValue) -> Result<()> {
+        let key = self.storage_key(path)?;
+        let value = scales::to_vec_with_info(&value, (&self.meta.types, key.ty).into())
+            .map_err(|e| Error::Encode(e.to_string()))?;
+        self.set_raw(key.key(), value);
+        Ok(())
+    }
+
+    pub fn set_raw(&self, key: RawKey, value: RawValue) {
+        self.storage
+            .lock()
+            .expect("not poisoned")
+            .insert(key, value);
+    }
+
+    pub fn remove(&self, path: &str) -> Result<()> {
+        let key = self.storage_key(path)?;
+        self.storage
+            .lock()
+            .expect("not poisoned")
+            .remove(&key.key());
+        Ok(())
+    }
+
+    /// Sets a value in the child trie identified by the prefixed `child_key`
+    pub fn set_child_raw(&self, child_key: RawKey, key: RawKey, value: RawValue) {
+        self.child_storage
+            .lock()
+            .expect("not poisoned")
+            .entry(child_key)
+            .or_default()
+            .insert(key, value);
+    }
+
+    /// Number of the latest block, queries without a block use it
+    pub fn set_block(&self, number: u32) {
+        *self.block.lock().expect("not poisoned") = number;
+    }
+
+    /// The extrinsics submitted so far in the order they were received
+    pub fn submitted(&self) -> Vec<Extrinsic> {
+        self.submitted.lock().expect("not poisoned").clone()
+    }
+
+    fn storage_key(&self, path: &str) -> Result<StorageKey> {
+        let (pallet, item, keys) = parse_uri(path).ok_or(Error::BadInput)?;
+        let pallet = self
+            .meta
+            .pallet_by_name(&pallet)
+            .ok_or(Error::PalletNotFound(pallet))?;
+        let key = StorageKey::build_with_registry(&self.meta.types, pallet, &item, &keys)?;
+        if key.is_partial() {
+            return Err(Error::BadKey);
+        }
+        Ok(key)
+    }
+
+    fn decode_extrinsic(&self, raw: &[u8]) -> Result<Extrinsic> {
+        let types = &self.meta.types;
+        let ty = types
+            .resolve(self.meta.extrinsic.ty.id)
+            .ok_or(Error::BadMetadata)?;
+        let param = |name: &str| {
+            ty.type_params
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.ty)
+                .map(|ty| ty.id)
+                .ok_or(Error::BadMetadata)
+        };
+
+        let mut data = raw;
+        Compact::<u32>::decode(&mut data).map_err(Error::Decode)?;
+        let (version, mut data) = data.split_first().ok_or(Error::BadInput)?;
+
+        // takes the next value of the given type from the data
+        let mut next = |ty: u32| {
+            let size = Value::new(data.to_vec(), ty, types).size();
+            let (value, rest) = data.split_at_checked(size).ok_or(Error::BadInput)?;
+            data = rest;
+            Ok::<_, Error>(JsonValue::from(Value::new(value.to_vec(), ty, types)))
+        };
+
+        let is_signed = version & 0b1000_0000 != 0;
+        let (signer, extra) = if is_signed {
+            let signer = next(param("Address")?)?;
+            next(param("Signature")?)?;
+            (Some(signer), Some(next(param("Extra")?)?))
+        } else {
+            (None, None)
+        };
+        let call = next(param("Call")?)?;
+
+        Ok(Extrinsic {
+            signer,
+            extra,
+            call,
+            raw: raw.to_vec(),
+        })
+    }
+
+    fn block_number(&self, at: Option<u32>) -> u32 {
+        at.unwrap_or_else(|| *self.block.lock().expect("not poisoned"))
+    }
+}
+
+fn block_hash(number: u32) -> [u8; 32] {
+    blake2_256(number.to_le_bytes())
+}
+
+impl Backend for MockBackend {
+    async fn get_storage_items(
+        &self,
+        keys: Vec<RawKey>,
+        _block: Option<u32>,
+    ) -> Result<impl Iterator<Item = (RawKey, RawValue)>> {
+        let storage = self.storage.lock().expect("not poisoned");
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let value = storage.get(&key)?.clone();
+                Some((key, value))
+            })
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    async fn get_keys_paged(
+        &self,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        _block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        let storage = self.storage.lock().expect("not poisoned");
+        let start = to.unwrap_or_else(|| from.clone());
+        Ok(storage
+            .range::<RawKey, _>((Bound::Excluded(&start), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(&from))
+            .take(size as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        _block: Option<u32>,
+    ) -> Result<RawValue> {
+        self.child_storage
+            .lock()
+            .expect("not poisoned")
+            .get(&child_key)
+            .and_then(|child| child.get(&key).cloned())
+            .ok_or(Error::StorageKeyNotFound)
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        _block: Option<u32>,
+    ) -> Result<Vec<(RawKey, RawValue)>> {
+        let child_storage = self.child_storage.lock().expect("not poisoned");
+        let Some(child) = child_storage.get(&child_key) else {
+            return Ok(vec![]);
+        };
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| Some((key.clone(), child.get(&key)?.clone())))
+            .collect())
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        _block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        let child_storage = self.child_storage.lock().expect("not poisoned");
+        let Some(child) = child_storage.get(&child_key) else {
+            return Ok(vec![]);
+        };
+        let start = to.unwrap_or_else(|| from.clone());
+        Ok(child
+            .range::<RawKey, _>((Bound::Excluded(&start), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(&from))
+            .take(size as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> Result<[u8; 32]> {
+        let value = self.get_child_storage_item(child_key, key, block).await?;
+        Ok(blake2_256(value))
+    }
+
+    async fn get_read_proof(&self, _keys: Vec<RawKey>, _block: Option<u32>) -> Result<ReadProof> {
+        Err(Error::Node("the mock backend has no storage proofs".into()))
+    }
+
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+        let extrinsic = self.decode_extrinsic(ext.as_ref())?;
+        log::debug!("Mock extrinsic submitted {:?}", extrinsic.call);
+        self.submitted.lock().expect("not poisoned").push(extrinsic);
+        Ok(blake2_256(ext))
+    }
+
+    async fn metadata(&self) -> Result<Metadata> {
+        Ok(self.meta.clone())
+    }
+
+    async fn block_info(&self, at: Option<u32>) -> Result<meta::BlockInfo> {
+        let number = self.block_number(at);
+        Ok(BlockInfo {
+            number: number.into(),
+            hash: block_hash(number),
+            parent: number.checked_sub(1).map(block_hash).unwrap_or_default(),
+        })
+    }
+
+    async fn properties(&self) -> Result<ChainProperties> {
+        self.properties.clone().ok_or(Error::ChainUnavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{metadata, storage_key, value_key};
+    use crate::{json, ExtrinsicBody, Response, SignerFn};
+    use codec::Encode;
+
+    async fn query(mock: &MockBackend, path: &str) -> JsonValue {
+        let res = crate::query(mock, mock.meta(), path, None).await;
+        serde_json::to_value(res.unwrap()).unwrap()
+    }
+
+    #[async_std::test]
+    async fn values_set_by_path() {
+        let mock = MockBackend::new(metadata()).with_storage([(value_key(1), 10u64.encode())]);
+        mock.set("maps/values/2", json!(20)).unwrap();
+
+        assert_eq!(query(&mock, "maps/values/1").await, json!(10));
+        assert_eq!(query(&mock, "maps/values/2").await, json!(20));
+        assert_eq!(
+            mock.get_storage_item(value_key(2), None).await.unwrap(),
+            20u64.encode()
+        );
+
+        mock.remove("maps/values/2").unwrap();
+        assert_eq!(query(&mock, "maps/values/2").await, json!(42));
+        assert!(mock.set("maps/values", json!(1)).is_err());
+    }
+
+    #[async_std::test]
+    async fn keys_are_paged_over_the_map() {
+        let mock = MockBackend::new(metadata())
+            .with_storage((0..5u32).map(|n| (value_key(n), (n as u64).encode())));
+        mock.set("maps/optional/1", json!(1)).unwrap();
+
+        let prefix = storage_key("Maps", "Values");
+        let first = mock.get_keys_paged(prefix.clone(), 3, None, None).await;
+        let first = first.unwrap();
+        assert_eq!(first.len(), 3);
+        let rest = mock
+            .get_keys_paged(prefix, 3, first.last().cloned(), None)
+            .await;
+        assert_eq!(rest.unwrap().len(), 2);
+
+        let values = query(&mock, "maps/values").await;
+        assert_eq!(values.as_array().unwrap().len(), 5);
+    }
+
+    #[async_std::test]
+    async fn submitted_extrinsics_are_decoded() {
+        let mock = MockBackend::new(metadata());
+        let account = [1u8; 32];
+        mock.set(
+            &format!("system/account/0x{}", hex::encode(account)),
+            json!({
+                "nonce": 5,
+                "consumers": 0,
+                "providers": 1,
+                "sufficients": 0,
+                "data": { "free": 100, "reserved": 0, "frozen": 0, "flags": 0 }
+            }),
+        )
+        .unwrap();
+
+        let signer = SignerFn::from((account, |_: &[u8]| async { Ok([2u8; 64]) }));
+        let body = ExtrinsicBody {
+            nonce: None,
+            body: json!({ "remark": [1, 2, 3] }),
+        };
+        let res = crate::submit(&mock, mock.meta(), "system/remark", body, signer).await;
+        let Response::Submitted { hash, extrinsic } = res.unwrap() else {
+            panic!("expected a submission");
+        };
+
+        let submitted = mock.submitted();
+        assert_eq!(submitted.len(), 1);
+        let ext = &submitted[0];
+        assert_eq!(ext.raw, extrinsic);
+        assert_eq!(hash, blake2_256(&extrinsic));
+        assert_eq!(ext.signer, Some(json!({ "Id": account })));
+        assert_eq!(ext.extra.as_ref().unwrap()["nonce"], json!(5));
+        assert_eq!(
+            ext.call,
+            json!({ "System": { "remark": { "remark": [1, 2, 3] } } })
+        );
+    }
+
+    #[async_std::test]
+    async fn blocks_follow_the_latest_number() {
+        let mock = MockBackend::new(metadata());
+        mock.set_block(3);
+
+        let latest = mock.block_info(None).await.unwrap();
+        let parent = mock.block_info(Some(2)).await.unwrap();
+        assert_eq!(latest.number, 3);
+        assert_eq!(latest.parent, parent.hash);
+        assert_eq!(mock.block_info(Some(0)).await.unwrap().parent, [0; 32]);
+    }
+}
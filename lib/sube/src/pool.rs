@@ -0,0 +1,535 @@
+use alloc::collections::VecDeque;
+use core::{
+    future::Future,
+    pin::pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{sync::Mutex, time::Instant};
+
+use futures_util::future::{join_all, select, Either};
+use jsonrpc::{
+    error::{standard_error, StandardError},
+    serde_json::{self, value::RawValue},
+};
+use serde::Deserialize;
+
+use crate::{
+    prelude::*,
+    rpc::{self, Rpc, RpcResult},
+};
+
+/// Methods sent to several nodes at once to improve their chances of
+/// reaching the network
+const BROADCAST_METHODS: &[&str] = &["author_submitExtrinsic"];
+
+/// Methods answering with a block hash, later requests for that block are
+/// sent to the node that answered it since a lagging node might not know
+/// about it yet
+const PINNING_METHODS: &[&str] = &["chain_getBlockHash", "chain_getFinalizedHead"];
+
+/// Number of block hashes remembered for pinning requests to a node
+const PINNED: usize = 64;
+
+/// How requests are distributed among the healthy nodes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strategy {
+    #[default]
+    RoundRobin,
+    /// Prefer the node that has been answering the fastest
+    Latency,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub strategy: Strategy,
+    /// Number of nodes a submitted extrinsic is sent to
+    pub broadcast: usize,
+    /// Time between health checks of the nodes
+    pub health_interval: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            strategy: Strategy::RoundRobin,
+            broadcast: 2,
+            health_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Health {
+    peers: u32,
+    is_syncing: bool,
+    should_have_peers: bool,
+}
+
+impl Health {
+    fn is_healthy(&self) -> bool {
+        !self.is_syncing && (self.peers > 0 || !self.should_have_peers)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Status {
+    healthy: bool,
+    /// Moving average of the response times
+    latency: Duration,
+    /// When the last health check was sent
+    checked: Option<Instant>,
+}
+
+impl Status {
+    fn record(&mut self, elapsed: Duration) {
+        self.healthy = true;
+        self.latency = if self.latency.is_zero() {
+            elapsed
+        } else {
+            self.latency * 7 / 8 + elapsed / 8
+        };
+    }
+}
+
+/// Rpc backend spreading the requests among several nodes of the same
+/// chain, nodes failing with a transport error are skipped until they
+/// pass a health check (`system_health`) again.
+/// Requests referring to a block hash the pool got from one of its nodes
+/// are sent to that same node.
+pub struct Pool<R> {
+    nodes: Vec<R>,
+    options: Options,
+    status: Mutex<Vec<Status>>,
+    next: AtomicUsize,
+    /// Block hashes (as JSON) and the node that answered them
+    pinned: Mutex<VecDeque<(String, usize)>>,
+}
+
+impl<R: Rpc> Pool<R> {
+    pub fn new(nodes: Vec<R>) -> Self {
+        Self::with_options(nodes, Options::default())
+    }
+
+    pub fn with_options(nodes: Vec<R>, options: Options) -> Self {
+        let status = nodes
+            .iter()
+            .map(|_| Status {
+                healthy: true,
+                latency: Duration::ZERO,
+                checked: None,
+            })
+            .collect();
+        Pool {
+            nodes,
+            options,
+            status: Mutex::new(status),
+            next: AtomicUsize::new(0),
+            pinned: Mutex::new(VecDeque::with_capacity(PINNED)),
+        }
+    }
+
+    pub fn nodes(&self) -> &[R] {
+        &self.nodes
+    }
+
+    /// Indexes of the nodes in the order they should be tried, healthy
+    /// nodes first and the unhealthy ones as a last resort. A healthy node
+    /// that answered a block hash found in the params goes first
+    fn order(&self, params: &[&str]) -> Vec<usize> {
+        let status = self.status.lock().expect("not poisoned");
+        let n = self.nodes.len();
+        let mut order = match self.options.strategy {
+            Strategy::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..n).map(|i| (start + i) % n).collect::<Vec<_>>()
+            }
+            Strategy::Latency => {
+                let mut order = (0..n).collect::<Vec<_>>();
+                order.sort_by_key(|&i| status[i].latency);
+                order
+            }
+        };
+        // stable sort keeps the order of the strategy within each group
+        order.sort_by_key(|&i| !status[i].healthy);
+
+        let pinned = self.pinned.lock().expect("not poisoned");
+        let pin = pinned
+            .iter()
+            .rev()
+            .find(|(hash, _)| params.contains(&hash.as_str()))
+            .map(|&(_, node)| node);
+        if let Some(node) = pin.filter(|&node| status[node].healthy) {
+            order.retain(|&i| i != node);
+            order.insert(0, node);
+        }
+        order
+    }
+
+    fn pin(&self, method: &str, res: &RawValue, node: usize) {
+        if !PINNING_METHODS.contains(&method) {
+            return;
+        }
+        let mut pinned = self.pinned.lock().expect("not poisoned");
+        if pinned.len() == PINNED {
+            pinned.pop_front();
+        }
+        pinned.push_back((res.get().into(), node));
+    }
+
+    fn update(&self, node: usize, f: impl FnOnce(&mut Status)) {
+        f(&mut self.status.lock().expect("not poisoned")[node]);
+    }
+
+    /// Health checks of the nodes not checked within the health interval,
+    /// the status of a node is updated as soon as it answers
+    fn health_checks(&self) -> Option<impl Future<Output = ()> + '_> {
+        let due = {
+            let mut status = self.status.lock().expect("not poisoned");
+            let now = Instant::now();
+            let interval = self.options.health_interval;
+            let due = (0..self.nodes.len())
+                .filter(|&i| !status[i].checked.is_some_and(|t| now - t < interval))
+                .collect::<Vec<_>>();
+            for &i in &due {
+                status[i].checked = Some(now);
+            }
+            due
+        };
+        if due.is_empty() {
+            return None;
+        }
+
+        let checks = due.into_iter().map(|i| async move {
+            let pending = PendingCheck(self, i);
+            let start = Instant::now();
+            let health = self.nodes[i].rpc::<Health>("system_health", &[]).await;
+            core::mem::forget(pending);
+            match health {
+                Ok(health) if health.is_healthy() => self.update(i, |s| s.record(start.elapsed())),
+                Ok(_) => {
+                    log::warn!("Node {} is syncing or has no peers", i);
+                    self.update(i, |s| s.healthy = false);
+                }
+                Err(err) => {
+                    log::warn!("Node {} failed the health check: {}", i, err);
+                    self.update(i, |s| s.healthy = false);
+                }
+            }
+        });
+        Some(async move {
+            join_all(checks).await;
+        })
+    }
+
+    /// Runs the request alongside the health checks that are due without
+    /// waiting for them, checks still pending once the request is answered
+    /// are dropped and sent again with the next request
+    async fn checking_health<T>(&self, request: impl Future<Output = T>) -> T {
+        let Some(checks) = self.health_checks() else {
+            return request.await;
+        };
+        // checks go first so they are sent even if the request is answered
+        // right away
+        match select(pin!(checks), pin!(request)).await {
+            Either::Left(((), request)) => request.await,
+            Either::Right((res, _)) => res,
+        }
+    }
+
+    /// Sends the request to the nodes in order until one of them doesn't
+    /// fail with a transport error, returns the node that answered
+    async fn route<'a, T, F, Fut>(&'a self, params: &[&str], request: F) -> RpcResult<(usize, T)>
+    where
+        F: Fn(&'a R) -> Fut,
+        Fut: Future<Output = RpcResult<T>>,
+    {
+        let mut last_err = None;
+        for i in self.order(params) {
+            let start = Instant::now();
+            match request(&self.nodes[i]).await {
+                Err(err @ rpc::error::Error::Transport(_)) => {
+                    log::warn!("Node {} failed, trying the next one: {}", i, err);
+                    self.update(i, |s| s.healthy = false);
+                    last_err = Some(err);
+                }
+                res => {
+                    self.update(i, |s| s.record(start.elapsed()));
+                    return res.map(|res| (i, res));
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| standard_error(StandardError::InternalError, None).into()))
+    }
+
+    /// Sends the request to several healthy nodes at once, it succeeds if
+    /// any of them accepts it
+    async fn broadcast<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let nodes = self
+            .order(params)
+            .into_iter()
+            .take(self.options.broadcast.max(1))
+            .collect::<Vec<_>>();
+        log::info!("Broadcasting `{}` to {} nodes", method, nodes.len());
+        let requests = nodes
+            .iter()
+            .map(|&i| self.nodes[i].rpc::<T>(method, params));
+
+        let mut accepted = None;
+        let mut last_err = None;
+        for (&i, res) in nodes.iter().zip(join_all(requests).await) {
+            match res {
+                Ok(res) => {
+                    accepted.get_or_insert(res);
+                }
+                Err(err) => {
+                    log::warn!("Node {} rejected `{}`: {}", i, method, err);
+                    if matches!(err, rpc::error::Error::Transport(_)) {
+                        self.update(i, |s| s.healthy = false);
+                    }
+                    last_err = Some(err);
+                }
+            }
+        }
+        match (accepted, last_err) {
+            (Some(res), _) => Ok(res),
+            (None, Some(err)) => Err(err),
+            (None, None) => Err(standard_error(StandardError::InternalError, None).into()),
+        }
+    }
+}
+
+/// Health check of a node, it is due again if dropped before the node
+/// answers
+struct PendingCheck<'a, R>(&'a Pool<R>, usize);
+
+impl<R> Drop for PendingCheck<'_, R> {
+    fn drop(&mut self) {
+        let mut status = self.0.status.lock().expect("not poisoned");
+        status[self.1].checked = None;
+    }
+}
+
+impl<R: Rpc> Rpc for Pool<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        if BROADCAST_METHODS.contains(&method) {
+            return self.checking_health(self.broadcast(method, params)).await;
+        }
+        let request = self.route(params, |node| node.rpc::<Box<RawValue>>(method, params));
+        let (node, res) = self.checking_health(request).await?;
+        self.pin(method, &res, node);
+        Ok(serde_json::from_str(res.get())?)
+    }
+
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        let params = requests
+            .iter()
+            .flat_map(|(_, params)| params.iter().copied())
+            .collect::<Vec<_>>();
+        let request = self.route(&params, |node| node.rpc_batch(requests));
+        let (node, results) = self.checking_health(request).await?;
+        for ((method, _), res) in requests.iter().zip(&results) {
+            self.pin(method, res, node);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use async_std::future::timeout;
+    use serde_json::{json, Value};
+
+    enum Fault {
+        /// Connection lost before the node answered
+        Transport,
+        /// The node answers with an error
+        Node,
+        /// The node never answers
+        Hang,
+    }
+
+    /// Node answering every method with a fixed result unless a fault was
+    /// injected for it, it remembers the requests it was sent
+    #[derive(Default)]
+    struct FakeNode {
+        results: BTreeMap<&'static str, Value>,
+        faults: Mutex<Vec<(&'static str, Fault)>>,
+        requests: Mutex<Vec<(String, Vec<String>)>>,
+    }
+
+    impl FakeNode {
+        fn new(results: impl IntoIterator<Item = (&'static str, Value)>) -> Self {
+            let mut results = results.into_iter().collect::<BTreeMap<_, _>>();
+            results.insert(
+                "system_health",
+                json!({"peers": 3, "isSyncing": false, "shouldHavePeers": true}),
+            );
+            FakeNode {
+                results,
+                ..Default::default()
+            }
+        }
+
+        fn inject(&self, method: &'static str, fault: Fault) {
+            self.faults.lock().unwrap().push((method, fault));
+        }
+
+        fn requests(&self, method: &str) -> Vec<Vec<String>> {
+            let requests = self.requests.lock().unwrap();
+            let sent = requests.iter().filter(|(m, _)| m == method);
+            sent.map(|(_, params)| params.clone()).collect()
+        }
+
+        fn count(&self, method: &str) -> usize {
+            self.requests(method).len()
+        }
+    }
+
+    impl Rpc for FakeNode {
+        async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+        where
+            T: for<'de> Deserialize<'de>,
+        {
+            let params = params.iter().map(|p| p.to_string()).collect();
+            self.requests.lock().unwrap().push((method.into(), params));
+            let fault = {
+                let mut faults = self.faults.lock().unwrap();
+                let fault = faults.iter().position(|(m, _)| *m == method);
+                fault.map(|i| faults.remove(i).1)
+            };
+            match fault {
+                Some(Fault::Transport) => {
+                    Err(rpc::error::Error::Transport("connection lost".into()))
+                }
+                Some(Fault::Node) => Err(rpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                    code: -32000,
+                    message: "boom".into(),
+                    data: None,
+                })),
+                Some(Fault::Hang) => core::future::pending().await,
+                None => {
+                    let res = self.results.get(method).unwrap_or(&Value::Null);
+                    Ok(serde_json::from_str(&res.to_string())?)
+                }
+            }
+        }
+    }
+
+    fn pool(n: usize, options: Options) -> Pool<FakeNode> {
+        let nodes = (0..n)
+            .map(|i| {
+                FakeNode::new([
+                    ("system_chain", json!("Kreivo")),
+                    ("chain_getBlockHash", json!(format!("0x{:064x}", i))),
+                    ("author_submitExtrinsic", json!(format!("0x{:064x}", 0))),
+                ])
+            })
+            .collect();
+        Pool::with_options(nodes, options)
+    }
+
+    #[async_std::test]
+    async fn requests_move_on_when_a_node_goes_down() {
+        let pool = pool(2, Options::default());
+        pool.nodes()[0].inject("system_chain", Fault::Transport);
+
+        for _ in 0..2 {
+            let chain = pool.rpc::<String>("system_chain", &[]).await;
+            assert_eq!(chain.unwrap(), "Kreivo");
+        }
+        // the failing node is left as a last resort after dropping the request
+        assert_eq!(pool.nodes()[0].count("system_chain"), 1);
+        assert_eq!(pool.nodes()[1].count("system_chain"), 2);
+    }
+
+    #[async_std::test]
+    async fn node_errors_are_not_retried() {
+        let pool = pool(2, Options::default());
+        for node in pool.nodes() {
+            node.inject("system_chain", Fault::Node);
+        }
+
+        assert!(pool.rpc::<String>("system_chain", &[]).await.is_err());
+        let tried = pool.nodes().iter().map(|n| n.count("system_chain"));
+        assert_eq!(tried.sum::<usize>(), 1);
+    }
+
+    #[async_std::test]
+    async fn extrinsics_are_broadcast() {
+        let ext = "\"0x0400\"";
+        let all = pool(
+            3,
+            Options {
+                broadcast: 3,
+                ..Default::default()
+            },
+        );
+        // a node rejecting it doesn't fail the submission
+        all.nodes()[1].inject("author_submitExtrinsic", Fault::Node);
+        let hash = all.rpc::<String>("author_submitExtrinsic", &[ext]).await;
+        assert!(hash.is_ok());
+        for node in all.nodes() {
+            assert_eq!(node.requests("author_submitExtrinsic"), vec![vec![ext]]);
+        }
+
+        // only as many nodes as the options tell
+        let some = pool(3, Options::default());
+        let hash = some.rpc::<String>("author_submitExtrinsic", &[ext]).await;
+        assert!(hash.is_ok());
+        let sent = some
+            .nodes()
+            .iter()
+            .map(|n| n.count("author_submitExtrinsic"));
+        assert_eq!(sent.sum::<usize>(), 2);
+    }
+
+    #[async_std::test]
+    async fn slow_health_checks_dont_hold_requests_back() {
+        let pool = pool(2, Options::default());
+        pool.nodes()[1].inject("system_health", Fault::Hang);
+
+        for _ in 0..2 {
+            let chain = timeout(
+                Duration::from_secs(1),
+                pool.rpc::<String>("system_chain", &[]),
+            );
+            assert_eq!(chain.await.expect("not blocked").unwrap(), "Kreivo");
+        }
+        // the check left unanswered is sent again with the next request
+        assert_eq!(pool.nodes()[0].count("system_health"), 1);
+        assert_eq!(pool.nodes()[1].count("system_health"), 2);
+        for node in pool.nodes() {
+            assert_eq!(node.count("system_chain"), 1);
+        }
+    }
+
+    #[async_std::test]
+    async fn requests_for_a_block_hash_go_to_the_node_that_answered_it() {
+        let pool = pool(3, Options::default());
+
+        for _ in 0..6 {
+            let hash = pool.rpc::<String>("chain_getBlockHash", &["3"]).await;
+            let hash = format!("\"{}\"", hash.unwrap());
+            let changes = pool
+                .rpc::<Value>("state_queryStorageAt", &["[]", &hash])
+                .await;
+            assert!(changes.is_ok());
+        }
+        for (i, node) in pool.nodes().iter().enumerate() {
+            let hash = format!("\"0x{:064x}\"", i);
+            let queries = node.requests("state_queryStorageAt");
+            assert!(queries.iter().all(|params| params[1] == hash));
+            assert_eq!(queries.len(), node.count("chain_getBlockHash"));
+        }
+    }
+}
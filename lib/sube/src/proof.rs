@@ -0,0 +1,521 @@
+//! Storage read proofs and their verification against a block's state root.
+//!
+//! Proofs are the set of Merkle-Patricia trie nodes a node visited to read some keys,
+//! with them the values can be looked up locally starting from the trusted state root.
+//! Nodes of both state versions(V0 and V1 with hashed values) are supported.
+use crate::hasher::blake2_256;
+use crate::prelude::*;
+use crate::{meta::BlockInfo, Backend, ChainProperties, Error, RawKey, RawValue, Result};
+use alloc::collections::BTreeMap;
+use codec::{Compact, Decode, Encode};
+
+pub type Hash = [u8; 32];
+
+/// The trie nodes needed to prove some storage values at a given block
+#[derive(Clone, Debug)]
+pub struct ReadProof {
+    pub at: Hash,
+    pub state_root: Hash,
+    pub proof: Vec<Vec<u8>>,
+}
+
+impl ReadProof {
+    /// Looks up the keys in the proof, `None` values are proven to be absent from the storage
+    pub fn verify(&self, keys: &[RawKey]) -> Result<Vec<(RawKey, Option<RawValue>)>> {
+        verify(&self.state_root, &self.proof, keys)
+    }
+
+    /// Like `verify` but against a state root or header that doesn't come from the node
+    /// that gave the proof, a header must be the one of the block the proof is for
+    pub fn verify_trusted(
+        &self,
+        trusted: &Trusted,
+        keys: &[RawKey],
+    ) -> Result<Vec<(RawKey, Option<RawValue>)>> {
+        let state_root = match trusted {
+            Trusted::StateRoot(root) => root,
+            Trusted::Header(header) if header.hash() == self.at => &header.state_root,
+            Trusted::Header(_) => return Err(Error::BadProof),
+        };
+        verify(state_root, &self.proof, keys)
+    }
+}
+
+/// Source of the state root proofs are verified against when the node isn't trusted
+/// to report it, e.g. a header of a finalized block obtained from a light client
+#[derive(Clone, Debug)]
+pub enum Trusted {
+    StateRoot(Hash),
+    Header(Header),
+}
+
+/// Block header, its hash commits to the state root of the block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub parent_hash: Hash,
+    pub number: u32,
+    pub state_root: Hash,
+    pub extrinsics_root: Hash,
+    /// SCALE encoded digest items
+    pub digest: Vec<Vec<u8>>,
+}
+
+impl Header {
+    /// Blake2 hash of the SCALE encoded header
+    pub fn hash(&self) -> Hash {
+        let encoded = [
+            &self.parent_hash[..],
+            &Compact(self.number).encode(),
+            &self.state_root,
+            &self.extrinsics_root,
+            &Compact(self.digest.len() as u32).encode(),
+            &self.digest.concat(),
+        ]
+        .concat();
+        blake2_256(encoded)
+    }
+}
+
+/// Verifies a storage proof against the given state root returning the values of the keys
+pub fn verify(
+    state_root: &Hash,
+    proof: &[Vec<u8>],
+    keys: &[RawKey],
+) -> Result<Vec<(RawKey, Option<RawValue>)>> {
+    let db = proof
+        .iter()
+        .map(|node| (blake2_256(node), node.as_slice()))
+        .collect::<BTreeMap<_, _>>();
+
+    keys.iter()
+        .map(|key| Ok((key.clone(), lookup(&db, state_root, key)?)))
+        .collect()
+}
+
+type ProofDb<'a> = BTreeMap<Hash, &'a [u8]>;
+
+fn lookup(db: &ProofDb, root: &Hash, key: &[u8]) -> Result<Option<RawValue>> {
+    if *root == blake2_256([EMPTY_TRIE]) {
+        return Ok(None);
+    }
+    let key = Nibbles::new(key);
+    let mut node_data = *db.get(root).ok_or(Error::BadProof)?;
+    let mut consumed = 0;
+
+    loop {
+        match Node::decode(node_data)? {
+            Node::Empty => return Ok(None),
+            Node::Leaf(partial, value) => {
+                return if key.len - consumed == partial.len && key.has_at(consumed, &partial) {
+                    value.resolve(db).map(Some)
+                } else {
+                    Ok(None)
+                };
+            }
+            Node::Branch(partial, children, value) => {
+                if !key.has_at(consumed, &partial) {
+                    return Ok(None);
+                }
+                consumed += partial.len;
+                if consumed == key.len {
+                    return value.map(|v| v.resolve(db)).transpose();
+                }
+                let Some(child) = children[key.at(consumed) as usize] else {
+                    return Ok(None);
+                };
+                consumed += 1;
+                node_data = match child {
+                    NodeRef::Hash(hash) => db.get(hash).ok_or(Error::BadProof)?,
+                    NodeRef::Inline(data) => data,
+                };
+            }
+        }
+    }
+}
+
+const EMPTY_TRIE: u8 = 0;
+const LEAF_PREFIX_MASK: u8 = 0b01 << 6;
+const BRANCH_WITHOUT_MASK: u8 = 0b10 << 6;
+const BRANCH_WITH_MASK: u8 = 0b11 << 6;
+const ALT_HASHING_LEAF_PREFIX_MASK: u8 = 0b001 << 5;
+const ALT_HASHING_BRANCH_WITH_MASK: u8 = 0b0001 << 4;
+
+/// A key split in 4 bit parts as it is used to traverse the trie
+struct Nibbles<'a> {
+    data: &'a [u8],
+    offset: usize,
+    len: usize,
+}
+
+impl<'a> Nibbles<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Nibbles {
+            data,
+            offset: 0,
+            len: data.len() * 2,
+        }
+    }
+
+    fn at(&self, i: usize) -> u8 {
+        let i = self.offset + i;
+        let byte = self.data[i / 2];
+        if i & 1 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0f
+        }
+    }
+
+    /// Checks if the `other` nibbles are found starting at position `pos`
+    fn has_at(&self, pos: usize, other: &Nibbles) -> bool {
+        self.len - pos >= other.len && (0..other.len).all(|i| self.at(pos + i) == other.at(i))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NodeRef<'a> {
+    Hash(&'a [u8; 32]),
+    Inline(&'a [u8]),
+}
+
+enum NodeValue<'a> {
+    Inline(&'a [u8]),
+    Hashed(&'a [u8; 32]),
+}
+
+impl NodeValue<'_> {
+    fn resolve(self, db: &ProofDb) -> Result<RawValue> {
+        Ok(match self {
+            NodeValue::Inline(value) => value.to_vec(),
+            NodeValue::Hashed(hash) => db.get(hash).ok_or(Error::BadProof)?.to_vec(),
+        })
+    }
+}
+
+enum Node<'a> {
+    Empty,
+    Leaf(Nibbles<'a>, NodeValue<'a>),
+    Branch(
+        Nibbles<'a>,
+        Box<[Option<NodeRef<'a>>; 16]>,
+        Option<NodeValue<'a>>,
+    ),
+}
+
+impl<'a> Node<'a> {
+    fn decode(mut data: &'a [u8]) -> Result<Self> {
+        let input = &mut data;
+        let header = take(input, 1)?[0];
+        // (is branch, has value, value is hashed, prefix bits of the header)
+        let (is_branch, has_value, hashed, prefix_bits) = match header & (0b11 << 6) {
+            _ if header == EMPTY_TRIE => return Ok(Node::Empty),
+            LEAF_PREFIX_MASK => (false, true, false, 2),
+            BRANCH_WITH_MASK => (true, true, false, 2),
+            BRANCH_WITHOUT_MASK => (true, false, false, 2),
+            _ if header & (0b111 << 5) == ALT_HASHING_LEAF_PREFIX_MASK => (false, true, true, 3),
+            _ if header & (0b1111 << 4) == ALT_HASHING_BRANCH_WITH_MASK => (true, true, true, 4),
+            _ => return Err(Error::BadProof),
+        };
+
+        let nibble_count = decode_size(header, input, prefix_bits)?;
+        let padding = nibble_count % 2;
+        let partial = take(input, nibble_count / 2 + padding)?;
+        if padding == 1 && partial[0] & 0xf0 != 0 {
+            return Err(Error::BadProof);
+        }
+        let partial = Nibbles {
+            data: partial,
+            offset: padding,
+            len: nibble_count,
+        };
+
+        if !is_branch {
+            return Ok(Node::Leaf(partial, decode_value(input, hashed)?));
+        }
+
+        let bitmap = u16::from_le_bytes(take(input, 2)?.try_into().expect("2 bytes"));
+        let value = if has_value {
+            Some(decode_value(input, hashed)?)
+        } else {
+            None
+        };
+        let mut children = Box::new([None; 16]);
+        for (i, child) in children.iter_mut().enumerate() {
+            if bitmap & (1 << i) == 0 {
+                continue;
+            }
+            let len = decode_len(input)?;
+            let data = take(input, len)?;
+            *child = Some(match data.try_into() {
+                Ok(hash) => NodeRef::Hash(hash),
+                Err(_) => NodeRef::Inline(data),
+            });
+        }
+        Ok(Node::Branch(partial, children, value))
+    }
+}
+
+fn decode_value<'a>(input: &mut &'a [u8], hashed: bool) -> Result<NodeValue<'a>> {
+    Ok(if hashed {
+        NodeValue::Hashed(take(input, 32)?.try_into().expect("32 bytes"))
+    } else {
+        let len = decode_len(input)?;
+        NodeValue::Inline(take(input, len)?)
+    })
+}
+
+/// Number of nibbles of the partial key, small sizes fit in the header
+/// and bigger ones continue in the following bytes.
+fn decode_size(header: u8, input: &mut &[u8], prefix_bits: u8) -> Result<usize> {
+    let max_value = 255u8 >> prefix_bits;
+    let mut size = (header & max_value) as usize;
+    if size < max_value as usize {
+        return Ok(size);
+    }
+    size -= 1;
+    loop {
+        let n = take(input, 1)?[0] as usize;
+        if n < 255 {
+            return Ok(size + n + 1);
+        }
+        size += 255;
+    }
+}
+
+fn decode_len(input: &mut &[u8]) -> Result<usize> {
+    Compact::<u32>::decode(input)
+        .map(|len| len.0 as usize)
+        .map_err(|_| Error::BadProof)
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(Error::BadProof);
+    }
+    let (data, rest) = input.split_at(len);
+    *input = rest;
+    Ok(data)
+}
+
+/// A backend wrapper that checks every storage value it returns against
+/// a read proof, other queries are passed through without verification.
+/// Proofs are verified against the state root reported by the node unless
+/// a trusted one is given.
+pub struct Verified<B>(pub B, pub Option<Trusted>);
+
+impl<B: Backend> Backend for Verified<B> {
+    async fn get_storage_items(
+        &self,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> Result<impl Iterator<Item = (RawKey, RawValue)>> {
+        let block = match &self.1 {
+            // the proof has to be of the block of the header
+            Some(Trusted::Header(header)) => Some(header.number),
+            _ => block,
+        };
+        let proof = self.0.get_read_proof(keys.clone(), block).await?;
+        let values = match &self.1 {
+            Some(trusted) => proof.verify_trusted(trusted, &keys)?,
+            None => proof.verify(&keys)?,
+        };
+        Ok(values
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value?))))
+    }
+
+    async fn get_keys_paged(
+        &self,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        self.0.get_keys_paged(from, size, to, block).await
+    }
+
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> Result<RawValue> {
+        self.0.get_child_storage_item(child_key, key, block).await
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawKey,
+        keys: Vec<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<(RawKey, RawValue)>> {
+        self.0.get_child_storage_items(child_key, keys, block).await
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawKey,
+        from: RawKey,
+        size: u16,
+        to: Option<RawKey>,
+        block: Option<u32>,
+    ) -> Result<Vec<RawKey>> {
+        self.0
+            .get_child_keys_paged(child_key, from, size, to, block)
+            .await
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawKey,
+        key: RawKey,
+        block: Option<u32>,
+    ) -> Result<[u8; 32]> {
+        self.0.get_child_storage_hash(child_key, key, block).await
+    }
+
+    async fn get_read_proof(&self, keys: Vec<RawKey>, block: Option<u32>) -> Result<ReadProof> {
+        self.0.get_read_proof(keys, block).await
+    }
+
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> Result<[u8; 32]> {
+        self.0.submit(ext).await
+    }
+
+    async fn metadata(&self) -> Result<crate::Metadata> {
+        self.0.metadata().await
+    }
+
+    async fn block_info(&self, at: Option<u32>) -> Result<BlockInfo> {
+        self.0.block_info(at).await
+    }
+
+    async fn properties(&self) -> Result<ChainProperties> {
+        self.0.properties().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(header: u8, partial: &[u8], value: &[u8]) -> Vec<u8> {
+        [
+            &[header],
+            partial,
+            &Compact(value.len() as u32).encode(),
+            value,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn verify_single_leaf() {
+        let node = leaf(LEAF_PREFIX_MASK | 4, &[0x12, 0x34], b"hello");
+        let root = blake2_256(&node);
+
+        let values = verify(&root, &[node], &[vec![0x12, 0x34], vec![0x12, 0x35]]).unwrap();
+
+        assert_eq!(values[0].1, Some(b"hello".to_vec()));
+        assert_eq!(values[1].1, None);
+    }
+
+    #[test]
+    fn verify_branch_with_inline_children() {
+        let child_a = leaf(LEAF_PREFIX_MASK, &[], b"a");
+        let child_b = leaf(LEAF_PREFIX_MASK, &[], b"b");
+        let branch = [
+            vec![BRANCH_WITHOUT_MASK | 1, 0x01, 0b11, 0x00],
+            Compact(child_a.len() as u32).encode(),
+            child_a,
+            Compact(child_b.len() as u32).encode(),
+            child_b,
+        ]
+        .concat();
+        let root = blake2_256(&branch);
+
+        let values = verify(&root, &[branch], &[vec![0x10], vec![0x11], vec![0x12]]).unwrap();
+
+        assert_eq!(values[0].1, Some(b"a".to_vec()));
+        assert_eq!(values[1].1, Some(b"b".to_vec()));
+        assert_eq!(values[2].1, None);
+    }
+
+    #[test]
+    fn verify_hashed_value_leaf() {
+        let value = [7u8; 64];
+        let node = [
+            &[ALT_HASHING_LEAF_PREFIX_MASK | 4, 0xab, 0xcd][..],
+            &blake2_256(value),
+        ]
+        .concat();
+        let root = blake2_256(&node);
+
+        let values = verify(&root, &[node.clone(), value.to_vec()], &[vec![0xab, 0xcd]]).unwrap();
+        assert_eq!(values[0].1, Some(value.to_vec()));
+
+        let missing_value = verify(&root, &[node], &[vec![0xab, 0xcd]]);
+        assert!(matches!(missing_value, Err(Error::BadProof)));
+    }
+
+    // Polkadot's genesis block
+    fn header() -> Header {
+        let hash = |h: &str| hex::decode(h).unwrap().try_into().unwrap();
+        Header {
+            parent_hash: [0; 32],
+            number: 0,
+            state_root: hash("29d0d972cd27cbc511e9589fcb7a4506d5eb6a9e8df205f00472e5ab354a4e17"),
+            extrinsics_root: hash(
+                "03170a2e7597b7b7e3d84c05391d139a62b157e78786d8c082f29dcf4c111314",
+            ),
+            digest: vec![],
+        }
+    }
+
+    #[test]
+    fn header_hash() {
+        assert_eq!(
+            hex::encode(header().hash()),
+            "91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3"
+        );
+    }
+
+    #[test]
+    fn verify_against_trusted_root_or_header() {
+        let node = leaf(LEAF_PREFIX_MASK | 4, &[0x12, 0x34], b"hello");
+        let header = Header {
+            state_root: blake2_256(&node),
+            ..header()
+        };
+        let proof = ReadProof {
+            at: header.hash(),
+            // a node lying about the state root
+            state_root: [1; 32],
+            proof: vec![node],
+        };
+        let keys = [vec![0x12, 0x34]];
+        assert!(matches!(proof.verify(&keys), Err(Error::BadProof)));
+
+        let root = Trusted::StateRoot(header.state_root);
+        assert_eq!(
+            proof.verify_trusted(&root, &keys).unwrap()[0].1,
+            Some(b"hello".to_vec())
+        );
+        let values = proof.verify_trusted(&Trusted::Header(header.clone()), &keys);
+        assert_eq!(values.unwrap()[0].1, Some(b"hello".to_vec()));
+
+        let other_block = Header {
+            number: 1,
+            ..header
+        };
+        let res = proof.verify_trusted(&Trusted::Header(other_block), &keys);
+        assert!(matches!(res, Err(Error::BadProof)));
+    }
+
+    #[test]
+    fn reject_proof_of_other_root() {
+        let node = leaf(LEAF_PREFIX_MASK | 4, &[0x12, 0x34], b"hello");
+        let res = verify(&[1; 32], &[node], &[vec![0x12, 0x34]]);
+        assert!(matches!(res, Err(Error::BadProof)));
+    }
+}
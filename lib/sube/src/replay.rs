@@ -0,0 +1,264 @@
+//! Recording of the requests sent to a node and their offline replay.
+//!
+//! A [`Recorder`] wraps a transport and saves every request with its
+//! response into a JSON fixture file, a [`Replay`] answers the same
+//! requests from the fixture without network access.
+use core::fmt;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use jsonrpc::{
+    error::RpcError,
+    serde_json::{self, value::RawValue, Value as JsonValue},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prelude::*,
+    rpc::{self, Rpc, RpcResult},
+    Error,
+};
+
+/// A request and the response the node gave to it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub params: Vec<JsonValue>,
+    #[serde(flatten)]
+    pub response: Outcome,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Result(Box<RawValue>),
+    Error(RpcError),
+}
+
+impl Exchange {
+    fn response<T>(&self) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        match &self.response {
+            Outcome::Result(res) => Ok(serde_json::from_str(res.get())?),
+            Outcome::Error(err) => Err(rpc::error::Error::Rpc(err.clone())),
+        }
+    }
+}
+
+fn parse_params(params: &[&str]) -> Vec<JsonValue> {
+    params
+        .iter()
+        .map(|p| serde_json::from_str(p).expect("params are valid JSON"))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The request doesn't match any of the recorded ones
+    Unexpected {
+        method: String,
+        params: Vec<JsonValue>,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unexpected { method, params } => {
+                write!(f, "unexpected request `{}` {:?}", method, params)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<ReplayError> for rpc::error::Error {
+    fn from(err: ReplayError) -> Self {
+        rpc::error::Error::Transport(Box::new(err))
+    }
+}
+
+/// Saves the requests of the wrapped transport and their responses to a
+/// fixture, the file is rewritten after every request
+pub struct Recorder<R> {
+    inner: R,
+    path: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl<R: Rpc> Recorder<R> {
+    pub fn new(inner: R, path: impl Into<PathBuf>) -> Self {
+        Recorder {
+            inner,
+            path: path.into(),
+            exchanges: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().expect("not poisoned").clone()
+    }
+
+    fn record(&self, exchange: Exchange) {
+        let mut exchanges = self.exchanges.lock().expect("not poisoned");
+        exchanges.push(exchange);
+        let fixture =
+            serde_json::to_string_pretty(&*exchanges).expect("exchanges are serializable");
+        if let Err(err) = std::fs::write(&self.path, fixture) {
+            log::error!("Can't save fixture {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+impl<R: Rpc> Rpc for Recorder<R> {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let response = match self.inner.rpc::<Box<RawValue>>(method, params).await {
+            Ok(res) => Outcome::Result(res),
+            Err(rpc::error::Error::Rpc(err)) => Outcome::Error(err),
+            // only the responses of the node are recorded
+            Err(err) => return Err(err),
+        };
+        let exchange = Exchange {
+            method: method.into(),
+            params: parse_params(params),
+            response,
+        };
+        let res = exchange.response();
+        self.record(exchange);
+        res
+    }
+}
+
+/// How requests are matched with the recorded ones
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Matching {
+    /// Requests are sent in the recorded order with the same params
+    Strict,
+    /// Requests can come in any order, a request without a recording with the
+    /// same params gets the response of another request of the same method
+    #[default]
+    Lenient,
+}
+
+/// Answers requests with the responses of a recording
+pub struct Replay {
+    exchanges: Vec<Exchange>,
+    matching: Matching,
+    /// Exchanges already replayed
+    used: Mutex<Vec<bool>>,
+}
+
+impl Replay {
+    pub fn new(exchanges: Vec<Exchange>, matching: Matching) -> Self {
+        Replay {
+            used: Mutex::new(vec![false; exchanges.len()]),
+            exchanges,
+            matching,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, matching: Matching) -> Result<Self, Error> {
+        let path = path.as_ref();
+        log::trace!("Replaying fixture {}", path.display());
+        let fixture =
+            std::fs::read_to_string(path).map_err(|err| Error::Platform(err.to_string()))?;
+        let exchanges =
+            serde_json::from_str(&fixture).map_err(|err| Error::Platform(err.to_string()))?;
+        Ok(Self::new(exchanges, matching))
+    }
+
+    fn find(&self, method: &str, params: &[JsonValue]) -> Option<&Exchange> {
+        let mut used = self.used.lock().expect("not poisoned");
+        let same = |e: &Exchange| e.method == method && e.params == params;
+        let pos = match self.matching {
+            Matching::Strict => {
+                let next = used.iter().position(|used| !used)?;
+                same(&self.exchanges[next]).then_some(next)
+            }
+            Matching::Lenient => {
+                let unused = |i: &usize| !used[*i];
+                let all = 0..self.exchanges.len();
+                all.clone()
+                    .filter(unused)
+                    .find(|&i| same(&self.exchanges[i]))
+                    .or_else(|| all.clone().rev().find(|&i| same(&self.exchanges[i])))
+                    .or_else(|| {
+                        all.clone()
+                            .filter(unused)
+                            .find(|&i| self.exchanges[i].method == method)
+                    })
+                    .or_else(|| all.rev().find(|&i| self.exchanges[i].method == method))
+            }
+        }?;
+        used[pos] = true;
+        Some(&self.exchanges[pos])
+    }
+}
+
+impl Rpc for Replay {
+    async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        log::info!("RPC `{}` replayed", method);
+        let params = parse_params(params);
+        self.find(method, &params)
+            .ok_or_else(|| ReplayError::Unexpected {
+                method: method.into(),
+                params,
+            })?
+            .response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(method: &str, params: &[&str], result: &str) -> Exchange {
+        Exchange {
+            method: method.into(),
+            params: parse_params(params),
+            response: Outcome::Result(RawValue::from_string(result.into()).unwrap()),
+        }
+    }
+
+    #[async_std::test]
+    async fn replay_matching() {
+        let exchanges = vec![
+            exchange("chain_getBlockHash", &["0"], r#""0x00""#),
+            exchange("chain_getBlockHash", &["1"], r#""0x01""#),
+        ];
+
+        let strict = Replay::new(exchanges.clone(), Matching::Strict);
+        assert!(strict
+            .rpc::<String>("chain_getBlockHash", &["1"])
+            .await
+            .is_err());
+        assert_eq!(
+            strict
+                .rpc::<String>("chain_getBlockHash", &["0"])
+                .await
+                .unwrap(),
+            "0x00"
+        );
+
+        let lenient = Replay::new(exchanges, Matching::Lenient);
+        let block = |n| lenient.rpc::<String>("chain_getBlockHash", n);
+        assert_eq!(block(&["1"]).await.unwrap(), "0x01");
+        assert_eq!(block(&["1"]).await.unwrap(), "0x01");
+        assert_eq!(block(&["2"]).await.unwrap(), "0x00");
+        assert!(lenient
+            .rpc::<String>("state_getStorage", &[])
+            .await
+            .is_err());
+    }
+}
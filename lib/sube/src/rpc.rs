@@ -1,12 +1,19 @@
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+use alloc::{collections::BTreeMap, sync::Arc};
 use core::convert::TryInto;
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+use futures_channel::{mpsc, oneshot};
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+use futures_util::StreamExt as _;
 use jsonrpc::serde_json::value::RawValue;
 pub use jsonrpc::{error, Request, Response};
 use serde::Deserialize;
 
 use crate::meta::{self, Metadata};
-use crate::Backend;
+use crate::proof::{Hash, Header, ReadProof};
 use crate::Error;
 use crate::{prelude::*, RawKey as RawStorageKey, StorageChangeSet};
+use crate::{util, Backend, ChainProperties};
 use meta::from_bytes;
 
 pub type RpcResult<T> = Result<T, error::Error>;
@@ -17,6 +24,16 @@ pub trait Rpc {
     where
         T: for<'de> Deserialize<'de>;
 
+    /// Sends several requests at once, failing if any of them fails.
+    /// Backends that don't support batches send them one after the other
+    async fn rpc_batch(&self, requests: &[(&str, &[&str])]) -> RpcResult<Vec<Box<RawValue>>> {
+        let mut results = Vec::with_capacity(requests.len());
+        for (method, params) in requests {
+            results.push(self.rpc(method, params).await?);
+        }
+        Ok(results)
+    }
+
     fn convert_params(params: &[&str]) -> Vec<Box<RawValue>> {
         params
             .iter()
@@ -27,32 +44,252 @@ pub trait Rpc {
     }
 }
 
+/// Result of a response as raw JSON, `null` when it has no result
+pub fn raw_result(res: Response) -> RpcResult<Box<RawValue>> {
+    if let Some(err) = res.error {
+        return Err(error::Error::Rpc(err));
+    }
+    Ok(res
+        .result
+        .unwrap_or_else(|| RawValue::from_string("null".into()).expect("valid json")))
+}
+
+/// Id given by the node to a subscription, it changes when the backend
+/// restores the subscription after reconnecting
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+#[derive(Clone, Default)]
+pub(crate) struct SubscriptionId(Arc<std::sync::Mutex<String>>);
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl SubscriptionId {
+    pub fn get(&self) -> String {
+        self.0.lock().expect("not poisoned").clone()
+    }
+
+    fn set(&self, id: String) {
+        *self.0.lock().expect("not poisoned") = id;
+    }
+}
+
+/// Notifications sent by the node for an active subscription
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+pub struct Subscription {
+    id: SubscriptionId,
+    notifications: mpsc::UnboundedReceiver<serde_json::Value>,
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl Subscription {
+    /// Current id of the subscription to use in calls that refer to it
+    pub fn id(&self) -> String {
+        self.id.get()
+    }
+
+    pub(crate) fn shared_id(&self) -> SubscriptionId {
+        self.id.clone()
+    }
+
+    /// Waits for the next notification, `None` once the backend stops routing them
+    pub async fn next(&mut self) -> Option<serde_json::Value> {
+        self.notifications.next().await
+    }
+
+    /// Takes a notification already received without waiting for more
+    pub fn try_next(&mut self) -> Option<serde_json::Value> {
+        self.notifications.try_recv().ok()
+    }
+}
+
+/// Rpc backends with a persistent connection that can receive the
+/// notifications of a subscription
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+pub trait RpcSubscribe: Rpc {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription>;
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+type Notifier = (mpsc::UnboundedSender<serde_json::Value>, SubscriptionId);
+
+/// Keeps track of the requests and subscriptions waiting for messages of the node
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+#[derive(Default)]
+pub(crate) struct Router {
+    requests: BTreeMap<u32, oneshot::Sender<Response>>,
+    subscribing: BTreeMap<u32, Notifier>,
+    subscriptions: BTreeMap<String, Notifier>,
+}
+
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+impl Router {
+    /// Registers a request waiting for the response with the given id
+    pub fn request(&mut self, id: u32) -> oneshot::Receiver<Response> {
+        let (tx, rx) = oneshot::channel();
+        self.requests.insert(id, tx);
+        rx
+    }
+
+    /// Registers a subscription request, its notifications are routed to
+    /// the returned subscription once the node answers with its id
+    pub fn subscription(&mut self, id: u32) -> (oneshot::Receiver<Response>, Subscription) {
+        let (tx, rx) = mpsc::unbounded();
+        let sub = Subscription {
+            id: SubscriptionId::default(),
+            notifications: rx,
+        };
+        self.subscribing.insert(id, (tx, sub.shared_id()));
+        (self.request(id), sub)
+    }
+
+    /// Routes the notifications of an existing subscription to the answer of
+    /// a new subscription request, returns `false` if it was dropped already
+    #[cfg(any(feature = "ws", feature = "ws-tokio"))]
+    pub fn resubscribe(&mut self, sub: &SubscriptionId, id: u32) -> bool {
+        match self.subscriptions.remove(&sub.get()) {
+            Some(notifier) if !notifier.0.is_closed() => {
+                self.subscribing.insert(id, notifier);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forgets about a request that won't be answered
+    pub fn cancel(&mut self, id: u32) {
+        self.requests.remove(&id);
+        self.subscribing.remove(&id);
+    }
+
+    /// Dispatches a message of the node to whoever is waiting for it,
+    /// returns the id of the request it answers if it's a response
+    pub fn route(&mut self, msg: &str) -> Option<u32> {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(msg) else {
+            log::warn!("Ignoring malformed message {}", msg);
+            return None;
+        };
+
+        if let Some(id) = msg.get("id").and_then(serde_json::Value::as_u64) {
+            let id = id as u32;
+            let res: Response = serde_json::from_value(msg).unwrap_or_else(|_| {
+                error::result_to_response(
+                    Err(error::standard_error(
+                        error::StandardError::ParseError,
+                        None,
+                    )),
+                    id.into(),
+                )
+            });
+            if let Some((notifications, sub_id)) = self.subscribing.remove(&id) {
+                // registered before answering so no notification is missed
+                if let Ok(sub) = res.result::<serde_json::Value>() {
+                    let key = subscription_key(&sub);
+                    sub_id.set(key.clone());
+                    self.subscriptions.insert(key, (notifications, sub_id));
+                }
+            }
+            if let Some(channel) = self.requests.remove(&id) {
+                log::debug!("Answered request id: {}", id);
+                if let Err(res) = channel.send(res) {
+                    log::warn!("response error: {:?}", res);
+                }
+            }
+            return Some(id);
+        }
+
+        let Some(params) = msg.get("params") else {
+            log::warn!("Ignoring unknown message {}", msg);
+            return None;
+        };
+        let (Some(sub), Some(result)) = (params.get("subscription"), params.get("result")) else {
+            log::warn!("Ignoring unknown message {}", msg);
+            return None;
+        };
+        let key = subscription_key(sub);
+        if let Some((notifications, _)) = self.subscriptions.get(&key) {
+            if notifications.unbounded_send(result.clone()).is_err() {
+                log::debug!("Subscription {} was dropped", key);
+                self.subscriptions.remove(&key);
+            }
+        }
+        None
+    }
+}
+
+/// Subscription ids can be strings or numbers
+#[cfg(any(feature = "ws", feature = "ws-tokio", feature = "light"))]
+fn subscription_key(id: &serde_json::Value) -> String {
+    match id {
+        serde_json::Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
 pub struct RpcClient<R>(pub R);
 
+/// JSON string param with the hex encoded bytes
+fn hex_param(bytes: impl AsRef<[u8]>) -> String {
+    format!("\"0x{}\"", hex::encode(bytes))
+}
+
+/// Bytes of a hex string the node answered with
+fn node_hex(value: &str) -> crate::Result<Vec<u8>> {
+    util::decode_hex(value)
+        .ok_or_else(|| Error::Decode("the node answered with invalid hex".into()))
+}
+
+impl<R: Rpc> RpcClient<R> {
+    /// Hash of the given block to use as the `at` param of state queries
+    async fn block_hash_param(&self, block: Option<u32>) -> crate::Result<Option<String>> {
+        let Some(block_number) = block else {
+            return Ok(None);
+        };
+        let info = self
+            .block_info(Some(block_number))
+            .await
+            .map_err(|_| Error::BadBlockNumber)?;
+        Ok(Some(hex_param(info.hash)))
+    }
+
+    /// Name of the chain, e.g. `Kusama`
+    pub async fn system_chain(&self) -> crate::Result<String> {
+        self.rpc_with_params("system_chain", &[]).await
+    }
+
+    /// Version of the node implementation
+    pub async fn system_version(&self) -> crate::Result<String> {
+        self.rpc_with_params("system_version", &[]).await
+    }
+
+    /// Properties of the chain like its `tokenSymbol`, `tokenDecimals` and `ss58Format`
+    pub async fn system_properties(&self) -> crate::Result<serde_json::Value> {
+        self.rpc_with_params("system_properties", &[]).await
+    }
+
+    async fn rpc_with_params<T>(&self, method: &str, params: &[String]) -> crate::Result<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.0
+            .rpc(
+                method,
+                params
+                    .iter()
+                    .map(|s| s.as_ref())
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )
+            .await
+            .map_err(|e| crate::Error::Node(e.to_string()))
+    }
+}
+
 impl<R: Rpc> Backend for RpcClient<R> {
     async fn get_storage_items(
         &self,
         keys: Vec<RawStorageKey>,
         block: Option<u32>,
     ) -> crate::Result<impl Iterator<Item = (Vec<u8>, Vec<u8>)>> {
-        let keys = serde_json::to_string(
-            &keys
-                .iter()
-                .map(|v| format!("0x{}", hex::encode(v)))
-                .collect::<Vec<String>>(),
-        )
-        .expect("it to be a valid json");
-
-        let params: Vec<String> = if let Some(block_number) = block {
-            let info = self
-                .block_info(Some(block_number))
-                .await
-                .map_err(|_| Error::BadBlockNumber)?;
-
-            vec![keys, format!("\"0x{}\"", hex::encode(info.hash))]
-        } else {
-            vec![keys]
-        };
+        let mut params = vec![storage_keys_param(&keys)];
+        params.extend(self.block_hash_param(block).await?);
 
         let result = self
             .0
@@ -67,26 +304,50 @@ impl<R: Rpc> Backend for RpcClient<R> {
             .await
             .map_err(|err| {
                 log::error!("error state_queryStorageAt {:?}", err);
-                crate::Error::StorageKeyNotFound
+                crate::Error::Node(err.to_string())
             })?;
 
-        let result = match result.into_iter().next() {
-            None => vec![],
-            Some(change_set) => change_set
-                .changes
-                .into_iter()
-                .map(|[k, v]| {
-                    log::info!("key: {} value: {}", k, v);
-
-                    (
-                        hex::decode(&k[2..]).expect("to be an hex"),
-                        hex::decode(&v[2..]).expect("to be an hex"),
-                    )
-                })
-                .collect(),
+        Ok(storage_changes(result).into_iter())
+    }
+
+    async fn get_storage_items_with_block_info(
+        &self,
+        keys: Vec<RawStorageKey>,
+        block: Option<u32>,
+        info_at: Option<u32>,
+    ) -> crate::Result<(Vec<(RawStorageKey, Vec<u8>)>, meta::BlockInfo)> {
+        // the storage query depends on the hash of the block
+        if block.is_some() {
+            let items = self.get_storage_items(keys, block).await?.collect();
+            return Ok((items, self.block_info(info_at).await?));
+        }
+
+        let keys = storage_keys_param(&keys);
+        let info_at = info_at.map(|n| n.to_string());
+        let info_params = info_at.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+        let results = self
+            .0
+            .rpc_batch(&[
+                ("state_queryStorageAt", &[keys.as_str()]),
+                ("chain_getBlockHash", &info_params),
+            ])
+            .await
+            .map_err(|e| crate::Error::Node(e.to_string()))?;
+
+        let (changes, hash) = match results.as_slice() {
+            [changes, hash] => (
+                serde_json::from_str::<Vec<StorageChangeSet>>(changes.get()),
+                serde_json::from_str::<String>(hash.get()),
+            ),
+            _ => return Err(crate::Error::Node("wrong batch response".into())),
         };
+        let changes = changes.map_err(|e| crate::Error::Node(e.to_string()))?;
+        let hash = hash.map_err(|e| crate::Error::Node(e.to_string()))?;
 
-        Ok(result.into_iter())
+        Ok((
+            storage_changes(changes),
+            block_info_from_hash(info_at.as_deref(), &hash),
+        ))
     }
 
     async fn get_keys_paged(
@@ -94,18 +355,24 @@ impl<R: Rpc> Backend for RpcClient<R> {
         from: RawStorageKey,
         size: u16,
         to: Option<RawStorageKey>,
+        block: Option<u32>,
     ) -> crate::Result<Vec<RawStorageKey>> {
+        let mut params = vec![
+            hex_param(&from),
+            size.to_string(),
+            hex_param(to.unwrap_or(from)),
+        ];
+        params.extend(self.block_hash_param(block).await?);
+
         let result: Vec<String> = self
             .0
             .rpc(
                 "state_getKeysPaged",
-                &[
-                    &format!("\"0x{}\"", hex::encode(&from)),
-                    &size.to_string(),
-                    &to.or(Some(from))
-                        .map(|f| format!("\"0x{}\"", hex::encode(f)))
-                        .unwrap(),
-                ],
+                params
+                    .iter()
+                    .map(|s| s.as_ref())
+                    .collect::<Vec<_>>()
+                    .as_slice(),
             )
             .await
             .map_err(|err| {
@@ -119,16 +386,129 @@ impl<R: Rpc> Backend for RpcClient<R> {
             .collect())
     }
 
-    async fn submit(&self, ext: impl AsRef<[u8]>) -> crate::Result<()> {
+    async fn get_child_storage_item(
+        &self,
+        child_key: RawStorageKey,
+        key: RawStorageKey,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<u8>> {
+        let mut params = vec![hex_param(child_key), hex_param(key)];
+        params.extend(self.block_hash_param(block).await?);
+
+        let value: Option<String> = self
+            .rpc_with_params("childstate_getStorage", &params)
+            .await?;
+        node_hex(&value.ok_or(Error::StorageKeyNotFound)?)
+    }
+
+    async fn get_child_storage_items(
+        &self,
+        child_key: RawStorageKey,
+        keys: Vec<RawStorageKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<(RawStorageKey, Vec<u8>)>> {
+        let mut params = vec![hex_param(child_key), storage_keys_param(&keys)];
+        params.extend(self.block_hash_param(block).await?);
+
+        let values: Vec<Option<String>> = self
+            .rpc_with_params("childstate_getStorageEntries", &params)
+            .await?;
+        keys.into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .map(|(key, value)| Ok((key, node_hex(&value)?)))
+            .collect()
+    }
+
+    async fn get_child_keys_paged(
+        &self,
+        child_key: RawStorageKey,
+        from: RawStorageKey,
+        size: u16,
+        to: Option<RawStorageKey>,
+        block: Option<u32>,
+    ) -> crate::Result<Vec<RawStorageKey>> {
+        let mut params = vec![
+            hex_param(child_key),
+            hex_param(from),
+            size.to_string(),
+            to.map(hex_param).unwrap_or_else(|| "null".into()),
+        ];
+        params.extend(self.block_hash_param(block).await?);
+
+        let result: Vec<String> = self
+            .rpc_with_params("childstate_getKeysPaged", &params)
+            .await?;
+        result.iter().map(|k| node_hex(k)).collect()
+    }
+
+    async fn get_child_storage_hash(
+        &self,
+        child_key: RawStorageKey,
+        key: RawStorageKey,
+        block: Option<u32>,
+    ) -> crate::Result<[u8; 32]> {
+        let mut params = vec![hex_param(child_key), hex_param(key)];
+        params.extend(self.block_hash_param(block).await?);
+
+        let hash: Option<String> = self
+            .rpc_with_params("childstate_getStorageHash", &params)
+            .await?;
+        node_hex(&hash.ok_or(Error::StorageKeyNotFound)?)?
+            .try_into()
+            .map_err(|_| Error::Decode("the hash is not 32 bytes long".into()))
+    }
+
+    async fn get_read_proof(
+        &self,
+        keys: Vec<RawStorageKey>,
+        block: Option<u32>,
+    ) -> crate::Result<ReadProof> {
+        #[derive(Deserialize)]
+        struct Proof {
+            at: String,
+            proof: Vec<String>,
+        }
+
+        let at = hex_param(self.block_info(block).await?.hash);
+        let params = [storage_keys_param(&keys), at.clone()];
+        let proof: Proof = self.rpc_with_params("state_getReadProof", &params).await?;
+        let header: RpcHeader = self.rpc_with_params("chain_getHeader", &[at]).await?;
+        let header = header.decode().ok_or(Error::BadProof)?;
+
+        // the state root is only as good as the header it comes from
+        let at = util::decode_hex(&proof.at)
+            .and_then(|h| Hash::try_from(h).ok())
+            .ok_or(Error::BadProof)?;
+        if header.hash() != at {
+            return Err(Error::BadProof);
+        }
+        Ok(ReadProof {
+            at,
+            state_root: header.state_root,
+            proof: proof
+                .proof
+                .iter()
+                .map(|node| util::decode_hex(node))
+                .collect::<Option<_>>()
+                .ok_or(Error::BadProof)?,
+        })
+    }
+
+    async fn submit(&self, ext: impl AsRef<[u8]>) -> crate::Result<[u8; 32]> {
         let extrinsic = format!("0x{}", hex::encode(ext.as_ref()));
         log::debug!("Extrinsic: {}", extrinsic);
 
-        self.0
-            .rpc::<serde_json::Value>("author_submitExtrinsic", &[&format!("\"{}\"", &extrinsic)])
+        let hash = self
+            .0
+            .rpc::<String>("author_submitExtrinsic", &[&format!("\"{}\"", &extrinsic)])
             .await
             .map_err(|e| crate::Error::Node(e.to_string()))?;
 
-        Ok(())
+        hex::decode(hash.trim_start_matches("0x"))
+            .ok()
+            .and_then(|h| h.try_into().ok())
+            .ok_or_else(|| crate::Error::Node(format!("invalid extrinsic hash {}", hash)))
     }
 
     async fn metadata(&self) -> crate::Result<Metadata> {
@@ -145,31 +525,129 @@ impl<R: Rpc> Backend for RpcClient<R> {
     }
 
     async fn block_info(&self, at: Option<u32>) -> crate::Result<meta::BlockInfo> {
-        #[inline]
-        async fn block_info(s: &impl Rpc, params: &[&str]) -> crate::Result<Vec<u8>> {
-            let f = s
-                .rpc::<String>("chain_getBlockHash", params)
-                .await
-                .map_err(|e| crate::Error::Node(e.to_string()));
-
-            Ok(hex::decode(&f?.as_str()[2..]).expect("to be an valid hex"))
-        }
+        let at = at.map(|n| n.to_string());
+        let params = at.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+        let hash = self
+            .0
+            .rpc::<String>("chain_getBlockHash", &params)
+            .await
+            .map_err(|e| crate::Error::Node(e.to_string()))?;
 
-        let block_hash = if let Some(block_number) = at {
-            let block_number = block_number.to_string();
-            block_info(&self.0, &[&block_number]).await?
-        } else {
-            block_info(&self.0, &[]).await?
+        Ok(block_info_from_hash(at.as_deref(), &hash))
+    }
+
+    async fn properties(&self) -> crate::Result<ChainProperties> {
+        let results = self
+            .0
+            .rpc_batch(&[
+                ("system_chain", &[]),
+                ("system_version", &[]),
+                ("system_properties", &[]),
+            ])
+            .await
+            .map_err(|e| crate::Error::Node(e.to_string()))?;
+        let [chain, version, properties] = results.as_slice() else {
+            return Err(crate::Error::Node("wrong batch response".into()));
         };
+        let node_err = |e: serde_json::Error| crate::Error::Node(e.to_string());
+        let text = |v: &RawValue| serde_json::from_str::<String>(v.get()).map_err(node_err);
+        let properties = serde_json::from_str(properties.get()).map_err(node_err)?;
+        Ok(ChainProperties::new(
+            text(chain)?,
+            text(version)?,
+            &properties,
+        ))
+    }
+}
+
+/// Header as returned by `chain_getHeader`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcHeader {
+    parent_hash: String,
+    number: String,
+    state_root: String,
+    extrinsics_root: String,
+    digest: RpcDigest,
+}
+
+#[derive(Deserialize)]
+struct RpcDigest {
+    logs: Vec<String>,
+}
 
-        Ok(meta::BlockInfo {
-            number: at.unwrap_or(0) as u64,
-            hash: block_hash[0..32]
-                .try_into()
-                .expect("Block hash is not 32 bytes"),
-            parent: block_hash[0..32]
-                .try_into()
-                .expect("Block hash is not 32 bytes"),
+impl RpcHeader {
+    fn decode(&self) -> Option<Header> {
+        let hash = |h: &str| util::decode_hex(h)?.try_into().ok();
+        Some(Header {
+            parent_hash: hash(&self.parent_hash)?,
+            number: u32::from_str_radix(self.number.strip_prefix("0x")?, 16).ok()?,
+            state_root: hash(&self.state_root)?,
+            extrinsics_root: hash(&self.extrinsics_root)?,
+            digest: self
+                .digest
+                .logs
+                .iter()
+                .map(|log| util::decode_hex(log))
+                .collect::<Option<_>>()?,
         })
     }
 }
+
+/// JSON array with the hex encoded storage keys
+fn storage_keys_param(keys: &[RawStorageKey]) -> String {
+    serde_json::to_string(
+        &keys
+            .iter()
+            .map(|v| format!("0x{}", hex::encode(v)))
+            .collect::<Vec<String>>(),
+    )
+    .expect("it to be a valid json")
+}
+
+fn storage_changes(result: Vec<StorageChangeSet>) -> Vec<(RawStorageKey, Vec<u8>)> {
+    match result.into_iter().next() {
+        None => vec![],
+        Some(change_set) => change_set
+            .changes
+            .into_iter()
+            // keys without a value are not present in the storage
+            .filter_map(|(k, v)| Some((k, v?)))
+            .map(|(k, v)| {
+                log::info!("key: {} value: {}", k, v);
+
+                (
+                    hex::decode(&k[2..]).expect("to be an hex"),
+                    hex::decode(&v[2..]).expect("to be an hex"),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn block_info_from_hash(at: Option<&str>, hash: &str) -> meta::BlockInfo {
+    let block_hash = hex::decode(&hash[2..]).expect("to be an valid hex");
+
+    meta::BlockInfo {
+        number: at.and_then(|n| n.parse().ok()).unwrap_or(0),
+        hash: block_hash[0..32]
+            .try_into()
+            .expect("Block hash is not 32 bytes"),
+        parent: block_hash[0..32]
+            .try_into()
+            .expect("Block hash is not 32 bytes"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_hex_is_a_decode_error() {
+        assert_eq!(node_hex("0x0102").unwrap(), [1, 2]);
+        for bad in ["", "0", "0xzz", "0102"] {
+            assert!(matches!(node_hex(bad), Err(Error::Decode(_))), "{}", bad);
+        }
+    }
+}
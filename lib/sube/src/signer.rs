@@ -1,9 +1,38 @@
-use crate::Result;
+use crate::prelude::*;
+use crate::{Error, Metadata, Result};
 use core::{future::Future, marker::PhantomData};
+use scale_info::{form::PortableForm, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde::{Deserialize, Serialize};
 
 pub type Bytes<const N: usize> = [u8; N];
 
-/// Signed extrinsics need to be signed by a `Signer` before submission  
+/// Signature scheme of the key signing extrinsics, it tells the variant of a
+/// `MultiSignature` to use and the kind of account that signs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    #[default]
+    Sr25519,
+    Ed25519,
+    Ecdsa,
+    /// ECDSA signatures of Ethereum-style 20 bytes accounts, e.g. in Moonbeam
+    Ethereum,
+}
+
+impl Scheme {
+    /// Variant and index of the scheme in a `MultiSignature`, Ethereum signatures
+    /// are only taken by chains with a plain signature type
+    fn multi_signature_variant(&self) -> Option<(&'static str, u8)> {
+        match self {
+            Scheme::Ed25519 => Some(("Ed25519", 0x00)),
+            Scheme::Sr25519 => Some(("Sr25519", 0x01)),
+            Scheme::Ecdsa => Some(("Ecdsa", 0x02)),
+            Scheme::Ethereum => None,
+        }
+    }
+}
+
+/// Signed extrinsics need to be signed by a `Signer` before submission
 pub trait Signer {
     type Account: AsRef<[u8]>;
     type Signature: AsRef<[u8]>;
@@ -11,29 +40,47 @@ pub trait Signer {
     fn sign(&self, data: impl AsRef<[u8]>) -> impl Future<Output = Result<Self::Signature>>;
 
     fn account(&self) -> Self::Account;
+
+    /// Scheme of the signatures, sr25519 unless the signer says otherwise
+    fn scheme(&self) -> Scheme {
+        Scheme::Sr25519
+    }
 }
 
 /// Wrapper to create a standard signer from an account and closure
 pub struct SignerFn<S, SF> {
-    account: Bytes<32>,
+    account: Vec<u8>,
+    scheme: Scheme,
     signer: S,
     _fut: PhantomData<SF>,
 }
 
-impl<S, SF> Signer for SignerFn<S, SF>
+impl<S, SF> SignerFn<S, SF> {
+    /// Sets the scheme of the signatures the closure makes, sr25519 by default
+    pub fn with_scheme(self, scheme: Scheme) -> Self {
+        SignerFn { scheme, ..self }
+    }
+}
+
+impl<S, SF, Sig> Signer for SignerFn<S, SF>
 where
     S: Fn(&[u8]) -> SF,
-    SF: Future<Output = Result<Bytes<64>>>,
+    SF: Future<Output = Result<Sig>>,
+    Sig: AsRef<[u8]>,
 {
-    type Account = Bytes<32>;
-    type Signature = Bytes<64>;
+    type Account = Vec<u8>;
+    type Signature = Sig;
 
     fn sign(&self, data: impl AsRef<[u8]>) -> impl Future<Output = Result<Self::Signature>> {
         (self.signer)(data.as_ref())
     }
 
     fn account(&self) -> Self::Account {
-        self.account
+        self.account.clone()
+    }
+
+    fn scheme(&self) -> Scheme {
+        self.scheme
     }
 }
 
@@ -44,9 +91,158 @@ where
 {
     fn from((account, signer): (A, S)) -> Self {
         SignerFn {
-            account: account.as_ref().try_into().expect("32bit account"),
+            account: account.as_ref().to_vec(),
+            scheme: Scheme::default(),
             signer,
             _fut: PhantomData,
         }
     }
 }
+
+/// Encodes the signer of an extrinsic as the `Address` type of the runtime's extrinsic,
+/// a variant of a `MultiAddress` that fits the account(`Id`, `Address20`, ..) or the
+/// plain account id, e.g. the `AccountId20` of Ethereum compatible chains
+pub(crate) fn encode_address(meta: &Metadata, account: &[u8]) -> Result<Vec<u8>> {
+    let Some(ty) = extrinsic_param(meta, "Address") else {
+        // assumed `MultiAddress::Id`
+        return Ok([&[0x00], account].concat());
+    };
+    encode_bytes(&meta.types, ty, account, &|_| true).ok_or(Error::BadAddress)
+}
+
+/// Encodes a signature as the `Signature` type of the runtime's extrinsic, the variant
+/// of the scheme of a `MultiSignature` or the plain signature, e.g. an `EthereumSignature`
+pub(crate) fn encode_signature(meta: &Metadata, scheme: Scheme, sig: &[u8]) -> Result<Vec<u8>> {
+    let variant = scheme.multi_signature_variant();
+    let unsupported = || {
+        Error::Encode(format!(
+            "{:?} signatures of {} bytes aren't supported by the chain",
+            scheme,
+            sig.len()
+        ))
+    };
+    let Some(ty) = extrinsic_param(meta, "Signature") else {
+        // assumed `MultiSignature`
+        let (_, index) = variant.ok_or_else(unsupported)?;
+        return Ok([&[index], sig].concat());
+    };
+    encode_bytes(&meta.types, ty, sig, &|name| {
+        variant.is_some_and(|(variant, _)| name == variant)
+    })
+    .ok_or_else(unsupported)
+}
+
+/// Type of a type parameter of the extrinsic, missing in metadata with simplified types
+fn extrinsic_param(meta: &Metadata, name: &str) -> Option<u32> {
+    let ty = meta.types.resolve(meta.extrinsic.ty.id)?;
+    let param = ty.type_params.iter().find(|p| p.name == name)?;
+    param.ty.map(|ty| ty.id)
+}
+
+/// Encodes raw bytes as a type that is either a fixed size array of bytes(or a newtype
+/// of one) of the same length or an enum with a variant wrapping such type
+fn encode_bytes(
+    registry: &PortableRegistry,
+    ty: u32,
+    bytes: &[u8],
+    variant: &dyn Fn(&str) -> bool,
+) -> Option<Vec<u8>> {
+    let TypeDef::Variant(v) = &registry.resolve(ty)?.type_def else {
+        return (fixed_len(registry, ty)? == bytes.len()).then(|| bytes.to_vec());
+    };
+    v.variants
+        .iter()
+        .filter(|v| variant(&v.name))
+        .find_map(|v| match v.fields.as_slice() {
+            [f] => encode_bytes(registry, f.ty.id, bytes, &|_| false)
+                .map(|encoded| [vec![v.index], encoded].concat()),
+            _ => None,
+        })
+}
+
+fn fixed_len(registry: &PortableRegistry, ty: u32) -> Option<usize> {
+    match &registry.resolve(ty)?.type_def {
+        TypeDef::Array(a) => {
+            let byte = &registry.resolve(a.type_param.id)?.type_def;
+            matches!(
+                byte,
+                TypeDef::<PortableForm>::Primitive(TypeDefPrimitive::U8)
+            )
+            .then_some(a.len as usize)
+        }
+        TypeDef::Composite(c) => match c.fields.as_slice() {
+            [f] => fixed_len(registry, f.ty.id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{metadata, metadata_signed_with, metadata_with_extrinsic};
+    use scale_info::{meta_type, Registry};
+
+    fn types<T: scale_info::TypeInfo + 'static>() -> (u32, PortableRegistry) {
+        let mut reg = Registry::new();
+        let id = reg.register_type(&meta_type::<T>()).id;
+        (id, reg.into())
+    }
+
+    #[test]
+    fn encodes_bytes_as_the_variant_that_fits() {
+        // like a `MultiAddress` with `Id` and `Address20` variants
+        let (id, reg) = types::<core::result::Result<[u8; 32], [u8; 20]>>();
+        let encoded = encode_bytes(&reg, id, &[7; 20], &|_| true).unwrap();
+        assert_eq!(encoded, [&[1][..], &[7; 20]].concat());
+        let encoded = encode_bytes(&reg, id, &[7; 32], &|_| true).unwrap();
+        assert_eq!(encoded, [&[0][..], &[7; 32]].concat());
+        assert_eq!(
+            encode_bytes(&reg, id, &[7; 32], &|name| name == "Err"),
+            None
+        );
+        assert_eq!(encode_bytes(&reg, id, &[7; 33], &|_| true), None);
+    }
+
+    #[test]
+    fn encodes_plain_bytes() {
+        let (id, reg) = types::<[u8; 65]>();
+        assert_eq!(
+            encode_bytes(&reg, id, &[1; 65], &|_| true),
+            Some(vec![1; 65])
+        );
+        assert_eq!(encode_bytes(&reg, id, &[1; 64], &|_| true), None);
+    }
+
+    #[test]
+    fn multi_signatures_are_assumed_without_type_params() {
+        let meta = metadata_with_extrinsic::<Vec<u8>>();
+        assert_eq!(
+            encode_address(&meta, &[1; 32]).unwrap(),
+            [&[0x00][..], &[1; 32]].concat()
+        );
+        assert_eq!(
+            encode_signature(&meta, Scheme::Ecdsa, &[7; 65]).unwrap(),
+            [&[0x02][..], &[7; 65]].concat()
+        );
+        assert!(matches!(
+            encode_signature(&meta, Scheme::Ethereum, &[7; 65]),
+            Err(Error::Encode(_))
+        ));
+    }
+
+    #[test]
+    fn ethereum_signatures_need_a_plain_signature_type() {
+        assert!(matches!(
+            encode_signature(&metadata(), Scheme::Ethereum, &[7; 65]),
+            Err(Error::Encode(_))
+        ));
+        let meta = metadata_signed_with::<[u8; 20], [u8; 65]>();
+        assert_eq!(
+            encode_signature(&meta, Scheme::Ethereum, &[7; 65]).unwrap(),
+            vec![7; 65]
+        );
+        assert_eq!(encode_address(&meta, &[1; 20]).unwrap(), vec![1; 20]);
+    }
+}
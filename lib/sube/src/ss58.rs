@@ -0,0 +1,131 @@
+//! SS58 addresses, the base58 format Substrate chains use to display account ids.
+//!
+//! Account ids(`AccountId32` and the `Id` of a `MultiAddress`) can be given as
+//! SS58 or `0x` hex strings in storage paths and call arguments, the registry
+//! types tell where an account id is expected.
+use crate::human::{self, Kind};
+use crate::prelude::*;
+use crate::{meta::Meta as _, Error, JsonValue, Metadata, Result, Value};
+use blake2::{Blake2b512, Digest};
+use scale_info::PortableRegistry;
+
+/// Prefix of the generic Substrate network, used when a chain doesn't define its own
+pub const DEFAULT_PREFIX: u16 = 42;
+const CHECKSUM_LEN: usize = 2;
+
+/// Encodes an account id as an SS58 address of the network with the given prefix
+pub fn encode(prefix: u16, account: &[u8; 32]) -> String {
+    let mut data = match prefix {
+        0..=63 => vec![prefix as u8],
+        _ => {
+            let ident = prefix & 0b0011_1111_1111_1111;
+            vec![
+                ((ident & 0b1111_1100) >> 2) as u8 | 0b0100_0000,
+                (ident >> 8) as u8 | ((ident & 0b11) << 6) as u8,
+            ]
+        }
+    };
+    data.extend(account);
+    let checksum = checksum(&data);
+    data.extend(&checksum[..CHECKSUM_LEN]);
+    bs58::encode(data).into_string()
+}
+
+/// Decodes an SS58 address into its network prefix and account id
+pub fn decode(address: &str) -> Result<(u16, [u8; 32])> {
+    let data = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| Error::BadAddress)?;
+    let (prefix, prefix_len) = match data.as_slice() {
+        [b @ 0..=63, ..] => (*b as u16, 1),
+        [b0 @ 64..=127, b1, ..] => {
+            let lower = (b0 << 2) | (b1 >> 6);
+            let upper = b1 & 0b0011_1111;
+            (lower as u16 | (upper as u16) << 8, 2)
+        }
+        _ => return Err(Error::BadAddress),
+    };
+    if data.len() != prefix_len + 32 + CHECKSUM_LEN {
+        return Err(Error::BadAddress);
+    }
+    let (body, check) = data.split_at(prefix_len + 32);
+    if checksum(body)[..CHECKSUM_LEN] != *check {
+        return Err(Error::BadAddress);
+    }
+    let account = body[prefix_len..].try_into().expect("32 bytes account");
+    Ok((prefix, account))
+}
+
+fn checksum(data: &[u8]) -> [u8; 64] {
+    Blake2b512::new()
+        .chain_update(b"SS58PRE")
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+/// The `SS58Prefix` constant of the `System` pallet
+pub fn prefix(meta: &Metadata) -> u16 {
+    meta.pallet_by_name("System")
+        .and_then(|p| p.constants.iter().find(|c| c.name == "SS58Prefix"))
+        .and_then(|c| {
+            let value: JsonValue = Value::new(c.value.clone(), c.ty.id, &meta.types).into();
+            value.as_u64()
+        })
+        .map_or(DEFAULT_PREFIX, |p| p as u16)
+}
+
+/// Account id of an SS58 or `0x` hex encoded address
+pub fn account(address: &str) -> Result<[u8; 32]> {
+    match address.strip_prefix("0x") {
+        Some(hex) => hex::decode(hex)
+            .ok()
+            .and_then(|a| a.try_into().ok())
+            .ok_or(Error::BadAddress),
+        None => decode(address).map(|(_, account)| account),
+    }
+}
+
+/// Map keys of account id types given as SS58 are converted to hex,
+/// the format used to encode keys
+pub(crate) fn hex_key(registry: &PortableRegistry, ty: u32, key: &str) -> Option<String> {
+    if key.starts_with("0x") || !matches!(human::kind(registry, ty), Some(Kind::Account)) {
+        return None;
+    }
+    let (_, account) = decode(key).ok()?;
+    Some(format!("0x{}", hex::encode(account)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const ALICE_ID: &str = "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+    #[test]
+    fn decodes_generic_substrate_address() {
+        let (prefix, account) = decode(ALICE).unwrap();
+        assert_eq!(prefix, DEFAULT_PREFIX);
+        assert_eq!(hex::encode(account), ALICE_ID);
+    }
+
+    #[test]
+    fn roundtrips_one_and_two_byte_prefixes() {
+        let account = <[u8; 32]>::try_from(hex::decode(ALICE_ID).unwrap()).unwrap();
+        assert_eq!(encode(42, &account), ALICE);
+        for prefix in [0, 2, 63, 64, 2207, 16383] {
+            assert_eq!(
+                decode(&encode(prefix, &account)).unwrap(),
+                (prefix, account)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut address = ALICE.to_string();
+        address.replace_range(10..11, "x");
+        assert!(decode(&address).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! TypeScript declarations(`.d.ts`) of the storage values, call arguments and
+//! events of a runtime.
+//!
+//! Storage values and events are typed as scales serializes decoded values and
+//! call arguments as its serializer takes them, e.g. a `u128` comes out as a
+//! number but can be given as a numeric string. Types with a path in the registry
+//! are declared once, named after the last segment of their path or the whole path
+//! when that is ambiguous, generic types with several instances are inlined.
+//! Pallets are namespaces with their `Storage`, `Calls` and `Events`.
+use crate::meta::{EntryTy as _, PalletMeta};
+use crate::prelude::*;
+use crate::Metadata;
+use alloc::collections::{BTreeMap, BTreeSet};
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+
+type TypeId = u32;
+
+const PRELUDE: &str = "\
+// Generated by sube from the runtime metadata
+
+/** `Vec<u8>` and compact numbers(SCALE encoded) come out as bytes */
+export type Bytes = Uint8Array | number[];
+/** 128 bit numbers are a `bigint` in sube-js and a number in JSON */
+export type BigNum = bigint | number;
+";
+
+/// TypeScript declarations of the storage, calls and events of every pallet
+pub fn declarations(meta: &Metadata) -> String {
+    let mut types = Declarations::new(&meta.types);
+    let pallets = meta
+        .pallets
+        .iter()
+        .map(|p| types.pallet(p))
+        .collect::<Vec<_>>();
+    let mut out = String::from(PRELUDE);
+    for decl in types.decls.values() {
+        out.push('\n');
+        out.push_str(decl);
+    }
+    for pallet in pallets {
+        out.push('\n');
+        out.push_str(&pallet);
+    }
+    out
+}
+
+/// Decoded values and the input of the serializer differ for some types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Shape {
+    Output,
+    Input,
+}
+
+struct Declarations<'r> {
+    registry: &'r PortableRegistry,
+    names: BTreeMap<TypeId, String>,
+    decls: BTreeMap<String, String>,
+    visiting: Vec<(TypeId, Shape)>,
+    differs: BTreeSet<TypeId>,
+}
+
+impl<'r> Declarations<'r> {
+    fn new(registry: &'r PortableRegistry) -> Self {
+        let path = |id| {
+            let path = registry.resolve(id)?.path.segments.as_slice();
+            let last = path.last()?.as_str();
+            (!matches!(last, "Option" | "BTreeMap")).then_some(path)
+        };
+        let mut instances = BTreeMap::<&[String], usize>::new();
+        let mut last_segments = BTreeMap::from([("Bytes", 1), ("BigNum", 1)]);
+        for ty in registry.types.iter() {
+            if let Some(path) = path(ty.id) {
+                *instances.entry(path).or_default() += 1;
+            }
+        }
+        for path in instances.iter().filter(|(_, n)| **n == 1).map(|(p, _)| p) {
+            *last_segments
+                .entry(path.last().expect("path").as_str())
+                .or_default() += 1;
+        }
+        let names = registry
+            .types
+            .iter()
+            .filter_map(|ty| {
+                let path = path(ty.id).filter(|p| instances[*p] == 1)?;
+                let last = path.last().expect("path");
+                Some(match last_segments[last.as_str()] {
+                    1 => (ty.id, last.clone()),
+                    _ => (ty.id, pascal_case(path)),
+                })
+            })
+            .collect();
+        Declarations {
+            registry,
+            names,
+            decls: BTreeMap::new(),
+            visiting: vec![],
+            differs: input_differs(registry),
+        }
+    }
+
+    fn pallet(&mut self, pallet: &PalletMeta) -> String {
+        let mut sections = vec![];
+        let storage = pallet
+            .storage
+            .iter()
+            .flat_map(|s| s.entries.iter())
+            .map(|e| {
+                let ty = self.ty(e.ty.value_ty(), Shape::Output);
+                format!("{}    export type {} = {};\n", doc(&e.docs, 4), e.name, ty)
+            })
+            .collect::<String>();
+        sections.push(("Storage", storage));
+
+        let calls = self
+            .variants(pallet.calls.as_ref().map(|c| c.ty.id))
+            .iter()
+            .map(|v| {
+                let args = match v.fields.as_slice() {
+                    [] => "Record<string, never>".into(),
+                    fields => self.fields(fields, Shape::Input, false),
+                };
+                let name = pascal_case(&[&v.name]);
+                format!("{}    export type {} = {};\n", doc(&v.docs, 4), name, args)
+            })
+            .collect::<String>();
+        sections.push(("Calls", calls));
+
+        let events = self
+            .variants(pallet.event.as_ref().map(|e| e.ty.id))
+            .iter()
+            .map(|v| {
+                let event = self.variant(v, Shape::Output);
+                format!(
+                    "{}    export type {} = {};\n",
+                    doc(&v.docs, 4),
+                    v.name,
+                    event
+                )
+            })
+            .collect::<String>();
+        sections.push(("Events", events));
+
+        let mut out = format!("export namespace {} {{\n", pallet.name);
+        for (section, decls) in sections.into_iter().filter(|(_, d)| !d.is_empty()) {
+            out.push_str(&format!(
+                "  export namespace {} {{\n{}  }}\n",
+                section, decls
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn variants(&self, ty: Option<TypeId>) -> Vec<scale_info::Variant<PortableForm>> {
+        match ty
+            .and_then(|ty| self.registry.resolve(ty))
+            .map(|t| &t.type_def)
+        {
+            Some(TypeDef::Variant(v)) => v.variants.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Type expression of a registry type, named types are declared the first time they are used
+    fn ty(&mut self, id: TypeId, shape: Shape) -> String {
+        if let Some(name) = self.name(id, shape) {
+            if !self.decls.contains_key(&name) && !self.visiting.contains(&(id, shape)) {
+                self.visiting.push((id, shape));
+                let body = self.body(id, shape, true);
+                self.visiting.pop();
+                self.declare(id, &name, body);
+            }
+            return name;
+        }
+        if self.visiting.contains(&(id, shape)) {
+            // an inlined type that contains itself has to be named
+            let ty = self.registry.resolve(id).expect("in registry");
+            let last = ty.path.segments.last().map_or("Type", |s| s.as_str());
+            self.names.insert(id, format!("{}{}", last, id));
+            return self.name(id, shape).expect("named");
+        }
+        self.visiting.push((id, shape));
+        let body = self.body(id, shape, false);
+        self.visiting.pop();
+        match self.name(id, shape) {
+            Some(name) => {
+                self.declare(id, &name, body);
+                name
+            }
+            None => body,
+        }
+    }
+
+    fn name(&self, id: TypeId, shape: Shape) -> Option<String> {
+        let name = self.names.get(&id)?.clone();
+        Some(if shape == Shape::Input && self.differs.contains(&id) {
+            name + "Input"
+        } else {
+            name
+        })
+    }
+
+    fn declare(&mut self, id: TypeId, name: &str, body: String) {
+        let docs = self
+            .registry
+            .resolve(id)
+            .map_or("".into(), |t| doc(&t.docs, 0));
+        // unions are declared one variant per line
+        let eq = if body.starts_with('\n') { "=" } else { "= " };
+        let decl = format!("{}export type {} {}{};\n", docs, name, eq, body);
+        self.decls.insert(name.into(), decl);
+    }
+
+    fn body(&mut self, id: TypeId, shape: Shape, top: bool) -> String {
+        let input = shape == Shape::Input;
+        let Some(ty) = self.registry.resolve(id) else {
+            return "unknown".into();
+        };
+        let name = ty.path.segments.last().map(String::as_str);
+        match &ty.type_def {
+            TypeDef::Composite(c) if name == Some("BTreeMap") => {
+                let value = map_value(self.registry, c.fields[0].ty.id);
+                let value = value.map_or("unknown".into(), |v| self.ty(v, shape));
+                format!("Record<string, {}>", value)
+            }
+            TypeDef::Composite(c) => self.fields(&c.fields, shape, top),
+            TypeDef::Variant(v) if name == Some("Option") => {
+                let some = v.variants.iter().find(|v| v.name == "Some");
+                let some = some.and_then(|v| v.fields.first()).map(|f| f.ty.id);
+                let some = some.map_or("unknown".into(), |s| self.ty(s, shape));
+                format!("{} | null", some)
+            }
+            TypeDef::Variant(v) => {
+                let variants = v
+                    .variants
+                    .iter()
+                    .map(|v| self.variant(v, shape))
+                    .collect::<Vec<_>>();
+                match (top, variants.is_empty()) {
+                    (_, true) => "never".into(),
+                    (true, _) => format!("\n  | {}", variants.join("\n  | ")),
+                    (false, _) => variants.join(" | "),
+                }
+            }
+            TypeDef::Sequence(s) if is_u8(self.registry, s.type_param.id) => {
+                if input { "string | number[]" } else { "Bytes" }.into()
+            }
+            TypeDef::Sequence(s) => format!("Array<{}>", self.ty(s.type_param.id, shape)),
+            TypeDef::Array(a) if is_u8(self.registry, a.type_param.id) => if input {
+                "string | number[]"
+            } else {
+                "number[]"
+            }
+            .into(),
+            TypeDef::Array(a) => format!("Array<{}>", self.ty(a.type_param.id, shape)),
+            TypeDef::Tuple(t) if t.fields.is_empty() => if input { "null" } else { "[]" }.into(),
+            TypeDef::Tuple(t) => {
+                let items = t.fields.iter().map(|f| self.ty(f.id, shape));
+                format!("[{}]", items.collect::<Vec<_>>().join(", "))
+            }
+            TypeDef::Primitive(p) => match p {
+                TypeDefPrimitive::Bool => "boolean",
+                TypeDefPrimitive::Str | TypeDefPrimitive::Char => "string",
+                TypeDefPrimitive::U8
+                | TypeDefPrimitive::U16
+                | TypeDefPrimitive::U32
+                | TypeDefPrimitive::I8
+                | TypeDefPrimitive::I16
+                | TypeDefPrimitive::I32 => "number",
+                TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => "unknown",
+                _ if input => "number | string",
+                TypeDefPrimitive::U64 | TypeDefPrimitive::I64 => "number",
+                TypeDefPrimitive::U128 | TypeDefPrimitive::I128 => "BigNum",
+            }
+            .into(),
+            TypeDef::Compact(_) => if input { "number | string" } else { "Bytes" }.into(),
+            TypeDef::BitSequence(_) => "unknown".into(),
+        }
+    }
+
+    /// Unit variants are their name, the others an object with the variant name as key
+    fn variant(&mut self, v: &scale_info::Variant<PortableForm>, shape: Shape) -> String {
+        if v.fields.is_empty() {
+            return format!("\"{}\"", v.name);
+        }
+        format!("{{ {}: {} }}", v.name, self.fields(&v.fields, shape, false))
+    }
+
+    fn fields(&mut self, fields: &[Field<PortableForm>], shape: Shape, top: bool) -> String {
+        match fields {
+            [] => "null".into(),
+            // newtypes are transparent
+            [f] if f.name.is_none() => self.ty(f.ty.id, shape),
+            [f, ..] if f.name.is_none() => {
+                let items = fields.iter().map(|f| self.ty(f.ty.id, shape));
+                format!("[{}]", items.collect::<Vec<_>>().join(", "))
+            }
+            _ if top => {
+                let props = fields.iter().map(|f| {
+                    let name = f.name.as_deref().unwrap_or_default();
+                    format!(
+                        "{}  {}: {};\n",
+                        doc(&f.docs, 2),
+                        name,
+                        self.ty(f.ty.id, shape)
+                    )
+                });
+                format!("{{\n{}}}", props.collect::<String>())
+            }
+            _ => {
+                let props = fields.iter().map(|f| {
+                    let name = f.name.as_deref().unwrap_or_default();
+                    format!("{}: {}", name, self.ty(f.ty.id, shape))
+                });
+                format!("{{ {} }}", props.collect::<Vec<_>>().join("; "))
+            }
+        }
+    }
+}
+
+/// Types the serializer takes in a different shape than the one they are decoded to,
+/// propagated from the types that differ to the ones containing them
+fn input_differs(registry: &PortableRegistry) -> BTreeSet<TypeId> {
+    let mut differs = BTreeSet::new();
+    loop {
+        let found = registry
+            .types
+            .iter()
+            .filter(|ty| !differs.contains(&ty.id))
+            .filter(|ty| {
+                let (own, contained) = differs_and_contains(registry, &ty.ty.type_def);
+                own || contained.iter().any(|id| differs.contains(id))
+            })
+            .map(|ty| ty.id)
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return differs;
+        }
+        differs.extend(found);
+    }
+}
+
+/// Whether a type differs by itself and the types it contains
+fn differs_and_contains(
+    registry: &PortableRegistry,
+    def: &TypeDef<PortableForm>,
+) -> (bool, Vec<TypeId>) {
+    let fields = |fields: &[Field<PortableForm>]| fields.iter().map(|f| f.ty.id).collect();
+    match def {
+        TypeDef::Composite(c) => (false, fields(&c.fields)),
+        TypeDef::Variant(v) => (
+            false,
+            v.variants.iter().flat_map(|v| fields(&v.fields)).collect(),
+        ),
+        TypeDef::Sequence(s) => (is_u8(registry, s.type_param.id), vec![s.type_param.id]),
+        TypeDef::Array(a) => (is_u8(registry, a.type_param.id), vec![a.type_param.id]),
+        TypeDef::Tuple(t) => (t.fields.is_empty(), t.fields.iter().map(|f| f.id).collect()),
+        TypeDef::Primitive(p) => {
+            let big = matches!(
+                p,
+                TypeDefPrimitive::U64
+                    | TypeDefPrimitive::U128
+                    | TypeDefPrimitive::I64
+                    | TypeDefPrimitive::I128
+            );
+            (big, vec![])
+        }
+        TypeDef::Compact(_) => (true, vec![]),
+        TypeDef::BitSequence(_) => (false, vec![]),
+    }
+}
+
+fn is_u8(registry: &PortableRegistry, id: TypeId) -> bool {
+    matches!(
+        registry.resolve(id).map(|t| &t.type_def),
+        Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+    )
+}
+
+/// Maps are a sequence of key-value tuples
+fn map_value(registry: &PortableRegistry, seq: TypeId) -> Option<TypeId> {
+    let TypeDef::Sequence(s) = &registry.resolve(seq)?.type_def else {
+        return None;
+    };
+    match &registry.resolve(s.type_param.id)?.type_def {
+        TypeDef::Tuple(t) => t.fields.last().map(|v| v.id),
+        _ => None,
+    }
+}
+
+/// `pallet_balances::types::AccountData` is `PalletBalancesTypesAccountData`
+fn pascal_case(segments: &[impl AsRef<str>]) -> String {
+    segments
+        .iter()
+        .flat_map(|s| s.as_ref().split('_'))
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or("".into(), |first| {
+                first.to_uppercase().chain(chars).collect::<String>()
+            })
+        })
+        .collect()
+}
+
+fn doc(lines: &[String], indent: usize) -> String {
+    let lines = lines
+        .iter()
+        .map(|l| l.strip_prefix(' ').unwrap_or(l).replace("*/", "*\\/"))
+        .collect::<Vec<_>>();
+    let pad = " ".repeat(indent);
+    match lines.as_slice() {
+        [] => "".into(),
+        [line] => format!("{}/** {} */\n", pad, line),
+        lines => {
+            let lines = lines
+                .iter()
+                .map(|l| format!("{} * {}", pad, l).trim_end().to_string())
+                .collect::<Vec<_>>();
+            format!("{}/**\n{}\n{} */\n", pad, lines.join("\n"), pad)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scale_info::{meta_type, Registry};
+
+    fn types<T: scale_info::TypeInfo + 'static>() -> (TypeId, PortableRegistry) {
+        let mut reg = Registry::new();
+        let id = reg.register_type(&meta_type::<T>()).id;
+        (id, reg.into())
+    }
+
+    #[test]
+    fn decoded_and_input_shapes() {
+        type Ty = (Option<u32>, Vec<u8>, [u8; 4], u128, Vec<bool>);
+        let (id, reg) = types::<Ty>();
+        let mut decls = Declarations::new(&reg);
+        assert_eq!(
+            decls.ty(id, Shape::Output),
+            "[number | null, Bytes, number[], BigNum, Array<boolean>]"
+        );
+        assert_eq!(
+            decls.ty(id, Shape::Input),
+            "[number | null, string | number[], string | number[], number | string, Array<boolean>]"
+        );
+    }
+
+    #[test]
+    fn converts_names_to_pascal_case() {
+        assert_eq!(pascal_case(&["transfer_keep_alive"]), "TransferKeepAlive");
+        assert_eq!(
+            pascal_case(&["pallet_balances", "types", "AccountData"]),
+            "PalletBalancesTypesAccountData"
+        );
+    }
+}
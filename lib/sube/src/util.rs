@@ -1,4 +1,5 @@
-use alloc::string::String;
+use crate::prelude::*;
+use serde::{ser::SerializeSeq, Serializer};
 
 pub fn to_camel(term: &str) -> String {
     let underscore_count = term.chars().filter(|c| *c == '-').count();
@@ -17,3 +18,36 @@ pub fn to_camel(term: &str) -> String {
     }
     result
 }
+
+/// Compares names ignoring their case and underscores, e.g. `transfer_keep_alive` and `transferKeepAlive`
+pub(crate) fn same_name(a: &str, b: &str) -> bool {
+    fn normalized(s: &str) -> impl Iterator<Item = char> + '_ {
+        s.chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+    }
+    normalized(a).eq(normalized(b))
+}
+
+/// Decodes a `0x` prefixed hex string
+pub fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    hex::decode(input.strip_prefix("0x")?).ok()
+}
+
+pub fn serialize_hex<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
+pub fn serialize_hex_pairs<S: Serializer>(
+    pairs: &[(Vec<u8>, Vec<u8>)],
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    let mut seq = s.serialize_seq(Some(pairs.len()))?;
+    for (k, v) in pairs {
+        seq.serialize_element(&(
+            format!("0x{}", hex::encode(k)),
+            format!("0x{}", hex::encode(v)),
+        ))?;
+    }
+    seq.end()
+}
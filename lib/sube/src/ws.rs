@@ -1,172 +1,642 @@
 use alloc::{collections::BTreeMap, sync::Arc};
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
 
-use ewebsock::{WsEvent, WsMessage as Message, WsReceiver as Rx, WsSender as Tx};
-use futures_channel::{mpsc, oneshot};
-use futures_util::StreamExt as _;
-use no_std_async::Mutex;
-// use futures_util::StreamExt;
-use jsonrpc::{
-    error::{result_to_response, standard_error, StandardError},
-    serde_json,
+use futures_channel::oneshot;
+use futures_util::{
+    future::{select, Either},
+    Stream, StreamExt as _,
 };
+use jsonrpc::serde_json;
 use log::info;
+use no_std_async::Mutex;
 use serde::Deserialize;
 
-#[cfg(not(feature = "js"))]
-use async_std::task::spawn;
-#[cfg(feature = "js")]
-use async_std::task::spawn_local as spawn;
-
 use crate::{
-    rpc::{self, Rpc, RpcResult},
-    Error,
+    prelude::*,
+    rpc::{self, Router, Rpc, RpcResult, RpcSubscribe, Subscription, SubscriptionId},
+    BackendConfig, Error,
 };
 
-const MAX_BUFFER: usize = usize::MAX >> 3;
+/// Events of the socket common to the supported websocket implementations
+enum Event {
+    Opened,
+    Message(String),
+    Closed,
+}
+
+type Events = Pin<Box<dyn Stream<Item = Event> + Send>>;
+
+/// Async runtime the connection of a backend runs on, with both features
+/// enabled each backend can use either of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Runtime {
+    /// async-std tasks and the ewebsock socket of the `ws` feature
+    #[cfg(feature = "ws")]
+    AsyncStd,
+    /// tokio tasks and the tokio-tungstenite socket of the `ws-tokio` feature
+    #[cfg(feature = "ws-tokio")]
+    Tokio,
+}
+
+impl Default for Runtime {
+    /// Tokio when called from a tokio runtime, async-std otherwise
+    fn default() -> Self {
+        #[cfg(feature = "ws-tokio")]
+        {
+            #[cfg(feature = "ws")]
+            if tokio::runtime::Handle::try_current().is_err() {
+                return Runtime::AsyncStd;
+            }
+            Runtime::Tokio
+        }
+        #[cfg(not(feature = "ws-tokio"))]
+        Runtime::AsyncStd
+    }
+}
+
+impl Runtime {
+    async fn connect(self, url: &str, config: &BackendConfig) -> Result<(Socket, Events), String> {
+        match self {
+            #[cfg(feature = "ws")]
+            Runtime::AsyncStd => async_std_runtime::connect(url, config)
+                .await
+                .map(|(socket, events)| (Socket::AsyncStd(socket), events)),
+            #[cfg(feature = "ws-tokio")]
+            Runtime::Tokio => {
+                // tokio tasks can't be spawned from other runtimes
+                if tokio::runtime::Handle::try_current().is_err() {
+                    return Err("the ws backend needs a tokio runtime".into());
+                }
+                tokio_runtime::connect(url, config)
+                    .await
+                    .map(|(socket, events)| (Socket::Tokio(socket), events))
+            }
+        }
+    }
+
+    fn spawn(self, task: impl Future<Output = ()> + Send + 'static) {
+        match self {
+            #[cfg(feature = "ws")]
+            Runtime::AsyncStd => {
+                async_std_runtime::spawn(task);
+            }
+            #[cfg(feature = "ws-tokio")]
+            Runtime::Tokio => {
+                tokio_runtime::spawn(task);
+            }
+        }
+    }
+
+    async fn sleep(self, duration: Duration) {
+        match self {
+            #[cfg(feature = "ws")]
+            Runtime::AsyncStd => async_std_runtime::sleep(duration).await,
+            #[cfg(feature = "ws-tokio")]
+            Runtime::Tokio => tokio_runtime::sleep(duration).await,
+        }
+    }
+
+    /// The output of the future or `None` if it doesn't complete in time
+    async fn timeout<F: Future>(self, duration: Duration, future: F) -> Option<F::Output> {
+        match self {
+            #[cfg(feature = "ws")]
+            Runtime::AsyncStd => async_std_runtime::timeout(duration, future).await.ok(),
+            #[cfg(feature = "ws-tokio")]
+            Runtime::Tokio => tokio_runtime::timeout(duration, future).await.ok(),
+        }
+    }
+}
+
+/// Sending half of the socket of a runtime
+enum Socket {
+    #[cfg(feature = "ws")]
+    AsyncStd(async_std_runtime::Socket),
+    #[cfg(feature = "ws-tokio")]
+    Tokio(tokio_runtime::Socket),
+}
+
+impl Socket {
+    fn send(&mut self, msg: String) {
+        match self {
+            #[cfg(feature = "ws")]
+            Socket::AsyncStd(socket) => socket.send(msg),
+            #[cfg(feature = "ws-tokio")]
+            Socket::Tokio(socket) => socket.send(msg),
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+mod async_std_runtime {
+    use super::{Event, Events};
+    use crate::{prelude::*, BackendConfig};
+    use core::future::ready;
+    use ewebsock::{WsEvent, WsMessage, WsSender};
+    use futures_util::StreamExt as _;
+
+    #[cfg(not(feature = "js"))]
+    pub use async_std::task::spawn;
+    #[cfg(feature = "js")]
+    pub use async_std::task::spawn_local as spawn;
+    pub use async_std::{future::timeout, task::sleep};
+
+    pub struct Socket(WsSender);
+
+    impl Socket {
+        pub fn send(&mut self, msg: String) {
+            self.0.send(WsMessage::Text(msg));
+        }
+    }
+
+    pub async fn connect(url: &str, config: &BackendConfig) -> Result<(Socket, Events), String> {
+        if config.all_headers().next().is_some()
+            || config.proxy.is_some()
+            || !config.root_certs.is_empty()
+        {
+            return Err("headers, proxies and certificates need the ws-tokio feature".into());
+        }
+        let (tx, rx) = ewebsock::connect(url, ewebsock::Options::default())?;
+        let events = rx.filter_map(|event| {
+            ready(match event {
+                WsEvent::Opened => Some(Event::Opened),
+                WsEvent::Message(WsMessage::Text(msg)) => Some(Event::Message(msg)),
+                WsEvent::Message(msg) => {
+                    log::trace!("Ignoring WS message {:?}", msg);
+                    None
+                }
+                WsEvent::Error(e) => {
+                    log::warn!("WS error {}", &e);
+                    Some(Event::Closed)
+                }
+                WsEvent::Closed => Some(Event::Closed),
+            })
+        });
+        Ok((Socket(tx), Box::pin(events)))
+    }
+}
+
+#[cfg(feature = "ws-tokio")]
+mod tokio_runtime {
+    use super::{Event, Events};
+    use crate::{prelude::*, BackendConfig};
+    use alloc::sync::Arc;
+    use core::future::ready;
+    use futures_channel::mpsc;
+    use futures_util::{stream, SinkExt as _, StreamExt as _};
+    use rustls::{
+        crypto::ring,
+        pki_types::{pem::PemObject, CertificateDer},
+        ClientConfig, RootCertStore,
+    };
+    use tokio_tungstenite::{
+        connect_async_tls_with_config,
+        tungstenite::{
+            client::IntoClientRequest,
+            http::{HeaderName, HeaderValue},
+            Message,
+        },
+        Connector,
+    };
+
+    pub use tokio::{
+        spawn,
+        time::{sleep, timeout},
+    };
+
+    /// Messages are written to the socket by a separate task
+    pub struct Socket(mpsc::UnboundedSender<String>);
+
+    impl Socket {
+        pub fn send(&mut self, msg: String) {
+            if self.0.unbounded_send(msg).is_err() {
+                log::warn!("WS socket closed");
+            }
+        }
+    }
+
+    pub async fn connect(url: &str, config: &BackendConfig) -> Result<(Socket, Events), String> {
+        if config.proxy.is_some() {
+            return Err("proxies are not supported by the websocket backend".into());
+        }
+        let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+        for (name, value) in config.all_headers() {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+            let value = HeaderValue::from_str(&value).map_err(|e| e.to_string())?;
+            request.headers_mut().append(name, value);
+        }
+        let connector = Connector::Rustls(Arc::new(tls_config(&config.root_certs)?));
+
+        let (ws, _) = connect_async_tls_with_config(request, None, false, Some(connector))
+            .await
+            .map_err(|e| e.to_string())?;
+        let (mut sink, rx) = ws.split();
+
+        let (tx, mut outgoing) = mpsc::unbounded::<String>();
+        spawn(async move {
+            while let Some(msg) = outgoing.next().await {
+                if let Err(e) = sink.send(Message::text(msg)).await {
+                    log::warn!("WS error {}", &e);
+                    break;
+                }
+            }
+            // the socket was dropped, let the node know
+            let _ = sink.close().await;
+        });
+
+        // the handshake is done by the time the socket is returned
+        let events = stream::once(ready(Event::Opened)).chain(rx.filter_map(|msg| {
+            ready(match msg {
+                Ok(Message::Text(msg)) => Some(Event::Message(msg.as_str().into())),
+                Ok(Message::Close(_)) => Some(Event::Closed),
+                Ok(msg) => {
+                    log::trace!("Ignoring WS message {:?}", msg);
+                    None
+                }
+                Err(e) => {
+                    log::warn!("WS error {}", &e);
+                    Some(Event::Closed)
+                }
+            })
+        }));
+        Ok((Socket(tx), Box::pin(events)))
+    }
+
+    /// Webpki roots extended with the custom certificates
+    fn tls_config(root_certs: &[Vec<u8>]) -> Result<ClientConfig, String> {
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        for pem in root_certs {
+            for cert in CertificateDer::pem_slice_iter(pem) {
+                roots
+                    .add(cert.map_err(|e| e.to_string())?)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(
+            ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|e| e.to_string())?
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    }
+}
 
 type Id = u32;
 
+/// Timeouts and reconnection policy of the websocket backend
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Time to wait for the response of a request
+    pub timeout: Duration,
+    /// Reconnection attempts before giving up on the node
+    pub max_reconnects: u32,
+    /// Delay before the first reconnection attempt, it doubles with every failed attempt
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    pub runtime: Runtime,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            timeout: Duration::from_secs(30),
+            max_reconnects: 10,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            runtime: Runtime::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WsError {
+    /// The node didn't answer the request in time
+    Timeout(Id),
+    /// The connection was lost and couldn't be restored
+    Disconnected(String),
+}
+
+impl fmt::Display for WsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(id) => write!(f, "WS request {} timed out", id),
+            Self::Disconnected(reason) => write!(f, "WS disconnected: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for WsError {}
+
+impl From<WsError> for rpc::error::Error {
+    fn from(err: WsError) -> Self {
+        rpc::error::Error::Transport(Box::new(err))
+    }
+}
+
+/// Subscription to start again when the connection is restored
+struct ActiveSubscription {
+    method: String,
+    params: Vec<String>,
+    id: SubscriptionId,
+}
+
+struct State {
+    router: Router,
+    socket: Option<Socket>,
+    /// Requests not answered yet, they are sent again after reconnecting
+    in_flight: BTreeMap<Id, String>,
+    subscriptions: Vec<ActiveSubscription>,
+    failure: Option<String>,
+}
+
+impl State {
+    fn send(&mut self, id: Id, msg: String) -> RpcResult<()> {
+        if let Some(reason) = &self.failure {
+            return Err(WsError::Disconnected(reason.clone()).into());
+        }
+        log::debug!("RPC Request {} ...", &msg);
+        // without a socket the request waits for the connection to be restored
+        if let Some(socket) = &mut self.socket {
+            socket.send(msg.clone());
+        }
+        self.in_flight.insert(id, msg);
+        Ok(())
+    }
+
+    fn forget(&mut self, id: Id) {
+        self.router.cancel(id);
+        self.in_flight.remove(&id);
+    }
+
+    fn opened(&mut self, mut socket: Socket, next_id: &AtomicU32) {
+        // subscription ids from the previous connection are no longer valid
+        for sub in core::mem::take(&mut self.subscriptions) {
+            let id = next_id.fetch_add(1, Ordering::Relaxed);
+            if self.router.resubscribe(&sub.id, id) {
+                let params = sub.params.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+                self.in_flight.insert(id, request(id, &sub.method, &params));
+                self.subscriptions.push(sub);
+            }
+        }
+        for msg in self.in_flight.values() {
+            socket.send(msg.clone());
+        }
+        self.socket = Some(socket);
+    }
+
+    /// Gives up on the connection, pending requests and subscriptions are dropped
+    fn failed(&mut self, reason: String) {
+        log::error!("WS connection failed: {}", reason);
+        self.failure = Some(reason);
+        self.socket = None;
+        self.router = Router::default();
+        self.in_flight.clear();
+        self.subscriptions.clear();
+    }
+}
+
+async fn open(
+    runtime: Runtime,
+    url: &str,
+    config: &BackendConfig,
+) -> Result<(Socket, Events), String> {
+    match config.connect_timeout {
+        Some(t) => runtime
+            .timeout(t, runtime.connect(url, config))
+            .await
+            .unwrap_or_else(|| Err(format!("connection to {} timed out", url))),
+        None => runtime.connect(url, config).await,
+    }
+}
+
+fn request(id: Id, method: &str, params: &[&str]) -> String {
+    serde_json::to_string(&rpc::Request {
+        id: id.into(),
+        jsonrpc: Some("2.0"),
+        method,
+        params: &Backend::convert_params(params),
+    })
+    .expect("Request is serializable")
+}
+
 pub struct Backend {
-    tx: Mutex<mpsc::Sender<Message>>,
-    ws_sender: Arc<Mutex<Tx>>,
-    messages: Arc<Mutex<BTreeMap<Id, oneshot::Sender<rpc::Response>>>>,
+    next_id: Arc<AtomicU32>,
+    options: Options,
+    state: Arc<Mutex<State>>,
+    /// Dropped along with the backend to stop the task of the connection
+    _close: oneshot::Sender<()>,
 }
-unsafe impl Send for Backend {}
-unsafe impl Sync for Backend {}
 
 impl Rpc for Backend {
     async fn rpc<T>(&self, method: &str, params: &[&str]) -> RpcResult<T>
     where
         T: for<'a> Deserialize<'a>,
     {
-        let id = self.next_id().await;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
         info!("RPC `{}` (ID={})", method, id);
 
-        // Store a sender that will notify our receiver when a matching message arrives
-        let (sender, recv) = oneshot::channel::<rpc::Response>();
-        let messages = self.messages.clone();
-        messages.lock().await.insert(id, sender);
+        let recv = {
+            let mut state = self.state.lock().await;
+            let recv = state.router.request(id);
+            state.send(id, request(id, method, params))?;
+            recv
+        };
+
+        self.response(id, recv).await?.result()
+    }
+}
 
-        // send rpc request
-        let msg = serde_json::to_string(&rpc::Request {
-            id: id.into(),
-            jsonrpc: Some("2.0"),
-            method,
-            params: &Self::convert_params(params),
-        })
-        .expect("Request is serializable");
+impl RpcSubscribe for Backend {
+    async fn subscribe(&self, method: &str, params: &[&str]) -> RpcResult<Subscription> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        info!("RPC subscription `{}` (ID={})", method, id);
 
-        log::debug!("RPC Request {} ...", &msg);
+        let (recv, sub) = {
+            let mut state = self.state.lock().await;
+            let (recv, sub) = state.router.subscription(id);
+            state.send(id, request(id, method, params))?;
+            (recv, sub)
+        };
 
-        self.tx
+        // the subscription id is set by the router with the response
+        self.response(id, recv)
+            .await?
+            .result::<serde_json::Value>()?;
+
+        self.state
             .lock()
             .await
-            .try_send(Message::Text(msg))
-            .map_err(|err| {
-                log::error!("Error tx lock message: {:?}", err);
-                standard_error(StandardError::InternalError, None)
-            })?;
-
-        log::info!("sent CMD");
-        // wait for the matching response to arrive
-        let res = recv
-            .await
-            .map_err(|err| {
-                log::error!("Error receiving message: {:?}", err);
-                standard_error(StandardError::InternalError, None)
-            })?
-            .result()?;
-
-        Ok(res)
+            .subscriptions
+            .push(ActiveSubscription {
+                method: method.into(),
+                params: params.iter().map(|p| p.to_string()).collect(),
+                id: sub.shared_id(),
+            });
+        Ok(sub)
     }
 }
 
 impl Backend {
-    async fn next_id(&self) -> Id {
-        self.messages.lock().await.keys().last().unwrap_or(&0) + 1
+    pub async fn new_ws2<'a, U: Into<&'a str>>(url: U) -> core::result::Result<Self, Error> {
+        Self::with_options(url, Options::default()).await
     }
 
-    pub async fn new_ws2<'a, U: Into<&'a str>>(url: U) -> core::result::Result<Self, Error> {
-        let url = url.into();
-        log::trace!("WS connecting to {}", url);
+    pub async fn with_options<'a, U: Into<&'a str>>(
+        url: U,
+        options: Options,
+    ) -> core::result::Result<Self, Error> {
+        Self::connect(url.into(), options, BackendConfig::default()).await
+    }
 
-        let (tx, rx) =
-            ewebsock::connect(url, ewebsock::Options::default()).map_err(Error::Platform)?;
+    /// Backend sending custom headers or credentials with the handshake,
+    /// the request timeout of the config takes precedence over the options
+    pub async fn with_config<'a, U: Into<&'a str>>(
+        url: U,
+        options: Options,
+        config: BackendConfig,
+    ) -> core::result::Result<Self, Error> {
+        let options = Options {
+            timeout: config.timeout.unwrap_or(options.timeout),
+            ..options
+        };
+        Self::connect(url.into(), options, config).await
+    }
 
-        let (sender, recv) = mpsc::channel::<Message>(MAX_BUFFER);
+    async fn connect(
+        url: &str,
+        options: Options,
+        config: BackendConfig,
+    ) -> core::result::Result<Self, Error> {
+        log::trace!("WS connecting to {}", url);
 
+        let (close, closed) = oneshot::channel();
         let backend = Backend {
-            tx: Mutex::new(sender),
-            ws_sender: Arc::new(Mutex::new(tx)),
-            messages: Arc::new(Mutex::new(BTreeMap::new())),
+            next_id: Arc::new(AtomicU32::new(1)),
+            options,
+            state: Arc::new(Mutex::new(State {
+                router: Router::default(),
+                socket: None,
+                in_flight: BTreeMap::new(),
+                subscriptions: vec![],
+                failure: None,
+            })),
+            _close: close,
         };
 
-        let recv = Arc::new(Mutex::new(recv));
-
-        backend.process_incoming_messages(rx, backend.ws_sender.clone(), recv.clone());
+        // fail early on urls the socket can't connect to
+        let connection = open(backend.options.runtime, url, &config)
+            .await
+            .map_err(Error::Platform)?;
+        backend.process_connection(url.into(), config, connection, closed);
         Ok(backend)
     }
 
-    fn process_tx_send_messages(tx: Arc<Mutex<Tx>>, recv: Arc<Mutex<mpsc::Receiver<Message>>>) {
-        spawn(async move {
-            info!("waiting for coommands...");
-
-            while let Some(m) = recv.lock().await.next().await {
-                info!("got for coommands...?");
-                tx.lock().await.send(m);
+    async fn response(
+        &self,
+        id: Id,
+        recv: oneshot::Receiver<rpc::Response>,
+    ) -> RpcResult<rpc::Response> {
+        let runtime = self.options.runtime;
+        match runtime.timeout(self.options.timeout, recv).await {
+            Some(Ok(res)) => Ok(res),
+            // the sender is only dropped when the connection fails for good
+            Some(Err(_)) => {
+                let reason = self.state.lock().await.failure.clone();
+                Err(WsError::Disconnected(reason.unwrap_or_default()).into())
             }
-        });
+            None => {
+                log::warn!("Request {} timed out", id);
+                self.state.lock().await.forget(id);
+                Err(WsError::Timeout(id).into())
+            }
+        }
     }
 
-    fn process_incoming_messages(
+    /// Handles the messages of the socket, reconnecting with an exponential
+    /// backoff when the connection is lost. The task ends and the socket is
+    /// closed once the backend is dropped.
+    fn process_connection(
         &self,
-        mut rx: Rx,
-        tx: Arc<Mutex<Tx>>,
-        recv: Arc<Mutex<mpsc::Receiver<Message>>>,
+        url: String,
+        config: BackendConfig,
+        connection: (Socket, Events),
+        mut closed: oneshot::Receiver<()>,
     ) {
-        let messages = self.messages.clone();
-        spawn(async move {
-            while let Some(event) = rx.next().await {
-                match event {
-                    WsEvent::Message(msg) => {
-                        log::trace!("Got WS message {:?}", msg);
-
-                        if let Message::Text(msg) = msg {
-                            let res: rpc::Response =
-                                serde_json::from_str(&msg).unwrap_or_else(|_| {
-                                    result_to_response(
-                                        Err(standard_error(StandardError::ParseError, None)),
-                                        ().into(),
-                                    )
-                                });
-                            if res.id.is_u64() {
-                                let id = res.id.as_u64().unwrap() as Id;
-                                log::trace!("Answering request {}", id);
-                                let mut messages = messages.lock().await;
-                                if let Some(channel) = messages.remove(&id) {
-                                    log::debug!("Answered request id: {}", id);
-                                    if let Err(res) = channel.send(res) {
-                                        log::warn!("response error: {:?}", res);
+        let state = self.state.clone();
+        let next_id = self.next_id.clone();
+        let options = self.options.clone();
+        let runtime = options.runtime;
+
+        runtime.spawn(async move {
+            let mut connection = Some(connection);
+            let mut attempts = 0;
+            loop {
+                let socket = match connection.take() {
+                    Some(socket) => Ok(socket),
+                    None => open(runtime, &url, &config).await,
+                };
+                match socket {
+                    Ok((socket, mut events)) => {
+                        let mut socket = Some(socket);
+                        loop {
+                            let event = match select(events.next(), &mut closed).await {
+                                Either::Left((Some(event), _)) => event,
+                                Either::Left((None, _)) => break,
+                                // the backend is gone, the socket is closed
+                                // when the state and the events are dropped
+                                Either::Right(_) => return,
+                            };
+                            match event {
+                                Event::Opened => {
+                                    log::trace!("Ws connection opened");
+                                    attempts = 0;
+                                    if let Some(socket) = socket.take() {
+                                        state.lock().await.opened(socket, &next_id);
+                                    }
+                                }
+                                Event::Message(msg) => {
+                                    log::trace!("Got WS message {}", msg);
+                                    let mut state = state.lock().await;
+                                    if let Some(id) = state.router.route(&msg) {
+                                        state.in_flight.remove(&id);
                                     }
                                 }
+                                Event::Closed => break,
                             }
                         }
+                        log::warn!("WS connection closed");
+                        state.lock().await.socket = None;
                     }
-                    WsEvent::Error(e) => {
-                        log::warn!("WS error {}", &e);
-                    }
-                    WsEvent::Closed => {
-                        log::info!("WS connection closed");
-                    }
-                    WsEvent::Opened => {
-                        log::info!("Processing tx msg");
-                        Backend::process_tx_send_messages(tx.clone(), recv.clone());
-                        log::trace!("Ws connection opened");
-                    }
+                    Err(e) => log::warn!("WS error {}", &e),
                 }
-            }
 
-            log::warn!("WS connection closed");
+                // nobody is left to use the connection
+                if closed.try_recv().is_err() {
+                    break;
+                }
+                if attempts >= options.max_reconnects {
+                    state.lock().await.failed(format!(
+                        "couldn't reconnect to {} after {} attempts",
+                        url, attempts
+                    ));
+                    break;
+                }
+                let backoff = options
+                    .min_backoff
+                    .saturating_mul(2u32.saturating_pow(attempts))
+                    .min(options.max_backoff);
+                attempts += 1;
+                log::info!("WS reconnecting to {} in {:?}", url, backoff);
+                runtime.sleep(backoff).await;
+            }
         });
     }
 }
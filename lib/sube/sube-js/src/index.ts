@@ -5,12 +5,21 @@ export interface SubeOptions {
   from: Uint8Array,
   body: any,
   nonce?: number,
+  /** Signature scheme of the `sign` function, `sr25519` by default */
+  scheme?: 'sr25519' | 'ed25519' | 'ecdsa' | 'ethereum',
+}
+
+/** Result of submitting an extrinsic, both values are hex encoded */
+export interface Submitted {
+  hash: string,
+  extrinsic: string,
 }
 
 
 export async function sube<T>(url: string, options?: SubeOptions) {
   return sube_js(url, options && {
     from: options.from,
+    scheme: options.scheme,
     call: {
       nonce: options.nonce,
       body: options.body,
@@ -1,10 +1,9 @@
 mod util;
 
-use core::convert::TryInto;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen;
 use sube::{
-    sube, Error as SubeError, ExtrinsicBody, JsonValue, Response, SubeBuilder
+    sube, Error as SubeError, ExtrinsicBody, JsonValue, Response, Scheme, SubeBuilder
 };
 use util::*;
 use wasm_bindgen::prelude::*;
@@ -29,6 +28,7 @@ extern "C" {
 #[derive(Serialize, Deserialize, Debug)]
 struct ExtrinsicBodyWithFrom {
     from: Vec<u8>,
+    scheme: Option<Scheme>,
     call: ExtrinsicBody<JsonValue>,
 }
 
@@ -50,8 +50,14 @@ pub async fn sube_js(
             .map_err(|e| JsError::new(&format!("Error querying: {:?}", &e.to_string())))?;
 
         let value = match response {
-            v @ Response::Value(_) | v @ Response::Meta(_) | v @ Response::Registry(_) => {
-                let value = serde_wasm_bindgen::to_value(&v)
+            v @ Response::Value(_)
+            | v @ Response::Json(_)
+            | v @ Response::Properties(_)
+            | v @ Response::Meta(_)
+            | v @ Response::Registry(_) => {
+                // plain objects instead of `Map`s, the shape of the generated TypeScript types
+                let value = v
+                    .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
                     .map_err(|_| JsError::new("failed to serialize response"))?;
                 Ok(value)
             }
@@ -63,8 +69,6 @@ pub async fn sube_js(
 
     let mut extrinsic_value: ExtrinsicBodyWithFrom = serde_wasm_bindgen::from_value(params)?;
 
-    extrinsic_value.call.body = decode_addresses(&extrinsic_value.call.body);
-
     log::info!("new extrinsic_value: {:?}", extrinsic_value);
 
     let signer = sube::SignerFn::from((
@@ -87,15 +91,14 @@ pub async fn sube_js(
                         .await
                         .map_err(|_| SubeError::Signing)?;
 
-                    let vec: Vec<u8> = serde_wasm_bindgen::from_value(response)
+                    let signature: Vec<u8> = serde_wasm_bindgen::from_value(response)
                         .map_err(|_| SubeError::Encode("Unknown value to decode".into()))?;
 
-                    let buffer: [u8; 64] = vec.try_into().expect("slice with incorrect length");
-
-                    Ok(buffer)
+                    Ok(signature)
             }
         },
-    ));
+    ))
+    .with_scheme(extrinsic_value.scheme.unwrap_or_default());
 
     let value = SubeBuilder::default()
         .with_url(url)
@@ -107,6 +110,8 @@ pub async fn sube_js(
 
     match value {
         Response::Void => Ok(JsValue::null()),
+        v @ Response::Submitted { .. } => serde_wasm_bindgen::to_value(&v)
+            .map_err(|_| JsError::new("failed to serialize response")),
         _ => Err(JsError::new("Unknown Response")),
     }
 }
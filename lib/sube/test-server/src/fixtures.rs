@@ -0,0 +1,294 @@
+//! Minimal chain data for tests that don't need a real runtime.
+use blake2::{digest::consts::U16, Blake2b, Digest};
+use codec::Encode;
+use core::{hash::Hasher as _, marker::PhantomData};
+use frame_metadata::{
+    v14::{
+        ExtrinsicMetadata, PalletCallMetadata, PalletConstantMetadata, PalletMetadata,
+        PalletStorageMetadata, RuntimeMetadataV14, StorageEntryMetadata, StorageEntryModifier,
+        StorageEntryType, StorageHasher,
+    },
+    RuntimeMetadataPrefixed,
+};
+use scale_info::{meta_type, MetaType, TypeInfo};
+
+/// Spec version of the runtime in the `System` `Version` constant
+pub const SPEC_VERSION: u32 = 100;
+/// Transaction version of the runtime in the `System` `Version` constant
+pub const TRANSACTION_VERSION: u32 = 1;
+
+#[derive(TypeInfo)]
+pub struct AccountId32(pub [u8; 32]);
+
+/// Account of Ethereum compatible chains, used as is as the extrinsic address
+#[derive(TypeInfo)]
+pub struct AccountId20(pub [u8; 20]);
+
+/// Account of the `System` pallet, `nonce` is used to sign extrinsics
+#[derive(Encode, TypeInfo)]
+pub struct AccountInfo {
+    pub nonce: u32,
+    pub consumers: u32,
+    pub providers: u32,
+    pub sufficients: u32,
+    pub data: AccountData,
+}
+
+#[derive(Encode, TypeInfo)]
+pub struct AccountData {
+    pub free: u128,
+    pub reserved: u128,
+    pub frozen: u128,
+    pub flags: u128,
+}
+
+#[derive(Encode, TypeInfo)]
+struct RuntimeVersion {
+    spec_version: u32,
+    transaction_version: u32,
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+enum MultiAddress {
+    Id(AccountId32),
+    Index(#[codec(compact)] u32),
+    Raw(Vec<u8>),
+    Address32([u8; 32]),
+    Address20([u8; 20]),
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+enum MultiSignature {
+    Ed25519([u8; 64]),
+    Sr25519([u8; 64]),
+    Ecdsa([u8; 65]),
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+struct EthereumSignature([u8; 65]);
+
+/// Signed extensions sube encodes, an immortal era, the nonce, the tip
+/// and the mode of the metadata hash check
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+struct Extra {
+    era: u8,
+    #[codec(compact)]
+    nonce: u32,
+    #[codec(compact)]
+    tip: u128,
+    mode: u8,
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code, non_camel_case_types)]
+enum SystemCall {
+    remark { remark: Vec<u8> },
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+enum RuntimeCall {
+    System(SystemCall),
+}
+
+#[derive(TypeInfo)]
+#[allow(dead_code)]
+struct UncheckedExtrinsic<Address, Call, Signature, Extra>(
+    Vec<u8>,
+    PhantomData<(Address, Call, Signature, Extra)>,
+);
+
+/// SCALE encoded metadata of a runtime with a `System` pallet storing the
+/// block `Number` and the `Account`s that can sign its `remark` call, a
+/// `Balances` pallet with the `TotalIssuance` and a `Maps` pallet with
+/// storage maps using different hashers:
+///
+/// - `Values`: `Twox64Concat` `u32` keys to `u64` values, `42` by default
+/// - `Optional`: `Blake2_128Concat` `u32` keys to `u32` values, an `OptionQuery`
+/// - `Hashed`: `Blake2_128` `u32` keys to `u32` values, the keys can't be decoded
+/// - `Double`: `Twox64Concat` `u32` and `Blake2_128Concat` `u16` keys to `u32` values
+///
+/// Extrinsics are signed with a `MultiAddress` and a `MultiSignature`.
+pub fn metadata() -> Vec<u8> {
+    runtime::<AccountId32, MultiAddress, MultiSignature>()
+}
+
+/// Metadata like [`metadata`] of an Ethereum compatible runtime, accounts are
+/// an `AccountId20` that signs extrinsics with a plain `EthereumSignature`
+pub fn ethereum_metadata() -> Vec<u8> {
+    runtime::<AccountId20, AccountId20, EthereumSignature>()
+}
+
+fn runtime<Account, Address, Signature>() -> Vec<u8>
+where
+    Account: TypeInfo + 'static,
+    Address: TypeInfo + 'static,
+    Signature: TypeInfo + 'static,
+{
+    let pallet = |name, index, entries| PalletMetadata {
+        name,
+        storage: Some(PalletStorageMetadata {
+            prefix: name,
+            entries,
+        }),
+        calls: None,
+        event: None,
+        constants: vec![],
+        error: None,
+        index,
+    };
+    let system = PalletMetadata {
+        calls: Some(PalletCallMetadata {
+            ty: meta_type::<SystemCall>(),
+        }),
+        constants: vec![PalletConstantMetadata {
+            name: "Version",
+            ty: meta_type::<RuntimeVersion>(),
+            value: RuntimeVersion {
+                spec_version: SPEC_VERSION,
+                transaction_version: TRANSACTION_VERSION,
+            }
+            .encode(),
+            docs: vec![],
+        }],
+        ..pallet(
+            "System",
+            0,
+            vec![
+                plain("Number", meta_type::<u32>(), 4),
+                map(
+                    "Account",
+                    vec![StorageHasher::Blake2_128Concat],
+                    meta_type::<Account>(),
+                    meta_type::<AccountInfo>(),
+                    Some(vec![0; 80]),
+                ),
+            ],
+        )
+    };
+    let pallets = vec![
+        system,
+        pallet(
+            "Balances",
+            1,
+            vec![plain("TotalIssuance", meta_type::<u128>(), 16)],
+        ),
+        pallet(
+            "Maps",
+            2,
+            vec![
+                map(
+                    "Values",
+                    vec![StorageHasher::Twox64Concat],
+                    meta_type::<u32>(),
+                    meta_type::<u64>(),
+                    Some(42u64.encode()),
+                ),
+                map(
+                    "Optional",
+                    vec![StorageHasher::Blake2_128Concat],
+                    meta_type::<u32>(),
+                    meta_type::<u32>(),
+                    None,
+                ),
+                map(
+                    "Hashed",
+                    vec![StorageHasher::Blake2_128],
+                    meta_type::<u32>(),
+                    meta_type::<u32>(),
+                    Some(0u32.encode()),
+                ),
+                map(
+                    "Double",
+                    vec![StorageHasher::Twox64Concat, StorageHasher::Blake2_128Concat],
+                    meta_type::<(u32, u16)>(),
+                    meta_type::<u32>(),
+                    Some(0u32.encode()),
+                ),
+            ],
+        ),
+    ];
+    let extrinsic = ExtrinsicMetadata {
+        ty: meta_type::<UncheckedExtrinsic<Address, RuntimeCall, Signature, Extra>>(),
+        version: 4,
+        signed_extensions: vec![],
+    };
+    let meta: RuntimeMetadataPrefixed =
+        RuntimeMetadataV14::new(pallets, extrinsic, meta_type::<()>()).into();
+    meta.encode()
+}
+
+fn plain(name: &'static str, ty: MetaType, size: usize) -> StorageEntryMetadata {
+    StorageEntryMetadata {
+        name,
+        modifier: StorageEntryModifier::Default,
+        ty: StorageEntryType::Plain(ty),
+        default: vec![0; size],
+        docs: vec![],
+    }
+}
+
+/// Storage map, entries without a default are an `OptionQuery`
+fn map(
+    name: &'static str,
+    hashers: Vec<StorageHasher>,
+    key: MetaType,
+    value: MetaType,
+    default: Option<Vec<u8>>,
+) -> StorageEntryMetadata {
+    let modifier = match default {
+        Some(_) => StorageEntryModifier::Default,
+        None => StorageEntryModifier::Optional,
+    };
+    StorageEntryMetadata {
+        name,
+        modifier,
+        ty: StorageEntryType::Map {
+            hashers,
+            key,
+            value,
+        },
+        default: default.unwrap_or_else(|| vec![0]),
+        docs: vec![],
+    }
+}
+
+/// Key of a storage item without map keys, e.g. `storage_key("System", "Number")`
+pub fn storage_key(pallet: &str, item: &str) -> Vec<u8> {
+    [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+/// Key of a storage map item given its hashed keys, e.g.
+/// `map_key("Maps", "Values", &[twox_64_concat(&1u32.encode())])`
+pub fn map_key(pallet: &str, item: &str, keys: &[Vec<u8>]) -> Vec<u8> {
+    [storage_key(pallet, item), keys.concat()].concat()
+}
+
+pub fn twox_64_concat(input: &[u8]) -> Vec<u8> {
+    let mut h = twox_hash::XxHash64::with_seed(0);
+    h.write(input);
+    [&h.finish().to_le_bytes()[..], input].concat()
+}
+
+pub fn blake2_128(input: &[u8]) -> Vec<u8> {
+    Blake2b::<U16>::digest(input).to_vec()
+}
+
+pub fn blake2_128_concat(input: &[u8]) -> Vec<u8> {
+    [blake2_128(input), input.to_vec()].concat()
+}
+
+fn twox_128(input: &[u8]) -> Vec<u8> {
+    [0, 1]
+        .into_iter()
+        .flat_map(|seed| {
+            let mut h = twox_hash::XxHash64::with_seed(seed);
+            h.write(input);
+            h.finish().to_le_bytes()
+        })
+        .collect()
+}
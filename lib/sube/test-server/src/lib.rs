@@ -0,0 +1,242 @@
+//! Fake Substrate node to test sube and its clients end to end.
+//!
+//! The server answers the JSON-RPC methods sube uses over HTTP and websockets
+//! from a metadata file and a snapshot of the storage and child tries. Tests
+//! can change the storage of the latest block, produce blocks, make the next
+//! requests of a method fail or drop the open connections to exercise error
+//! paths.
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tokio::sync::oneshot;
+
+pub mod fixtures;
+mod node;
+
+use node::Node;
+
+/// Data the node starts with
+#[derive(Clone, Debug, Default)]
+pub struct Chain {
+    /// SCALE encoded `RuntimeMetadataPrefixed`
+    pub metadata: Vec<u8>,
+    pub storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Child tries by their prefixed key, e.g. `:child_storage:default:foo`
+    pub child_storage: BTreeMap<Vec<u8>, BTreeMap<Vec<u8>, Vec<u8>>>,
+    /// Number of the latest block
+    pub block: u32,
+    /// Answer of `system_properties`, an empty object by default
+    pub properties: serde_json::Value,
+}
+
+impl Chain {
+    pub fn new(metadata: Vec<u8>) -> Self {
+        Chain {
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_storage(mut self, items: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) -> Self {
+        self.storage.extend(items);
+        self
+    }
+
+    pub fn with_child_storage(
+        mut self,
+        child_key: impl Into<Vec<u8>>,
+        items: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> Self {
+        self.child_storage
+            .entry(child_key.into())
+            .or_default()
+            .extend(items);
+        self
+    }
+
+    pub fn at_block(self, block: u32) -> Self {
+        Chain { block, ..self }
+    }
+
+    /// Token and address format, e.g. `{ "tokenSymbol": "KSM", "tokenDecimals": 12 }`
+    pub fn with_properties(self, properties: serde_json::Value) -> Self {
+        Chain { properties, ..self }
+    }
+
+    /// Reads the metadata in SCALE or hex(as returned by `state_getMetadata`)
+    /// and a storage snapshot, a JSON object with hex encoded keys and values
+    pub fn from_files(metadata: impl AsRef<Path>, storage: Option<&Path>) -> io::Result<Self> {
+        let metadata = std::fs::read(metadata)?;
+        let metadata = match metadata.strip_prefix(b"0x") {
+            Some(hex) => hex::decode(hex.trim_ascii_end()).map_err(invalid_data)?,
+            None => metadata,
+        };
+        let Some(storage) = storage else {
+            return Ok(Chain::new(metadata));
+        };
+        let snapshot: BTreeMap<String, String> =
+            serde_json::from_slice(&std::fs::read(storage)?).map_err(invalid_data)?;
+        let decode = |s: &str| hex::decode(s.trim_start_matches("0x")).map_err(invalid_data);
+        let items = snapshot
+            .iter()
+            .map(|(k, v)| Ok((decode(k)?, decode(v)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Chain::new(metadata).with_storage(items))
+    }
+}
+
+fn invalid_data(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Failure the node answers a request with instead of its result
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// JSON-RPC error response
+    Error { code: i64, message: String },
+    /// The connection is dropped without an answer, over HTTP the request
+    /// fails with a `503 Service Unavailable`
+    Disconnect,
+}
+
+/// A node listening on a random local port, it runs in its own thread so
+/// it can be used from any async runtime and is stopped when dropped
+pub struct TestServer {
+    addr: SocketAddr,
+    node: Arc<Node>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub fn start(chain: Chain) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let node = Arc::new(Node::new(chain));
+        let (shutdown, stopped) = oneshot::channel();
+
+        let app = Router::new()
+            .route("/", get(websocket).post(http))
+            .route("/{*path}", get(websocket).post(http))
+            .with_state(node.clone());
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let listener =
+                    tokio::net::TcpListener::from_std(listener).expect("listener in a runtime");
+                let stopped = async {
+                    stopped.await.ok();
+                };
+                if let Err(err) = axum::serve(listener, app)
+                    .with_graceful_shutdown(stopped)
+                    .await
+                {
+                    log::error!("Test server failed: {}", err);
+                }
+            })
+        });
+        log::info!("Test server listening on {}", addr);
+
+        Ok(TestServer {
+            addr,
+            node,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn http_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    /// Sets or removes(with `None`) a storage item from the latest block on,
+    /// earlier blocks keep the value they had
+    pub fn set_storage(&self, key: impl Into<Vec<u8>>, value: Option<Vec<u8>>) {
+        self.node.set_storage(key.into(), value)
+    }
+
+    /// Number of the latest block
+    pub fn block(&self) -> u32 {
+        self.node.block()
+    }
+
+    /// Produces a new block, subscribers of new and finalized heads get its
+    /// header and watched extrinsics are reported as included in it
+    pub fn new_block(&self) -> u32 {
+        self.node.new_block()
+    }
+
+    /// Raw extrinsics received in the order they were submitted
+    pub fn submitted(&self) -> Vec<Vec<u8>> {
+        self.node.submitted()
+    }
+
+    /// Methods of the requests received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.node.requests()
+    }
+
+    /// Answers the next request of the method with a fault, faults of the
+    /// same method are used in the order they were injected
+    pub fn inject(&self, method: &str, fault: Fault) {
+        self.node.inject(method, fault)
+    }
+
+    /// Closes every open websocket, clients are expected to reconnect
+    pub fn disconnect(&self) {
+        self.node.disconnect()
+    }
+
+    /// Number of websockets open at the moment
+    pub fn connections(&self) -> usize {
+        self.node.connections()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.node.disconnect();
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+async fn http(State(node): State<Arc<Node>>, body: String) -> Response {
+    match node.handle(&body, None) {
+        Some(res) => ([(header::CONTENT_TYPE, "application/json")], res).into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn websocket(State(node): State<Arc<Node>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| node.serve_socket(socket))
+}
@@ -0,0 +1,710 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axum::extract::ws::{Message, WebSocket};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use codec::{Compact, Encode};
+use futures_util::{SinkExt as _, StreamExt as _};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, watch};
+
+use crate::{Chain, Fault};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const PARSE_ERROR: i64 = -32700;
+/// Items a `chainHead_v1_storage` operation sends in each event, the node waits
+/// for a `chainHead_v1_continue` before sending the next ones
+const ITEMS_PER_EVENT: usize = 1000;
+
+type Outgoing = mpsc::UnboundedSender<String>;
+type Storage = BTreeMap<Vec<u8>, Vec<u8>>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Topic {
+    NewHeads,
+    FinalizedHeads,
+    /// Status of a submitted extrinsic, it's included in the next block
+    Extrinsic,
+    /// Events of a `chainHead_v1_follow` subscription and its operations
+    ChainHead,
+    /// Progress of a transaction of `transactionWatch_v1_submitAndWatch`
+    Transaction,
+}
+
+impl Topic {
+    fn method(&self) -> &'static str {
+        match self {
+            Topic::NewHeads => "chain_newHead",
+            Topic::FinalizedHeads => "chain_finalizedHead",
+            Topic::Extrinsic => "author_extrinsicUpdate",
+            Topic::ChainHead => "chainHead_v1_followEvent",
+            Topic::Transaction => "transactionWatch_v1_watchEvent",
+        }
+    }
+}
+
+struct Subscriber {
+    topic: Topic,
+    tx: Outgoing,
+}
+
+/// Storage items of a `chainHead_v1_storage` operation yet to be sent
+struct Operation {
+    follow: String,
+    pending: VecDeque<Vec<Value>>,
+}
+
+/// State of the fake node shared by all the connections
+pub(crate) struct Node {
+    metadata: Vec<u8>,
+    properties: Value,
+    /// Storage by the block it was changed in, blocks without changes
+    /// have the storage of the closest block before them
+    storage: Mutex<BTreeMap<u32, Storage>>,
+    /// Child tries, they are the same in every block
+    child_storage: BTreeMap<Vec<u8>, Storage>,
+    block: AtomicU32,
+    submitted: Mutex<Vec<Vec<u8>>>,
+    requests: Mutex<Vec<String>>,
+    faults: Mutex<BTreeMap<String, VecDeque<Fault>>>,
+    subscriptions: Mutex<BTreeMap<String, Subscriber>>,
+    operations: Mutex<BTreeMap<String, Operation>>,
+    /// Ids of the subscriptions and operations
+    next_id: AtomicU64,
+    /// Changed to close every open websocket
+    disconnect: watch::Sender<u64>,
+    /// Websockets being served
+    connections: AtomicUsize,
+}
+
+/// SCALE encoded header of the block, headers have an empty digest and zeroed roots
+fn encoded_header(parent: [u8; 32], number: u32) -> Vec<u8> {
+    (
+        parent,
+        Compact(number),
+        [0u8; 32],
+        [0u8; 32],
+        Vec::<()>::new(),
+    )
+        .encode()
+}
+
+/// Hash of the SCALE encoded header of the block
+fn block_hash(number: u32) -> [u8; 32] {
+    (0..=number).fold([0; 32], |parent, number| {
+        Blake2b::<U32>::digest(encoded_header(parent, number)).into()
+    })
+}
+
+fn parent_hash(number: u32) -> [u8; 32] {
+    number.checked_sub(1).map(block_hash).unwrap_or_default()
+}
+
+fn value_hash(value: &[u8]) -> String {
+    to_hex(Blake2b::<U32>::digest(value))
+}
+
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn invalid_params(message: impl Into<String>) -> Fault {
+    Fault::Error {
+        code: INVALID_PARAMS,
+        message: message.into(),
+    }
+}
+
+fn bytes_param(param: Option<&Value>) -> Result<Vec<u8>, Fault> {
+    param
+        .and_then(Value::as_str)
+        .and_then(|p| hex::decode(p.trim_start_matches("0x")).ok())
+        .ok_or_else(|| invalid_params("expected a hex string"))
+}
+
+fn keys_param(param: Option<&Value>) -> Result<Vec<Vec<u8>>, Fault> {
+    param
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid_params("expected a list of keys"))?
+        .iter()
+        .map(|key| bytes_param(Some(key)))
+        .collect()
+}
+
+/// Up to `count` keys with the given prefix after the `start` key
+fn keys_paged(storage: &Storage, prefix: &[u8], count: u64, start: Option<Vec<u8>>) -> Value {
+    let start = match start {
+        Some(key) => Bound::Excluded(key),
+        None => Bound::Included(prefix.to_vec()),
+    };
+    storage
+        .range((start, Bound::Unbounded))
+        .map(|(key, _)| key)
+        .take_while(|key| key.starts_with(prefix))
+        .take(count as usize)
+        .map(to_hex)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Results of an item of a `chainHead_v1_storage` request, items without
+/// a value are left out
+fn storage_items(storage: &Storage, item: &Value) -> Result<Vec<Value>, Fault> {
+    let key = bytes_param(item.get("key"))?;
+    let value = storage.get(&key);
+    Ok(match item.get("type").and_then(Value::as_str) {
+        Some("value") => value
+            .map(|v| json!({ "key": to_hex(&key), "value": to_hex(v) }))
+            .into_iter()
+            .collect(),
+        Some("hash") => value
+            .map(|v| json!({ "key": to_hex(&key), "hash": value_hash(v) }))
+            .into_iter()
+            .collect(),
+        Some("descendantsHashes") => storage
+            .range(key.clone()..)
+            .take_while(|(k, _)| k.starts_with(&key))
+            .map(|(k, v)| json!({ "key": to_hex(k), "hash": value_hash(v) }))
+            .collect(),
+        _ => return Err(invalid_params("unsupported storage query")),
+    })
+}
+
+impl Node {
+    pub fn new(chain: Chain) -> Self {
+        Node {
+            metadata: chain.metadata,
+            properties: chain.properties,
+            storage: Mutex::new(BTreeMap::from([(0, chain.storage)])),
+            child_storage: chain.child_storage,
+            block: AtomicU32::new(chain.block),
+            submitted: Mutex::new(vec![]),
+            requests: Mutex::new(vec![]),
+            faults: Mutex::new(BTreeMap::new()),
+            subscriptions: Mutex::new(BTreeMap::new()),
+            operations: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            disconnect: watch::Sender::new(0),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn set_storage(&self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let block = self.block();
+        let mut history = self.storage.lock().expect("not poisoned");
+        if !history.contains_key(&block) {
+            let previous = history
+                .range(..block)
+                .next_back()
+                .map(|(_, storage)| storage.clone())
+                .unwrap_or_default();
+            history.insert(block, previous);
+        }
+        let storage = history.get_mut(&block).expect("storage of the block");
+        match value {
+            Some(value) => storage.insert(key, value),
+            None => storage.remove(&key),
+        };
+    }
+
+    pub fn block(&self) -> u32 {
+        self.block.load(Ordering::Relaxed)
+    }
+
+    /// Reads the storage as it was at the given block
+    fn storage_at<T>(&self, block: u32, read: impl FnOnce(&Storage) -> T) -> T {
+        let history = self.storage.lock().expect("not poisoned");
+        let storage = history.range(..=block).next_back().map(|(_, s)| s);
+        read(storage.unwrap_or(&Storage::new()))
+    }
+
+    pub fn submitted(&self) -> Vec<Vec<u8>> {
+        self.submitted.lock().expect("not poisoned").clone()
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().expect("not poisoned").clone()
+    }
+
+    pub fn inject(&self, method: &str, fault: Fault) {
+        self.faults
+            .lock()
+            .expect("not poisoned")
+            .entry(method.into())
+            .or_default()
+            .push_back(fault);
+    }
+
+    pub fn disconnect(&self) {
+        self.disconnect.send_modify(|n| *n += 1);
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Produces a block notifying its header to the subscribers, extrinsics
+    /// being watched are included in it and it's finalized right away
+    pub fn new_block(&self) -> u32 {
+        let number = self.block.fetch_add(1, Ordering::Relaxed) + 1;
+        let header = header(number);
+        let hash = to_hex(block_hash(number));
+
+        let mut subscriptions = self.subscriptions.lock().expect("not poisoned");
+        subscriptions.retain(|id, sub| {
+            let results = match sub.topic {
+                Topic::NewHeads | Topic::FinalizedHeads => vec![header.clone()],
+                Topic::Extrinsic => vec![json!({ "inBlock": hash }), json!({ "finalized": hash })],
+                Topic::ChainHead => vec![
+                    json!({
+                        "event": "newBlock",
+                        "blockHash": hash,
+                        "parentBlockHash": to_hex(parent_hash(number)),
+                        "newRuntime": null,
+                    }),
+                    json!({ "event": "bestBlockChanged", "bestBlockHash": hash }),
+                    json!({
+                        "event": "finalized",
+                        "finalizedBlockHashes": [hash],
+                        "prunedBlockHashes": [],
+                    }),
+                ],
+                Topic::Transaction => vec![
+                    json!({ "event": "bestChainBlockIncluded", "block": { "hash": hash, "index": 0 } }),
+                    json!({ "event": "finalized", "block": { "hash": hash, "index": 0 } }),
+                ],
+            };
+            let done = matches!(sub.topic, Topic::Extrinsic | Topic::Transaction);
+            let sent = results
+                .into_iter()
+                .all(|result| sub.tx.send(notification(sub.topic, id, result)).is_ok());
+            sent && !done
+        });
+        number
+    }
+
+    /// Answers a request or batch of requests, `None` when the connection
+    /// should be dropped without an answer
+    pub fn handle(&self, msg: &str, conn: Option<&Outgoing>) -> Option<String> {
+        let Ok(msg) = serde_json::from_str::<Value>(msg) else {
+            let err = Fault::Error {
+                code: PARSE_ERROR,
+                message: "Parse error".into(),
+            };
+            return Some(response(&Value::Null, Err(err))?.to_string());
+        };
+        let res = match msg {
+            Value::Array(batch) => Value::Array(
+                batch
+                    .iter()
+                    .map(|req| self.request(req, conn))
+                    .collect::<Option<_>>()?,
+            ),
+            req => self.request(&req, conn)?,
+        };
+        Some(res.to_string())
+    }
+
+    fn request(&self, req: &Value, conn: Option<&Outgoing>) -> Option<Value> {
+        let id = req.get("id").unwrap_or(&Value::Null);
+        let method = req
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = match req.get("params") {
+            Some(Value::Array(params)) => params.as_slice(),
+            _ => &[],
+        };
+        log::debug!("RPC `{}` {:?}", method, params);
+        self.requests
+            .lock()
+            .expect("not poisoned")
+            .push(method.into());
+
+        let fault = self
+            .faults
+            .lock()
+            .expect("not poisoned")
+            .get_mut(method)
+            .and_then(VecDeque::pop_front);
+        let res = match fault {
+            Some(fault) => Err(fault),
+            None => self.call(method, params, conn),
+        };
+        response(id, res)
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        params: &[Value],
+        conn: Option<&Outgoing>,
+    ) -> Result<Value, Fault> {
+        let param = |i: usize| params.get(i).filter(|p| !p.is_null());
+        Ok(match method {
+            "system_health" => json!({ "peers": 1, "isSyncing": false, "shouldHavePeers": true }),
+            "system_chain" => json!("Development"),
+            "system_version" => json!(env!("CARGO_PKG_VERSION")),
+            "system_properties" => match &self.properties {
+                Value::Null => json!({}),
+                properties => properties.clone(),
+            },
+            "state_getMetadata" => json!(to_hex(&self.metadata)),
+            "chain_getBlockHash" => {
+                let number = match param(0) {
+                    None => self.block(),
+                    Some(Value::Number(n)) => n.as_u64().unwrap_or(u64::MAX) as u32,
+                    Some(Value::String(n)) => {
+                        u32::from_str_radix(n.trim_start_matches("0x"), 16)
+                            .map_err(|_| invalid_params("invalid block number"))?
+                    }
+                    _ => return Err(invalid_params("invalid block number")),
+                };
+                if number > self.block() {
+                    Value::Null
+                } else {
+                    json!(to_hex(block_hash(number)))
+                }
+            }
+            "chain_getHeader" => header(self.block_at(param(0))?),
+            "state_getReadProof" => {
+                // there is no trie, the proof only tells the block it is for
+                keys_param(param(0))?;
+                let block = self.block_at(param(1))?;
+                json!({ "at": to_hex(block_hash(block)), "proof": [] })
+            }
+            "chain_getFinalizedHead" => json!(to_hex(block_hash(self.block()))),
+            "state_getStorage" => {
+                let key = bytes_param(param(0))?;
+                let block = self.block_at(param(1))?;
+                self.storage_at(block, |storage| storage.get(&key).map(to_hex).into())
+            }
+            "state_queryStorageAt" => {
+                let keys = keys_param(param(0))?;
+                let block = self.block_at(param(1))?;
+                let changes = self.storage_at(block, |storage| {
+                    keys.iter()
+                        .map(|key| json!([to_hex(key), storage.get(key).map(to_hex)]))
+                        .collect::<Vec<_>>()
+                });
+                json!([{ "block": to_hex(block_hash(block)), "changes": changes }])
+            }
+            "state_getKeysPaged" => {
+                let prefix = bytes_param(param(0))?;
+                let count = param(1)
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("expected a count"))?;
+                let start = param(2).map(|key| bytes_param(Some(key))).transpose()?;
+                let block = self.block_at(param(3))?;
+                self.storage_at(block, |storage| keys_paged(storage, &prefix, count, start))
+            }
+            "childstate_getStorage" => {
+                let key = bytes_param(param(1))?;
+                self.block_at(param(2))?;
+                self.child_trie(param(0))?.get(&key).map(to_hex).into()
+            }
+            "childstate_getStorageEntries" => {
+                let keys = keys_param(param(1))?;
+                self.block_at(param(2))?;
+                let child = self.child_trie(param(0))?;
+                keys.iter()
+                    .map(|key| child.get(key).map(to_hex))
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+            "childstate_getStorageHash" => {
+                let key = bytes_param(param(1))?;
+                self.block_at(param(2))?;
+                let child = self.child_trie(param(0))?;
+                child.get(&key).map(|v| value_hash(v)).into()
+            }
+            "childstate_getKeysPaged" => {
+                let child = self.child_trie(param(0))?;
+                let prefix = bytes_param(param(1))?;
+                let count = param(2)
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("expected a count"))?;
+                let start = param(3).map(|key| bytes_param(Some(key))).transpose()?;
+                self.block_at(param(4))?;
+                keys_paged(child, &prefix, count, start)
+            }
+            "author_submitExtrinsic" => json!(to_hex(self.submit(param(0))?)),
+            "author_submitAndWatchExtrinsic" => {
+                let conn = conn.ok_or_else(no_subscriptions)?;
+                self.submit(param(0))?;
+                json!(self.subscribe(Topic::Extrinsic, conn))
+            }
+            "chain_subscribeNewHeads" => {
+                json!(self.subscribe(Topic::NewHeads, conn.ok_or_else(no_subscriptions)?))
+            }
+            "chain_subscribeFinalizedHeads" => {
+                json!(self.subscribe(Topic::FinalizedHeads, conn.ok_or_else(no_subscriptions)?))
+            }
+            "chainSpec_v1_genesisHash" => json!(to_hex(block_hash(0))),
+            "chainHead_v1_follow" => {
+                let id = self.subscribe(Topic::ChainHead, conn.ok_or_else(no_subscriptions)?);
+                // only the latest block is pinned, it's the finalized one
+                let hash = to_hex(block_hash(self.block()));
+                self.notify(
+                    &id,
+                    json!({ "event": "initialized", "finalizedBlockHashes": [hash] }),
+                );
+                json!(id)
+            }
+            "chainHead_v1_header" => {
+                self.follow_param(param(0))?;
+                let block = self.block_at(param(1))?;
+                json!(to_hex(encoded_header(parent_hash(block), block)))
+            }
+            "chainHead_v1_storage" => {
+                let follow = self.follow_param(param(0))?;
+                let block = self.block_at(param(1))?;
+                let items = param(2)
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid_params("expected storage items"))?;
+                let child = param(3).map(|key| self.child_trie(Some(key))).transpose()?;
+                let results = self.storage_at(block, |storage| {
+                    let storage = child.unwrap_or(storage);
+                    items
+                        .iter()
+                        .map(|item| storage_items(storage, item))
+                        .collect::<Result<Vec<_>, _>>()
+                })?;
+                let pending = results
+                    .concat()
+                    .chunks(ITEMS_PER_EVENT)
+                    .map(<[Value]>::to_vec)
+                    .collect();
+                let id = self.next_id();
+                self.operations
+                    .lock()
+                    .expect("not poisoned")
+                    .insert(id.clone(), Operation { follow, pending });
+                self.continue_operation(&id)?;
+                json!({ "result": "started", "operationId": id, "discardedItems": 0 })
+            }
+            "chainHead_v1_continue" => {
+                self.follow_param(param(0))?;
+                let id = param(1).and_then(Value::as_str).unwrap_or_default();
+                self.continue_operation(id)?;
+                Value::Null
+            }
+            "chainHead_v1_stopOperation" => {
+                self.follow_param(param(0))?;
+                let id = param(1).and_then(Value::as_str).unwrap_or_default();
+                self.operations.lock().expect("not poisoned").remove(id);
+                Value::Null
+            }
+            "chainHead_v1_call" => {
+                let follow = self.follow_param(param(0))?;
+                self.block_at(param(1))?;
+                let mut event = match param(2).and_then(Value::as_str) {
+                    // the runtime only knows how to return its metadata
+                    Some("Metadata_metadata") => json!({
+                        "event": "operationCallDone",
+                        "output": to_hex(self.metadata.encode()),
+                    }),
+                    function => json!({
+                        "event": "operationError",
+                        "error": format!("unknown runtime function {:?}", function),
+                    }),
+                };
+                let id = self.next_id();
+                event["operationId"] = json!(id);
+                self.notify(&follow, event);
+                json!({ "result": "started", "operationId": id })
+            }
+            "chainHead_v1_unpin" => {
+                self.follow_param(param(0))?;
+                Value::Null
+            }
+            "transactionWatch_v1_submitAndWatch" => {
+                let conn = conn.ok_or_else(no_subscriptions)?;
+                self.submit(param(0))?;
+                json!(self.subscribe(Topic::Transaction, conn))
+            }
+            "chain_unsubscribeNewHeads"
+            | "chain_unsubscribeFinalizedHeads"
+            | "author_unwatchExtrinsic"
+            | "chainHead_v1_unfollow"
+            | "transactionWatch_v1_unwatch" => {
+                let id = param(0).and_then(Value::as_str).unwrap_or_default();
+                let removed = self.subscriptions.lock().expect("not poisoned").remove(id);
+                json!(removed.is_some())
+            }
+            _ => {
+                return Err(Fault::Error {
+                    code: METHOD_NOT_FOUND,
+                    message: format!("Method not found: {}", method),
+                })
+            }
+        })
+    }
+
+    /// Number of the block with the given hash, the latest one without hash
+    fn block_at(&self, hash: Option<&Value>) -> Result<u32, Fault> {
+        let Some(hash) = hash else {
+            return Ok(self.block());
+        };
+        let hash = bytes_param(Some(hash))?;
+        (0..=self.block())
+            .rev()
+            .find(|&n| block_hash(n)[..] == hash[..])
+            .ok_or_else(|| invalid_params("unknown block"))
+    }
+
+    /// Id of an active `chainHead_v1_follow` subscription
+    fn follow_param(&self, id: Option<&Value>) -> Result<String, Fault> {
+        let id = id.and_then(Value::as_str).unwrap_or_default();
+        let subscriptions = self.subscriptions.lock().expect("not poisoned");
+        match subscriptions.get(id) {
+            Some(sub) if sub.topic == Topic::ChainHead => Ok(id.into()),
+            _ => Err(invalid_params("unknown follow subscription")),
+        }
+    }
+
+    /// Sends the next items of a storage operation, the operation is done
+    /// once there are no more items
+    fn continue_operation(&self, id: &str) -> Result<(), Fault> {
+        let mut operations = self.operations.lock().expect("not poisoned");
+        let operation = operations
+            .get_mut(id)
+            .ok_or_else(|| invalid_params("unknown operation"))?;
+        if let Some(items) = operation.pending.pop_front() {
+            self.notify(
+                &operation.follow,
+                json!({ "event": "operationStorageItems", "operationId": id, "items": items }),
+            );
+        }
+        if operation.pending.is_empty() {
+            let follow = operations.remove(id).expect("operation").follow;
+            self.notify(
+                &follow,
+                json!({ "event": "operationStorageDone", "operationId": id }),
+            );
+        } else {
+            self.notify(
+                &operation.follow,
+                json!({ "event": "operationWaitingForContinue", "operationId": id }),
+            );
+        }
+        Ok(())
+    }
+
+    /// Child trie of the given prefixed key, empty if it doesn't exist
+    fn child_trie(&self, key: Option<&Value>) -> Result<&Storage, Fault> {
+        static EMPTY: Storage = Storage::new();
+        let key = bytes_param(key)?;
+        Ok(self.child_storage.get(&key).unwrap_or(&EMPTY))
+    }
+
+    fn submit(&self, ext: Option<&Value>) -> Result<[u8; 32], Fault> {
+        let ext = bytes_param(ext)?;
+        let hash = Blake2b::<U32>::digest(&ext).into();
+        self.submitted.lock().expect("not poisoned").push(ext);
+        Ok(hash)
+    }
+
+    fn next_id(&self) -> String {
+        self.next_id.fetch_add(1, Ordering::Relaxed).to_string()
+    }
+
+    fn subscribe(&self, topic: Topic, conn: &Outgoing) -> String {
+        let id = self.next_id();
+        self.subscriptions.lock().expect("not poisoned").insert(
+            id.clone(),
+            Subscriber {
+                topic,
+                tx: conn.clone(),
+            },
+        );
+        id
+    }
+
+    /// Sends a notification to the subscriber, the connection will answer
+    /// the current request before it
+    fn notify(&self, id: &str, result: Value) {
+        let subscriptions = self.subscriptions.lock().expect("not poisoned");
+        if let Some(sub) = subscriptions.get(id) {
+            let _ = sub.tx.send(notification(sub.topic, id, result));
+        }
+    }
+
+    /// Answers the requests of a websocket until the client or the node
+    /// closes the connection
+    pub async fn serve_socket(self: Arc<Self>, socket: WebSocket) {
+        let (mut sink, mut stream) = socket.split();
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let mut disconnect = self.disconnect.subscribe();
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        loop {
+            let msg = tokio::select! {
+                msg = stream.next() => match msg {
+                    Some(Ok(Message::Text(msg))) => match self.handle(msg.as_str(), Some(&tx)) {
+                        Some(res) => res,
+                        None => break,
+                    },
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                Some(msg) = rx.recv() => msg,
+                _ = disconnect.changed() => break,
+            };
+            if sink.send(Message::Text(msg.into())).await.is_err() {
+                break;
+            }
+        }
+        log::debug!("WS connection closed");
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+        drop(rx);
+        self.subscriptions
+            .lock()
+            .expect("not poisoned")
+            .retain(|_, sub| !sub.tx.is_closed());
+    }
+}
+
+fn no_subscriptions() -> Fault {
+    Fault::Error {
+        code: METHOD_NOT_FOUND,
+        message: "Subscriptions are not available over HTTP".into(),
+    }
+}
+
+fn header(number: u32) -> Value {
+    json!({
+        "parentHash": to_hex(parent_hash(number)),
+        "number": format!("0x{:x}", number),
+        "stateRoot": to_hex([0; 32]),
+        "extrinsicsRoot": to_hex([0; 32]),
+        "digest": { "logs": [] },
+    })
+}
+
+fn notification(topic: Topic, id: &str, result: Value) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": topic.method(),
+        "params": { "subscription": id, "result": result },
+    })
+    .to_string()
+}
+
+fn response(id: &Value, res: Result<Value, Fault>) -> Option<Value> {
+    Some(match res {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(Fault::Error { code, message }) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+        Err(Fault::Disconnect) => return None,
+    })
+}
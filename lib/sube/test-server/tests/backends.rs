@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use blake2::{digest::consts::U32, Blake2b, Digest};
+
+use codec::Encode;
+use sube::{
+    chain_head::ChainHeadClient,
+    http, json,
+    rpc::{RpcClient, RpcSubscribe as _},
+    ws, Backend as _, JsonValue, Scheme, SignerFn,
+};
+use sube_test_server::{fixtures, Chain, Fault, TestServer};
+
+fn server() -> TestServer {
+    let chain = Chain::new(fixtures::metadata())
+        .with_storage([(
+            fixtures::storage_key("System", "Number"),
+            7u32.to_le_bytes().into(),
+        )])
+        .at_block(7);
+    TestServer::start(chain).expect("server started")
+}
+
+async fn query_number(backend: &impl sube::Backend) -> sube::Result<JsonValue> {
+    let meta = backend.metadata().await?;
+    let res = sube::query(backend, &meta, "system/number", None).await?;
+    Ok(json!(res))
+}
+
+#[tokio::test]
+async fn http_query() {
+    let server = server();
+    let backend = RpcClient(http::Backend::new(server.http_url().as_str()));
+
+    assert_eq!(query_number(&backend).await.unwrap(), json!(7));
+    let (latest, at_3) = (backend.block_info(None), backend.block_info(Some(3)));
+    assert_ne!(latest.await.unwrap().hash, at_3.await.unwrap().hash);
+
+    let url = format!("{}/system/number", server.http_url());
+    let res = sube::sube(&url).await.unwrap();
+    assert_eq!(json!(res), json!(7));
+}
+
+#[tokio::test]
+async fn query_many_in_one_request() {
+    let server = server();
+    let paths = ["system/number", "balances/totalIssuance", "system/number"];
+
+    let url = server.http_url();
+    let res = sube::sube_many(&url, &paths).await.unwrap();
+    assert_eq!(json!(res), json!([7, 0, 7]));
+    let queries = server
+        .requests()
+        .iter()
+        .filter(|m| *m == "state_queryStorageAt")
+        .count();
+    assert_eq!(queries, 1);
+}
+
+#[tokio::test]
+async fn http_errors() {
+    let server = server();
+    let backend = RpcClient(http::Backend::new(server.http_url().as_str()));
+
+    server.inject(
+        "state_queryStorageAt",
+        Fault::Error {
+            code: -32000,
+            message: "boom".into(),
+        },
+    );
+    let err = query_number(&backend).await.unwrap_err();
+    assert!(err.to_string().contains("boom"), "{}", err);
+
+    server.inject("state_queryStorageAt", Fault::Disconnect);
+    assert!(query_number(&backend).await.is_err());
+
+    assert_eq!(query_number(&backend).await.unwrap(), json!(7));
+}
+
+#[tokio::test]
+async fn ws_reconnects() {
+    let server = server();
+    let ws = ws::Backend::new_ws2(server.ws_url().as_str())
+        .await
+        .unwrap();
+    let mut heads = ws.subscribe("chain_subscribeNewHeads", &[]).await.unwrap();
+
+    server.new_block();
+    let head = heads.next().await.unwrap();
+    assert_eq!(head["number"], "0x8");
+
+    server.disconnect();
+    let backend = RpcClient(ws);
+    assert_eq!(query_number(&backend).await.unwrap(), json!(7));
+
+    // the subscription is restored along with the connection
+    let resubscribed = || {
+        server
+            .requests()
+            .iter()
+            .filter(|m| *m == "chain_subscribeNewHeads")
+            .count()
+            == 2
+    };
+    while !resubscribed() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    server.new_block();
+    let head = heads.next().await.unwrap();
+    assert_eq!(head["number"], "0x9");
+}
+
+#[async_std::test]
+async fn ws_runs_on_async_std() {
+    let server = server();
+    // outside of tokio the socket and its tasks use async-std
+    assert_eq!(ws::Options::default().runtime, ws::Runtime::AsyncStd);
+    let url = format!("{}/system/number", server.ws_url());
+    assert_eq!(json!(sube::sube(&url).await.unwrap()), json!(7));
+
+    let options = ws::Options {
+        runtime: ws::Runtime::Tokio,
+        ..Default::default()
+    };
+    let tokio = ws::Backend::with_options(server.ws_url().as_str(), options).await;
+    assert!(tokio.is_err());
+}
+
+#[tokio::test]
+async fn dropped_ws_backends_close_their_socket() {
+    let server = server();
+    let ws = ws::Backend::new_ws2(server.ws_url().as_str()).await;
+    let backend = RpcClient(ws.unwrap());
+    assert_eq!(query_number(&backend).await.unwrap(), json!(7));
+    assert_eq!(server.connections(), 1);
+
+    drop(backend);
+    let closed = async {
+        while server.connections() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), closed)
+        .await
+        .expect("the socket is closed");
+}
+
+#[tokio::test]
+async fn read_proofs_come_with_the_state_root_of_their_block() {
+    let server = server();
+    let backend = RpcClient(http::Backend::new(server.http_url().as_str()));
+    let key = fixtures::storage_key("System", "Number");
+
+    let proof = backend.get_read_proof(vec![key.clone()], Some(3)).await;
+    let proof = proof.unwrap();
+    assert_eq!(proof.at, backend.block_info(Some(3)).await.unwrap().hash);
+    assert_eq!(proof.state_root, [0; 32]);
+    // the test node has no trie to prove its values
+    let values = proof.verify(&[key]);
+    assert!(matches!(values, Err(sube::Error::BadProof)), "{:?}", values);
+}
+
+#[tokio::test]
+async fn metadata_explorer() {
+    let server = server();
+    let query = |path: &str| {
+        let url = format!("{}/_meta/{}", server.http_url(), path);
+        async move { json!(sube::sube(&url).await.unwrap()) }
+    };
+
+    let pallets = query("pallets").await;
+    assert_eq!(pallets[1]["name"], "Balances");
+    assert_eq!(pallets[1]["storage"], 1);
+
+    let number = query("system/storage/number").await;
+    assert_eq!(number["type"], "u32");
+    assert_eq!(number["modifier"], "Default");
+    assert_eq!(
+        query("balances").await["storage"][0],
+        query("balances/storage/total_issuance").await
+    );
+
+    let schema = query("system/storage/number/schema").await;
+    assert_eq!(schema["type"], "integer");
+    assert_eq!(schema["maximum"], u32::MAX);
+    let schema = query("balances/storage/total_issuance/schema").await;
+    assert_eq!(schema["anyOf"][1]["type"], "string");
+}
+
+#[tokio::test]
+async fn chain_properties() {
+    let chain = Chain::new(fixtures::metadata()).with_properties(json!({
+        "ss58Format": 2,
+        "tokenDecimals": [12, 12],
+        "tokenSymbol": ["KSM", "KAR"],
+    }));
+    let server = TestServer::start(chain).expect("server started");
+
+    let url = format!("{}/_system/properties", server.http_url());
+    let res = json!(sube::sube(&url).await.unwrap());
+    assert_eq!(res["chain"], "Development");
+    assert_eq!(res["tokenSymbol"], "KSM");
+    assert_eq!(res["tokenDecimals"], 12);
+    assert_eq!(res["ss58Format"], 2);
+}
+
+#[tokio::test]
+async fn submit_is_recorded() {
+    let server = server();
+    let backend = RpcClient(http::Backend::new(server.http_url().as_str()));
+
+    let hash = backend.submit([0x04, 0x00]).await.unwrap();
+    assert_eq!(server.submitted(), vec![vec![0x04, 0x00]]);
+    assert_eq!(hash, <[u8; 32]>::from(Blake2b::<U32>::digest([0x04, 0x00])));
+}
+
+/// Submits a `remark` of the account of `signer`, the node doesn't check signatures
+async fn submit_remark(server: &TestServer, signer: impl sube::Signer) -> sube::Result<Vec<u8>> {
+    let backend = RpcClient(http::Backend::new(server.http_url().as_str()));
+    let meta = backend.metadata().await?;
+    let body = sube::ExtrinsicBody {
+        nonce: None,
+        body: json!({ "remark": [1, 2] }),
+    };
+    sube::submit(&backend, &meta, "system/remark", body, signer).await?;
+    Ok(server.submitted().pop().expect("extrinsic submitted"))
+}
+
+/// Signed extrinsic of a `remark` of `[1, 2]` with the default extra of a new account
+fn remark(address: &[u8], signature: &[u8]) -> Vec<u8> {
+    let extra = [0u8; 4];
+    let call = [0u8, 0, 0x08, 1, 2];
+    let inner = [&[0x84][..], address, signature, &extra, &call].concat();
+    [codec::Compact(inner.len() as u32).encode(), inner].concat()
+}
+
+#[tokio::test]
+async fn submit_signed_by_ed25519() {
+    let server = server();
+    let signer = SignerFn::from(([1u8; 32], |_: &[u8]| async {
+        Ok::<_, sube::Error>([7u8; 64])
+    }))
+    .with_scheme(Scheme::Ed25519);
+
+    let extrinsic = submit_remark(&server, signer).await.unwrap();
+    // `MultiAddress::Id` and `MultiSignature::Ed25519`
+    let expected = remark(
+        &[&[0x00][..], &[1; 32]].concat(),
+        &[&[0x00][..], &[7; 64]].concat(),
+    );
+    assert_eq!(extrinsic, expected);
+}
+
+#[tokio::test]
+async fn submit_to_an_ethereum_runtime() {
+    let server = TestServer::start(Chain::new(fixtures::ethereum_metadata())).unwrap();
+    let signer = SignerFn::from(([1u8; 20], |_: &[u8]| async {
+        Ok::<_, sube::Error>([7u8; 65])
+    }))
+    .with_scheme(Scheme::Ethereum);
+
+    let extrinsic = submit_remark(&server, signer).await.unwrap();
+    assert_eq!(extrinsic, remark(&[1; 20], &[7; 65]));
+
+    let sr25519 = SignerFn::from(([1u8; 20], |_: &[u8]| async {
+        Ok::<_, sube::Error>([7u8; 64])
+    }));
+    assert!(matches!(
+        submit_remark(&server, sr25519).await,
+        Err(sube::Error::Encode(_))
+    ));
+}
+
+async fn chain_head(server: &TestServer) -> ChainHeadClient<ws::Backend> {
+    let ws = ws::Backend::new_ws2(server.ws_url().as_str()).await;
+    ChainHeadClient::new(ws.unwrap())
+}
+
+#[tokio::test]
+async fn chain_head_query() {
+    let server = server();
+    assert_eq!(
+        query_number(&chain_head(&server).await).await.unwrap(),
+        json!(7)
+    );
+
+    let url = format!("{}/system/number?chainhead", server.ws_url());
+    assert_eq!(json!(sube::sube(&url).await.unwrap()), json!(7));
+    assert!(server.requests().iter().any(|m| m == "chainHead_v1_follow"));
+    assert!(!server.requests().iter().any(|m| m.starts_with("state_")));
+
+    // the legacy functions can't follow the chain over HTTP
+    let url = format!("{}/system/number?chainhead", server.http_url());
+    assert!(matches!(sube::sube(&url).await, Err(sube::Error::BadInput)));
+}
+
+#[tokio::test]
+async fn chain_head_blocks() {
+    let server = server();
+    let backend = chain_head(&server).await;
+    let rpc = RpcClient(http::Backend::new(server.http_url().as_str()));
+
+    let latest = backend.block_info(None).await.unwrap();
+    assert_eq!(latest.number, 7);
+    assert_eq!(latest.hash, rpc.block_info(Some(7)).await.unwrap().hash);
+    // the genesis comes from the chain spec as it isn't pinned
+    let genesis = backend.block_info(Some(0)).await.unwrap();
+    assert_eq!(genesis.hash, rpc.block_info(Some(0)).await.unwrap().hash);
+
+    let Err(err) = backend.block_info(Some(3)).await else {
+        panic!("block 3 is not pinned");
+    };
+    assert!(err.to_string().contains("not pinned"), "{}", err);
+}
+
+#[tokio::test]
+async fn chain_head_lists_the_keys_once() {
+    let items = (0..1500u32).map(|n| (n.to_be_bytes().to_vec(), n.encode()));
+    let chain =
+        Chain::new(fixtures::metadata()).with_child_storage(":child_storage:default:kids", items);
+    let server = TestServer::start(chain).expect("server started");
+
+    let url = format!("{}/_child/kids?chainhead", server.ws_url());
+    let items = json!(sube::sube(&url).await.unwrap());
+    let items = items.as_array().unwrap();
+    assert_eq!(items.len(), 1500);
+    assert_eq!(items[1499], json!(["0x000005db", "0xdb050000"]));
+
+    // the pages of keys come from a single operation, the values are read in batches
+    let requests = server.requests();
+    let count = |method: &str| requests.iter().filter(|m| *m == method).count();
+    assert_eq!(count("chainHead_v1_storage"), 3);
+}
+
+#[tokio::test]
+async fn chain_head_submit() {
+    let server = server();
+    let backend = chain_head(&server).await;
+
+    let included = async {
+        while server.submitted().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        server.new_block();
+    };
+    let (hash, _) = tokio::join!(backend.submit([0x04, 0x00]), included);
+    assert_eq!(
+        hash.unwrap(),
+        <[u8; 32]>::from(Blake2b::<U32>::digest([0x04, 0x00]))
+    );
+    assert_eq!(server.submitted(), vec![vec![0x04, 0x00]]);
+    assert!(server
+        .requests()
+        .iter()
+        .any(|m| m == "transactionWatch_v1_unwatch"));
+}
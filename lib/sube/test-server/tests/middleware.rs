@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use sube::{
+    middleware::{Middleware, Retry},
+    BackendConfig,
+};
+use sube_test_server::{fixtures, Chain, Fault, TestServer};
+
+fn server() -> TestServer {
+    TestServer::start(Chain::new(fixtures::metadata())).expect("server started")
+}
+
+fn retry(max_retries: u32) -> Retry {
+    Retry {
+        max_retries,
+        min_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+        ..Default::default()
+    }
+}
+
+fn count(server: &TestServer, method: &str) -> usize {
+    server.requests().iter().filter(|m| *m == method).count()
+}
+
+#[tokio::test]
+async fn builder_backends_use_the_middleware() {
+    let server = server();
+    let middleware = Middleware {
+        retry: Some(retry(3)),
+        ..Default::default()
+    };
+
+    server.inject("state_getMetadata", Fault::Disconnect);
+    let url = format!("{}/system/number", server.http_url());
+    let number = sube::sube(&url)
+        .with_config(BackendConfig::default().with_middleware(middleware))
+        .await;
+    assert!(number.is_ok(), "{:?}", number);
+    assert_eq!(count(&server, "state_getMetadata"), 2);
+}